@@ -346,6 +346,14 @@ fun copyRustBuild() {
 
     Files.copy(originFile.toPath(), FileOutputStream(destinyFile))
 
+    // The fsmonitor hook talks through unix sockets, so it's only useful outside of Windows
+    if (currentOs() != OS.WINDOWS) {
+        val originHookFile = File(workingDir, fsMonitorHookName)
+        val destinyHookFile = File(directory, fsMonitorHookName)
+
+        Files.copy(originHookFile.toPath(), FileOutputStream(destinyHookFile))
+    }
+
     println("Copy rs build completed")
 }
 
@@ -353,6 +361,8 @@ fun findBinaryInPath(binaryName: String): String? {
     return findBinary(System.getenv("PATH").split(":"), binaryName)
 }
 
+val fsMonitorHookName = "gitnuro-fsmonitor-hook"
+
 val libName = when (currentOs()) {
     OS.LINUX -> "libgitnuro_rs.so"
     OS.WINDOWS -> "gitnuro_rs.dll"
@@ -29,9 +29,12 @@ import com.jetpackduba.gitnuro.avatarproviders.GravatarAvatarProvider
 import com.jetpackduba.gitnuro.avatarproviders.NoneAvatarProvider
 import com.jetpackduba.gitnuro.common.OS
 import com.jetpackduba.gitnuro.common.currentOs
+import com.jetpackduba.gitnuro.common.printError
 import com.jetpackduba.gitnuro.common.systemSeparator
 import com.jetpackduba.gitnuro.data.git.signers.AppGpgSigner
 import com.jetpackduba.gitnuro.data.git.signers.SshSigner
+import com.jetpackduba.gitnuro.domain.AppFilesManager
+import com.jetpackduba.gitnuro.domain.FsMonitorConstants
 import com.jetpackduba.gitnuro.domain.TempFilesManager
 import com.jetpackduba.gitnuro.domain.credentials.CredentialsRequest
 import com.jetpackduba.gitnuro.domain.models.*
@@ -83,6 +86,8 @@ sealed interface Screen : NavKey {
     data object SubmoduleAdd : Screen
     data object HttpCredentials : Screen
     data class SshCredentials(val credentialsRequest: CredentialsRequest.SshCredentialsRequest) : Screen
+    data class SshHostKey(val hostKeyRequest: CredentialsRequest.SshHostKeyRequest) : Screen
+    data class SshInteractiveAuth(val interactiveAuthRequest: CredentialsRequest.SshInteractiveAuthRequest) : Screen
     data class GpgCredentials(val credentialsRequest: CredentialsRequest.GpgCredentialsRequest) : Screen
     data object LfsCredentials : Screen
     data object QuickActions : Screen
@@ -98,6 +103,7 @@ class App @Inject constructor(
     private val appEnvInfo: AppEnvInfo,
     private val appViewModel: AppViewModel,
     private val tempFilesManager: TempFilesManager,
+    private val appFilesManager: AppFilesManager,
     private val logsRepository: LogsRepository,
     private val gpgSigner: AppGpgSigner,
     private val sshSigner: SshSigner,
@@ -251,6 +257,44 @@ class App @Inject constructor(
 
             System.load(gitnuroRsFile.absolutePath)
         } ?: throw Exception("GitnuroRs native dependency not found")
+
+        setNativeLogger(AppNativeLogger())
+
+        extractFsMonitorHook()
+    }
+
+    /**
+     * Extracts the `core.fsmonitor` hook to [AppFilesManager.getFsMonitorHookFile]. Git may be running the hook of a
+     * previous session, so it's only replaced when its content changes, and through a rename instead of writing into
+     * the file being executed.
+     */
+    private fun extractFsMonitorHook() {
+        if (currentOs == OS.WINDOWS) {
+            return
+        }
+
+        try {
+            val hook = javaClass.getResourceAsStream("/${FsMonitorConstants.HOOK_NAME}")?.use { inputStream ->
+                inputStream.readBytes()
+            } ?: return
+
+            val hookFile = appFilesManager.getFsMonitorHookFile()
+
+            if (hookFile.canExecute() && hookFile.readBytes().contentEquals(hook)) {
+                return
+            }
+
+            val tempHookFile = File(hookFile.parentFile, "${hookFile.name}.tmp")
+            tempHookFile.writeBytes(hook)
+            tempHookFile.setExecutable(true)
+
+            if (!tempHookFile.renameTo(hookFile)) {
+                tempHookFile.delete()
+                printError(TAG, "The fsmonitor hook could not be replaced")
+            }
+        } catch (ex: Exception) {
+            printError(TAG, "Extracting the fsmonitor hook failed", ex)
+        }
     }
 
     private fun initProxySettings() {
@@ -0,0 +1,18 @@
+package com.jetpackduba.gitnuro
+
+import com.jetpackduba.gitnuro.common.printDebug
+import com.jetpackduba.gitnuro.common.printError
+import com.jetpackduba.gitnuro.common.printLog
+
+/**
+ * Forwards the messages of the native library to the app logger.
+ */
+class AppNativeLogger : NativeLogger {
+    override fun log(level: LogLevel, tag: String, message: String) {
+        when (level) {
+            LogLevel.DEBUG -> printDebug(tag, message)
+            LogLevel.INFO -> printLog(tag, message)
+            LogLevel.ERROR -> printError(tag, message)
+        }
+    }
+}
@@ -5,7 +5,9 @@ import com.jetpackduba.gitnuro.data.git.GetWorktreePathGitAction
 import com.jetpackduba.gitnuro.data.git.author.LoadAuthorGitAction
 import com.jetpackduba.gitnuro.data.git.author.SaveAuthorGitAction
 import com.jetpackduba.gitnuro.data.git.branches.*
+import com.jetpackduba.gitnuro.data.git.config.LoadFsMonitorHookGitAction
 import com.jetpackduba.gitnuro.data.git.config.LoadSignOffConfigGitAction
+import com.jetpackduba.gitnuro.data.git.config.SaveFsMonitorHookGitAction
 import com.jetpackduba.gitnuro.data.git.config.SaveLocalRepositoryConfigGitAction
 import com.jetpackduba.gitnuro.data.git.diff.*
 import com.jetpackduba.gitnuro.data.git.lfs.*
@@ -292,6 +294,10 @@ interface TabScopeGitActionsModule {
     @TabScope
     fun bindsLoadAuthorGitAction(action: LoadAuthorGitAction): ILoadAuthorGitAction
 
+    @Binds
+    @TabScope
+    fun bindsLoadFsMonitorHookGitAction(action: LoadFsMonitorHookGitAction): ILoadFsMonitorHookGitAction
+
     @Binds
     @TabScope
     fun bindsLoadSignOffConfigGitAction(action: LoadSignOffConfigGitAction): ILoadSignOffConfigGitAction
@@ -356,6 +362,10 @@ interface TabScopeGitActionsModule {
     @TabScope
     fun bindsSaveAuthorGitAction(action: SaveAuthorGitAction): ISaveAuthorGitAction
 
+    @Binds
+    @TabScope
+    fun bindsSaveFsMonitorHookGitAction(action: SaveFsMonitorHookGitAction): ISaveFsMonitorHookGitAction
+
     @Binds
     @TabScope
     fun bindsSaveLocalRepositoryConfigGitAction(action: SaveLocalRepositoryConfigGitAction): ISaveLocalRepositoryConfigGitAction
@@ -12,6 +12,7 @@ import androidx.compose.ui.focus.FocusRequester
 import androidx.compose.ui.focus.focusRequester
 import androidx.compose.ui.input.key.onPreviewKeyEvent
 import androidx.compose.ui.platform.LocalDensity
+import androidx.compose.ui.platform.LocalWindowInfo
 import androidx.compose.ui.unit.dp
 import com.jetpackduba.gitnuro.LocalTabFocusRequester
 import com.jetpackduba.gitnuro.Screen
@@ -169,6 +170,15 @@ fun RepositoryOpenPage(
     LaunchedEffect(repositoryOpenViewModel) {
         focusRequester.requestFocus()
     }
+
+    // Events can be missed while the system is suspended, so the files are compared again when coming back to the app
+    val isWindowFocused = LocalWindowInfo.current.isWindowFocused
+
+    LaunchedEffect(isWindowFocused) {
+        if (isWindowFocused) {
+            repositoryOpenViewModel.rescanWatchedFiles()
+        }
+    }
 }
 
 @Composable
@@ -139,6 +139,7 @@ class RepositoryOpenViewModel @Inject constructor(
     private val fetchAllUseCase: FetchAllBranchUseCase,
     private val stashChangesUseCase: StashChangesUseCase,
     private val openRepositoryInTerminalGitAction: OpenRepositoryInTerminalGitAction,
+    private val rescanWatchedFilesUseCase: RescanWatchedFilesUseCase,
 ) : IVerticalSplitPaneConfig by verticalSplitPaneConfig,
     TabViewModel() {
     val completedTasks = repositoryStateRepository.completedTasks
@@ -710,6 +711,10 @@ class RepositoryOpenViewModel @Inject constructor(
         openUrlInBrowserUseCase(url)
     }
 
+    fun rescanWatchedFiles() {
+        rescanWatchedFilesUseCase()
+    }
+
     var savedSearchFilter: String = ""
     var graphPadding = 0f
 
@@ -107,6 +107,8 @@ fun AppTab(
             CredentialsRequest.HttpCredentialsRequest -> Screen.HttpCredentials
             CredentialsRequest.LfsCredentialsRequest -> Screen.LfsCredentials
             is CredentialsRequest.SshCredentialsRequest -> Screen.SshCredentials(state)
+            is CredentialsRequest.SshHostKeyRequest -> Screen.SshHostKey(state)
+            is CredentialsRequest.SshInteractiveAuthRequest -> Screen.SshInteractiveAuth(state)
             else -> null
         }
 
@@ -265,6 +267,36 @@ fun AppTab(
                                 }
                             )
                         }
+                        entry<Screen.SshHostKey>(
+                            metadata = dialogsMetadata
+                        ) { entry ->
+                            SshHostKeyDialog(
+                                hostKeyRequest = entry.hostKeyRequest,
+                                onReject = {
+                                    repositoryTabViewModel.credentialsDenied()
+                                    backStack.removeLastOrNull()
+                                },
+                                onAccept = {
+                                    repositoryTabViewModel.sshHostKeyAccepted()
+                                    backStack.removeLastOrNull()
+                                }
+                            )
+                        }
+                        entry<Screen.SshInteractiveAuth>(
+                            metadata = dialogsMetadata
+                        ) { entry ->
+                            SshInteractiveAuthDialog(
+                                interactiveAuthRequest = entry.interactiveAuthRequest,
+                                onReject = {
+                                    repositoryTabViewModel.credentialsDenied()
+                                    backStack.removeLastOrNull()
+                                },
+                                onAccept = { answers ->
+                                    repositoryTabViewModel.sshInteractiveAuthAccepted(answers)
+                                    backStack.removeLastOrNull()
+                                }
+                            )
+                        }
                         entry<Screen.GpgCredentials>(
                             metadata = dialogsMetadata
                         ) { entry ->
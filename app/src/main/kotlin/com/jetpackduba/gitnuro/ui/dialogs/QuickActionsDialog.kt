@@ -42,6 +42,7 @@ fun QuickActionsDialog(
             QuickAction(Res.drawable.download, "Clone new repository", QuickActionType.CLONE),
             QuickAction(Res.drawable.refresh, "Refresh repository data", QuickActionType.REFRESH),
             QuickAction(Res.drawable.sign, "Signoff config", QuickActionType.SIGN_OFF),
+            QuickAction(Res.drawable.bolt, "Toggle git fsmonitor for this repository", QuickActionType.FS_MONITOR),
         )
     }
 
@@ -65,6 +66,7 @@ fun QuickActionsDialog(
                 QuickActionType.CLONE -> onShowClone()
                 QuickActionType.REFRESH -> viewModel.refreshRepository()
                 QuickActionType.SIGN_OFF -> onShowSignOff()
+                QuickActionType.FS_MONITOR -> viewModel.toggleFsMonitor()
             }
             
             onDismiss()
@@ -165,5 +167,6 @@ enum class QuickActionType {
     OPEN_DIR_IN_FILE_MANAGER,
     CLONE,
     REFRESH,
-    SIGN_OFF
+    SIGN_OFF,
+    FS_MONITOR,
 }
\ No newline at end of file
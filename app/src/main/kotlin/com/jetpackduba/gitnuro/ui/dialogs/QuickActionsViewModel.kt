@@ -4,8 +4,10 @@ import com.jetpackduba.gitnuro.TabViewModel
 import com.jetpackduba.gitnuro.domain.errors.Either
 import com.jetpackduba.gitnuro.domain.usecases.DataToRefresh
 import com.jetpackduba.gitnuro.domain.usecases.GetWorktreeUseCase
+import com.jetpackduba.gitnuro.domain.usecases.LoadFsMonitorEnabledUseCase
 import com.jetpackduba.gitnuro.domain.usecases.OpenPathInSystemUseCase
 import com.jetpackduba.gitnuro.domain.usecases.RefreshDataUseCase
+import com.jetpackduba.gitnuro.domain.usecases.SaveFsMonitorEnabledUseCase
 import kotlinx.coroutines.launch
 import javax.inject.Inject
 
@@ -13,6 +15,8 @@ class QuickActionsViewModel @Inject constructor(
     private val refreshDataUseCase: RefreshDataUseCase,
     private val getWorktreeUseCase: GetWorktreeUseCase,
     private val openPathInSystemUseCase: OpenPathInSystemUseCase,
+    private val loadFsMonitorEnabledUseCase: LoadFsMonitorEnabledUseCase,
+    private val saveFsMonitorEnabledUseCase: SaveFsMonitorEnabledUseCase,
 ) : TabViewModel() {
 
     // TODO Implement bunch of methods
@@ -28,4 +32,14 @@ class QuickActionsViewModel @Inject constructor(
             }
         }
     }
+
+    fun toggleFsMonitor() {
+        viewModelScope.launch {
+            val isEnabled = loadFsMonitorEnabledUseCase()
+
+            if (isEnabled is Either.Ok) {
+                saveFsMonitorEnabledUseCase(!isEnabled.value)
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+package com.jetpackduba.gitnuro.ui.dialogs
+
+import androidx.compose.foundation.layout.padding
+import androidx.compose.material.MaterialTheme
+import androidx.compose.material.Text
+import androidx.compose.runtime.Composable
+import androidx.compose.ui.Modifier
+import androidx.compose.ui.text.font.FontFamily
+import androidx.compose.ui.unit.dp
+import com.jetpackduba.gitnuro.app.generated.resources.Res
+import com.jetpackduba.gitnuro.app.generated.resources.security
+import com.jetpackduba.gitnuro.app.generated.resources.warning
+import com.jetpackduba.gitnuro.domain.credentials.CredentialsRequest
+import com.jetpackduba.gitnuro.ui.dialogs.base.IconBasedDialog
+import org.jetbrains.compose.resources.painterResource
+
+@Composable
+fun SshHostKeyDialog(
+    onReject: () -> Unit,
+    onAccept: () -> Unit,
+    hostKeyRequest: CredentialsRequest.SshHostKeyRequest,
+) {
+    val host = "${hostKeyRequest.host}:${hostKeyRequest.port}"
+
+    val (title, subtitle) = if (hostKeyRequest.isChanged) {
+        "The host key of $host has changed" to
+                "It doesn't match the one in your known hosts, someone could be intercepting the connection.\nOnly continue if you know the key was replaced."
+    } else {
+        "Unknown host $host" to
+                "The authenticity of the host can't be established.\nCheck that its key fingerprint is the expected one."
+    }
+
+    IconBasedDialog(
+        icon = painterResource(if (hostKeyRequest.isChanged) Res.drawable.warning else Res.drawable.security),
+        title = title,
+        subtitle = subtitle,
+        primaryActionText = "Trust host",
+        onDismiss = onReject,
+        onPrimaryActionClicked = onAccept,
+        beforeActionsFocusRequester = null,
+        actionsFocusRequester = null,
+        afterActionsFocusRequester = null,
+    ) {
+        Text(
+            text = hostKeyRequest.fingerprint,
+            modifier = Modifier.padding(bottom = 8.dp),
+            color = MaterialTheme.colors.onBackground,
+            style = MaterialTheme.typography.body2,
+            fontFamily = FontFamily.Monospace,
+        )
+    }
+}
@@ -0,0 +1,86 @@
+package com.jetpackduba.gitnuro.ui.dialogs
+
+import androidx.compose.foundation.layout.padding
+import androidx.compose.foundation.layout.width
+import androidx.compose.runtime.*
+import androidx.compose.ui.Modifier
+import androidx.compose.ui.focus.FocusRequester
+import androidx.compose.ui.focus.focusRequester
+import androidx.compose.ui.input.key.onPreviewKeyEvent
+import androidx.compose.ui.text.input.PasswordVisualTransformation
+import androidx.compose.ui.text.input.VisualTransformation
+import androidx.compose.ui.unit.dp
+import com.jetpackduba.gitnuro.app.generated.resources.Res
+import com.jetpackduba.gitnuro.app.generated.resources.generic_button_continue
+import com.jetpackduba.gitnuro.app.generated.resources.lock
+import com.jetpackduba.gitnuro.domain.credentials.CredentialsRequest
+import com.jetpackduba.gitnuro.keybindings.KeybindingOption
+import com.jetpackduba.gitnuro.keybindings.matchesBinding
+import com.jetpackduba.gitnuro.theme.outlinedTextFieldColors
+import com.jetpackduba.gitnuro.ui.components.AdjustableOutlinedTextField
+import com.jetpackduba.gitnuro.ui.dialogs.base.IconBasedDialog
+import org.jetbrains.compose.resources.painterResource
+import org.jetbrains.compose.resources.stringResource
+
+@Composable
+fun SshInteractiveAuthDialog(
+    onReject: () -> Unit,
+    onAccept: (answers: List<String>) -> Unit,
+    interactiveAuthRequest: CredentialsRequest.SshInteractiveAuthRequest,
+) {
+    val prompts = interactiveAuthRequest.prompts
+    val answers = remember(interactiveAuthRequest) { mutableStateListOf(*Array(prompts.count()) { "" }) }
+    val focusRequesters = remember(interactiveAuthRequest) { List(prompts.count()) { FocusRequester() } }
+    val acceptDialog = {
+        onAccept(answers.toList())
+    }
+
+    IconBasedDialog(
+        icon = painterResource(Res.drawable.lock),
+        title = interactiveAuthRequest.name.ifBlank { "SSH authentication" },
+        subtitle = interactiveAuthRequest.instruction.ifBlank { "The server requires additional information" },
+        primaryActionText = stringResource(Res.string.generic_button_continue),
+        onDismiss = onReject,
+        onPrimaryActionClicked = acceptDialog,
+        beforeActionsFocusRequester = null,
+        actionsFocusRequester = null,
+        afterActionsFocusRequester = null,
+    ) {
+        prompts.forEachIndexed { index, prompt ->
+            AdjustableOutlinedTextField(
+                modifier = Modifier
+                    .padding(bottom = 8.dp)
+                    .focusRequester(focusRequesters[index])
+                    .width(300.dp)
+                    .onPreviewKeyEvent { keyEvent ->
+                        if (keyEvent.matchesBinding(KeybindingOption.SIMPLE_ACCEPT)) {
+                            val nextFocusRequester = focusRequesters.getOrNull(index + 1)
+
+                            if (nextFocusRequester != null) {
+                                nextFocusRequester.requestFocus()
+                            } else {
+                                acceptDialog()
+                            }
+
+                            true
+                        } else {
+                            false
+                        }
+                    },
+                value = answers[index],
+                colors = outlinedTextFieldColors(),
+                maxLines = 1,
+                singleLine = true,
+                hint = prompt.prompt.trim(),
+                onValueChange = {
+                    answers[index] = it
+                },
+                visualTransformation = if (prompt.echo) VisualTransformation.None else PasswordVisualTransformation(),
+            )
+        }
+
+        LaunchedEffect(interactiveAuthRequest) {
+            focusRequesters.firstOrNull()?.requestFocus()
+        }
+    }
+}
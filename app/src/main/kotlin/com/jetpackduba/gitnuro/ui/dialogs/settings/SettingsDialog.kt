@@ -449,6 +449,15 @@ private fun Environment(settingsViewState: SettingsViewState, onAction: (Setting
             onAction(SettingsAction.SetConfig(AppConfig.CloneDefaultDirectory(value)))
         },
     )
+
+    SettingToggle(
+        title = "Keep a journal of the file changes",
+        subtitle = "Changes made while a repository is closed are detected when it's opened again. Applies to the repositories opened afterwards",
+        value = settingsViewState.changeJournal,
+        onValueChanged = { value ->
+            onAction(SettingsAction.SetConfig(AppConfig.ChangeJournal(value)))
+        }
+    )
 }
 
 @Composable
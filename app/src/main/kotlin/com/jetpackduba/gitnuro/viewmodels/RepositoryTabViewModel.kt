@@ -197,6 +197,14 @@ class RepositoryTabViewModel @AssistedInject constructor(
         credentialsStateManager.sshCredentialsAccepted(password)
     }
 
+    fun sshHostKeyAccepted() {
+        credentialsStateManager.sshHostKeyAccepted()
+    }
+
+    fun sshInteractiveAuthAccepted(answers: List<String>) {
+        credentialsStateManager.sshInteractiveAuthAccepted(answers)
+    }
+
     fun gpgCredentialsAccepted(password: String) {
         credentialsStateManager.gpgCredentialsAccepted(password)
     }
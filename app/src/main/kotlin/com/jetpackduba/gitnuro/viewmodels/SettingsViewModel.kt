@@ -62,6 +62,7 @@ class SettingsViewModel @Inject constructor(
             appSettingsService.pushWithLease,
             appSettingsService.fastForwardMerge,
             appSettingsService.autoStashOnMerge,
+            appSettingsService.changeJournal,
             appSettingsService.cloneDefaultDirectory,
             appSettingsService.useProxy,
             appSettingsService.proxyUseAuth,
@@ -87,6 +88,7 @@ class SettingsViewModel @Inject constructor(
             pushWithLease,
             fastForwardMerge,
             autoStashOnMerge,
+            changeJournal,
             cloneDefaultDirectory,
             useProxy,
             proxyUseAuth,
@@ -114,6 +116,7 @@ class SettingsViewModel @Inject constructor(
                 pushWithLease,
                 fastForwardMerge,
                 autoStashOnMerge,
+                changeJournal,
                 cloneDefaultDirectory,
                 useProxy,
                 proxyUseAuth,
@@ -145,6 +148,7 @@ class SettingsViewModel @Inject constructor(
             pushWithLease = false,
             fastForwardMerge = false,
             autoStashOnMerge = false,
+            changeJournal = false,
             cloneDefaultDirectory = "",
             useProxy = false,
             proxyUseAuth = false,
@@ -176,6 +180,7 @@ data class SettingsViewState(
     val pushWithLease: Boolean,
     val fastForwardMerge: Boolean,
     val autoStashOnMerge: Boolean,
+    val changeJournal: Boolean,
     val cloneDefaultDirectory: String?,
     val useProxy: Boolean,
     val proxyUseAuth: Boolean,
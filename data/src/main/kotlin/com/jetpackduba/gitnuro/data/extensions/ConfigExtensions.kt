@@ -0,0 +1,29 @@
+package com.jetpackduba.gitnuro.data.extensions
+
+import org.eclipse.jgit.lib.Config
+import org.eclipse.jgit.lib.ConfigConstants
+import java.io.File
+import java.nio.file.FileSystems
+
+/**
+ * Global excludes file set with `core.excludesFile`, or null if it's not set. The file may not exist.
+ */
+val Config.excludesFile: File?
+    get() {
+        var excludesFilePath = getString(ConfigConstants.CONFIG_CORE_SECTION, null, ConfigConstants.CONFIG_KEY_EXCLUDESFILE)
+            ?: ""
+
+        if (excludesFilePath.isEmpty()) {
+            return null
+        }
+
+        if (excludesFilePath.startsWith("~")) {
+            excludesFilePath = excludesFilePath.replace("~", System.getProperty("user.home").orEmpty())
+        }
+
+        return FileSystems
+            .getDefault()
+            .getPath(excludesFilePath)
+            .normalize()
+            .toFile()
+    }
@@ -1,9 +1,17 @@
 package com.jetpackduba.gitnuro.data.git
 
+import com.jetpackduba.gitnuro.BatchInfo
+import com.jetpackduba.gitnuro.BulkChange
 import com.jetpackduba.gitnuro.FileChanged
 import com.jetpackduba.gitnuro.FileWatcher
+import com.jetpackduba.gitnuro.JournalQueryResult
+import com.jetpackduba.gitnuro.WatchBackend
 import com.jetpackduba.gitnuro.WatchDirectoryNotifier
+import com.jetpackduba.gitnuro.WatchPriority
 import com.jetpackduba.gitnuro.common.TabScope
+import com.jetpackduba.gitnuro.common.printError
+import com.jetpackduba.gitnuro.common.printLog
+import com.jetpackduba.gitnuro.data.extensions.excludesFile
 import com.jetpackduba.gitnuro.domain.interfaces.IFileChangesWatcher
 import com.jetpackduba.gitnuro.domain.models.WatcherEvent
 import kotlinx.coroutines.channels.awaitClose
@@ -11,6 +19,11 @@ import kotlinx.coroutines.channels.trySendBlocking
 import kotlinx.coroutines.flow.Flow
 import kotlinx.coroutines.flow.callbackFlow
 import kotlinx.coroutines.isActive
+import org.eclipse.jgit.lib.Constants
+import org.eclipse.jgit.storage.file.FileBasedConfig
+import org.eclipse.jgit.util.FS
+import org.eclipse.jgit.util.SystemReader
+import java.io.File
 import javax.inject.Inject
 
 private const val TAG = "FileChangesWatcher"
@@ -23,22 +36,79 @@ class FileChangesWatcher @Inject constructor() : AutoCloseable, IFileChangesWatc
     init {
         // TODO add error handling
         fileWatcher.init()
+        // The default bulk change threshold is kept: batches of more than 10,000 paths are reported as
+        // BulkChangesDetected with a summary per directory instead of every path.
     }
 
-    override fun addPathToWatch(path: String, isRecursive: Boolean) {
-        fileWatcher.addWatch(path, isRecursive)
+    override fun addPathToWatch(path: String, isRecursive: Boolean, isHighPriority: Boolean) {
+        val priority = if (isHighPriority) WatchPriority.HIGH else WatchPriority.NORMAL
+        fileWatcher.addWatchWithPriority(path, isRecursive, priority)
+
+        if (fileWatcher.watchBackend(path) == WatchBackend.POLLING) {
+            printLog(TAG, "$path is on a network or FUSE filesystem, its changes will be polled")
+        }
     }
 
     override fun removePathFromWatch(path: String) {
         fileWatcher.removeWatch(path)
     }
 
+    override fun trackIgnoreRules(worktreePath: String, gitDirPath: String) {
+        // When core.excludesFile is not set, the watcher uses git's default location
+        val excludesFile = try {
+            val config = FileBasedConfig(
+                SystemReader.getInstance().userConfig,
+                File(gitDirPath, Constants.CONFIG),
+                FS.DETECTED,
+            )
+            config.load()
+            config.excludesFile
+        } catch (ex: Exception) {
+            printError(TAG, "Reading core.excludesFile failed", ex)
+            null
+        }
+
+        fileWatcher.trackIgnoreRules(worktreePath, gitDirPath, excludesFile?.absolutePath)
+    }
+
+    override fun rescan() {
+        fileWatcher.rescan()
+    }
+
+    override fun refreshWatchedDirectories() {
+        fileWatcher.refreshWatchedDirectories()
+    }
+
+    override fun enableJournal(journalPath: String, worktreePath: String): Boolean {
+        return fileWatcher.enableJournal(journalPath, worktreePath) == 0
+    }
+
+    override fun journalClock(): String? = fileWatcher.journalClock()
+
+    override fun journalChangesSince(clock: String): JournalQueryResult? = fileWatcher.journalChangesSince(clock)
+
+    override fun startFsMonitor(gitDirPath: String): Boolean {
+        return fileWatcher.startFsmonitor(gitDirPath) == 0
+    }
+
+    override fun stopFsMonitor() {
+        fileWatcher.stopFsmonitor()
+    }
+
     override suspend fun observeEvents(): Flow<WatcherEvent> = callbackFlow {
         fileWatcher.watch(
             notifier = object : WatchDirectoryNotifier {
                 override fun shouldKeepLooping(): Boolean = coroutineContext.isActive && shouldKeepLooping
-                override fun detectedChange(paths: List<FileChanged>) {
-                    trySendBlocking(WatcherEvent.ChangesDetected(paths))
+                override fun detectedChange(paths: List<FileChanged>, batch: BatchInfo) {
+                    trySendBlocking(WatcherEvent.ChangesDetected(paths, batch))
+                }
+
+                override fun detectedBulkChange(bulkChange: BulkChange, batch: BatchInfo) {
+                    trySendBlocking(WatcherEvent.BulkChangesDetected(bulkChange, batch))
+                }
+
+                override fun ignoreRulesChanged() {
+                    trySendBlocking(WatcherEvent.IgnoreRulesChanged)
                 }
 
                 override fun onError(code: Int) {
@@ -0,0 +1,22 @@
+package com.jetpackduba.gitnuro.data.git.config
+
+import com.jetpackduba.gitnuro.data.git.JGit
+import com.jetpackduba.gitnuro.domain.FsMonitorConstants
+import com.jetpackduba.gitnuro.domain.extensions.nullIfEmpty
+import com.jetpackduba.gitnuro.domain.interfaces.ILoadFsMonitorHookGitAction
+import javax.inject.Inject
+
+class LoadFsMonitorHookGitAction @Inject constructor(
+    private val jgit: JGit,
+) : ILoadFsMonitorHookGitAction {
+    override suspend operator fun invoke(repositoryPath: String) = jgit.provide(repositoryPath) { git ->
+        val config = git.repository.config
+        config.load()
+
+        config.getString(
+            FsMonitorConstants.SECTION,
+            null,
+            FsMonitorConstants.FIELD_HOOK,
+        )?.nullIfEmpty
+    }
+}
@@ -0,0 +1,33 @@
+package com.jetpackduba.gitnuro.data.git.config
+
+import com.jetpackduba.gitnuro.data.git.JGit
+import com.jetpackduba.gitnuro.domain.FsMonitorConstants
+import com.jetpackduba.gitnuro.domain.interfaces.ISaveFsMonitorHookGitAction
+import javax.inject.Inject
+
+class SaveFsMonitorHookGitAction @Inject constructor(
+    private val jgit: JGit,
+) : ISaveFsMonitorHookGitAction {
+    override suspend operator fun invoke(
+        repositoryPath: String,
+        hookPath: String?,
+    ) = jgit.provide(repositoryPath) { git ->
+        val config = git.repository.config
+        config.load()
+
+        if (hookPath != null) {
+            config.setString(FsMonitorConstants.SECTION, null, FsMonitorConstants.FIELD_HOOK, hookPath)
+            config.setInt(
+                FsMonitorConstants.SECTION,
+                null,
+                FsMonitorConstants.FIELD_HOOK_VERSION,
+                FsMonitorConstants.HOOK_VERSION
+            )
+        } else {
+            config.unset(FsMonitorConstants.SECTION, null, FsMonitorConstants.FIELD_HOOK)
+            config.unset(FsMonitorConstants.SECTION, null, FsMonitorConstants.FIELD_HOOK_VERSION)
+        }
+
+        config.save()
+    }
+}
@@ -0,0 +1,36 @@
+package com.jetpackduba.gitnuro.data.git.credentials
+
+import com.jetpackduba.gitnuro.HostKeyDecision
+import com.jetpackduba.gitnuro.HostKeyInfo
+import com.jetpackduba.gitnuro.HostKeyStatus
+import com.jetpackduba.gitnuro.HostKeyVerifier
+import com.jetpackduba.gitnuro.domain.credentials.CredentialsStateManager
+import kotlinx.coroutines.runBlocking
+import javax.inject.Inject
+import kotlin.coroutines.cancellation.CancellationException
+
+class SshHostKeyVerifier @Inject constructor(
+    private val credentialsStateManager: CredentialsStateManager,
+) : HostKeyVerifier {
+    override fun verifyHostKey(hostKey: HostKeyInfo): HostKeyDecision {
+        return try {
+            runBlocking {
+                credentialsStateManager.requestSshHostKeyConfirmation(
+                    host = hostKey.host,
+                    port = hostKey.port.toInt(),
+                    fingerprint = hostKey.fingerprint,
+                    isChanged = hostKey.status != HostKeyStatus.UNKNOWN,
+                )
+            }
+
+            // A changed key is only trusted for this connection, replacing the stored one must not happen implicitly
+            if (hostKey.status == HostKeyStatus.UNKNOWN) {
+                HostKeyDecision.ACCEPT_AND_SAVE
+            } else {
+                HostKeyDecision.ACCEPT_ONCE
+            }
+        } catch (ex: CancellationException) {
+            HostKeyDecision.REJECT
+        }
+    }
+}
@@ -0,0 +1,31 @@
+package com.jetpackduba.gitnuro.data.git.credentials
+
+import com.jetpackduba.gitnuro.InteractiveAuthPrompter
+import com.jetpackduba.gitnuro.InteractiveAuthRequest
+import com.jetpackduba.gitnuro.domain.credentials.CredentialsRequest
+import com.jetpackduba.gitnuro.domain.credentials.CredentialsStateManager
+import kotlinx.coroutines.runBlocking
+import javax.inject.Inject
+import kotlin.coroutines.cancellation.CancellationException
+
+class SshInteractiveAuthPrompter @Inject constructor(
+    private val credentialsStateManager: CredentialsStateManager,
+) : InteractiveAuthPrompter {
+    override fun answerPrompts(request: InteractiveAuthRequest): List<String>? {
+        return try {
+            val accepted = runBlocking {
+                credentialsStateManager.requestSshInteractiveAnswers(
+                    name = request.name,
+                    instruction = request.instruction,
+                    prompts = request.prompts.map { prompt ->
+                        CredentialsRequest.SshInteractivePrompt(prompt.prompt, prompt.echo)
+                    },
+                )
+            }
+
+            accepted.answers
+        } catch (ex: CancellationException) {
+            null
+        }
+    }
+}
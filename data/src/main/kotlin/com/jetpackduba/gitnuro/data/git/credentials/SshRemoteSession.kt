@@ -1,9 +1,19 @@
 package com.jetpackduba.gitnuro.data.git.credentials
 
+import com.jetpackduba.gitnuro.AuthMethod
+import com.jetpackduba.gitnuro.AuthStatus
 import com.jetpackduba.gitnuro.Session
+import com.jetpackduba.gitnuro.SessionOptions
+import com.jetpackduba.gitnuro.SshAgent
+import com.jetpackduba.gitnuro.SshException as SshError
+import com.jetpackduba.gitnuro.common.printDebug
+import com.jetpackduba.gitnuro.common.printError
+import com.jetpackduba.gitnuro.common.printLog
+import com.jetpackduba.gitnuro.defaultSessionOptions
 import com.jetpackduba.gitnuro.domain.credentials.SshProcess
 import com.jetpackduba.gitnuro.domain.exceptions.SshException
-import com.jetpackduba.gitnuro.domain.extensions.throwIfSshMessage
+import com.jetpackduba.gitnuro.domain.extensions.rethrowSshError
+import com.jetpackduba.gitnuro.resolveJumpHosts
 import org.eclipse.jgit.transport.CredentialItem
 import org.eclipse.jgit.transport.CredentialsProvider
 import org.eclipse.jgit.transport.RemoteSession
@@ -11,13 +21,23 @@ import org.eclipse.jgit.transport.URIish
 import javax.inject.Inject
 
 
+private const val TAG = "SshRemoteSession"
 private const val NOT_EXPLICIT_PORT = -1
+private const val CONNECT_TIMEOUT_MS = 30_000UL
+private const val AUTH_TIMEOUT_MS = 60_000UL
 
-class SshRemoteSession @Inject constructor() : RemoteSession {
+class SshRemoteSession @Inject constructor(
+    private val hostKeyVerifier: SshHostKeyVerifier,
+    private val interactiveAuthPrompter: SshInteractiveAuthPrompter,
+) : RemoteSession {
     private lateinit var session: Session
     private lateinit var process: SshProcess
+
+    /** Sessions of the jump hosts, each one connected through the previous one. */
+    private val jumpSessions = mutableListOf<Session>()
+
     override fun exec(commandName: String, timeout: Int): Process {
-        println("Running command $commandName")
+        printDebug(TAG, "Running command $commandName")
 
         process = SshProcess()
 
@@ -30,9 +50,46 @@ class SshRemoteSession @Inject constructor() : RemoteSession {
         process.closeChannel()
         session.disconnect()
         session.close()
+        disconnectJumpSessions()
     }
 
     fun setup(uri: URIish, sshCredentialsProvider: CredentialsProvider) {
+        val options = defaultSessionOptions().copy(
+            connectTimeoutMs = CONNECT_TIMEOUT_MS,
+            authTimeoutMs = AUTH_TIMEOUT_MS,
+        )
+
+        val jumpHosts = rethrowSshError { resolveJumpHosts(uri.host, options) }
+
+        try {
+            for (jumpHost in jumpHosts) {
+                val jumpHostUri = uri
+                    .setHost(jumpHost.host)
+                    .setUser(jumpHost.user)
+                    .setPort(jumpHost.port?.toInt() ?: NOT_EXPLICIT_PORT)
+
+                printLog(TAG, "Connecting through jump host ${jumpHostUri.host}")
+
+                jumpSessions.add(connect(jumpHostUri, sshCredentialsProvider, options, jumpSessions.lastOrNull()))
+            }
+
+            this.session = connect(uri, sshCredentialsProvider, options, jumpSessions.lastOrNull())
+        } catch (ex: Exception) {
+            disconnectJumpSessions()
+            throw ex
+        }
+    }
+
+    /**
+     * Connects to the host of [uri], through [jumpSession] if it's not null. The host key and the authentication are
+     * handled the same way for jump hosts and for the destination.
+     */
+    private fun connect(
+        uri: URIish,
+        sshCredentialsProvider: CredentialsProvider,
+        options: SessionOptions,
+        jumpSession: Session?,
+    ): Session {
         val session = Session()
             ?: throw SshException("Could not obtain the session, this is likely a bug. Please file a report.")
 
@@ -41,27 +98,116 @@ class SshRemoteSession @Inject constructor() : RemoteSession {
         } else
             uri.port
 
-        session.setup(uri.host, uri.user ?: "", port).throwIfSshMessage()
+        rethrowSshError {
+            if (jumpSession == null) {
+                session.setup(uri.host, uri.user ?: "", port, options)
+            } else {
+                session.setupThrough(jumpSession, uri.host, uri.user ?: "", port, options)
+            }
+        }
+
+        try {
+            rethrowSshError { session.verifyHostKey(hostKeyVerifier) }
+        } catch (ex: SshException) {
+            session.disconnect()
+            throw ex
+        }
+
+        val authInfo = rethrowSshError { session.serverAuthInfo() }
+
+        authInfo.banner?.let { banner ->
+            printLog(TAG, "Server banner: $banner")
+        }
+
+        if (!authInfo.authenticated) {
+            authenticate(session, uri, sshCredentialsProvider, authInfo.methods)
+        }
+
+        return session
+    }
+
+    /** Each tunnel goes through the previous jump host, so they are closed from the last one. */
+    private fun disconnectJumpSessions() {
+        jumpSessions.asReversed().forEach { jumpSession ->
+            jumpSession.disconnect()
+            jumpSession.close()
+        }
+
+        jumpSessions.clear()
+    }
+
+    private fun authenticate(
+        session: Session,
+        uri: URIish,
+        sshCredentialsProvider: CredentialsProvider,
+        methods: List<AuthMethod>,
+    ) {
+        val allowsPublicKey = methods.contains(AuthMethod.PUBLIC_KEY)
+        val allowsPassword = methods.contains(AuthMethod.PASSWORD)
+        val allowsKeyboardInteractive = methods.contains(AuthMethod.KEYBOARD_INTERACTIVE)
+
+        var result = if (allowsPublicKey) {
+            agentAuth(session)
+        } else {
+            AuthStatus.Denied
+        }
+
+        // A broken agent shouldn't prevent using the other methods
+        if (result is AuthStatus.Error) {
+            printError(TAG, "SSH agent authentication failed: ${result.message}")
+            result = AuthStatus.Denied
+        }
+
+        if (result == AuthStatus.Denied && allowsPublicKey) {
+            result = session.publicKeyAuth("")
+        }
 
-        var result = session.publicKeyAuth("")
+        // Servers asking for a one-time code after the key answer Partial
+        if ((result == AuthStatus.Denied || result == AuthStatus.Partial) && allowsKeyboardInteractive) {
+            result = session.keyboardInteractiveAuth(interactiveAuthPrompter)
+        }
 
-        if (result == 2) {//AuthStatus.DENIED) {
+        // Only ask for a password if the server can accept it, either for the key or the user
+        if (result == AuthStatus.Denied && (allowsPublicKey || allowsPassword)) {
             val passwordCredentialItem = CredentialItem.Password()
             sshCredentialsProvider.get(uri, passwordCredentialItem)
 
             val password = passwordCredentialItem.value.joinToString("")
 
-            result = session.publicKeyAuth(password)
+            if (allowsPublicKey) {
+                result = session.publicKeyAuth(password)
+            }
 
-            if (result != 1) {//AuthStatus.SUCCESS) {
+            if (result != AuthStatus.Success && allowsPassword) {
                 result = session.passwordAuth(password)
             }
         }
 
-        if (result != 1) {//AuthStatus.SUCCESS)
-            throw Exception("Something went wrong with authentication. Code $result")
+        when (result) {
+            AuthStatus.Success -> {}
+            is AuthStatus.Error -> throw SshException(result.message)
+            AuthStatus.Again -> throw SshException("Authentication to ${uri.host} timed out")
+            else -> throw SshException(
+                "Something went wrong with authentication. Status $result, methods allowed by the server: ${
+                    methods.joinToString()
+                }"
+            )
+        }
+    }
+
+    private fun agentAuth(session: Session): AuthStatus = SshAgent(null).use { agent ->
+        if (!agent.isAvailable()) {
+            return AuthStatus.Denied
+        }
+
+        try {
+            agent.identities().forEach { identity ->
+                printDebug(TAG, "Offering agent key ${identity.keyType} ${identity.fingerprint} ${identity.comment}")
+            }
+        } catch (ex: SshError) {
+            printError(TAG, "Listing the SSH agent keys failed: ${ex.message}")
         }
 
-        this.session = session
+        session.agentAuth()
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,6 @@
 package com.jetpackduba.gitnuro.data.git.workspace
 
+import com.jetpackduba.gitnuro.data.extensions.excludesFile
 import com.jetpackduba.gitnuro.domain.interfaces.IGetIgnoreRulesGitAction
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.withContext
@@ -7,7 +8,6 @@ import org.eclipse.jgit.ignore.FastIgnoreRule
 import org.eclipse.jgit.lib.Config
 import org.eclipse.jgit.lib.Repository
 import java.io.File
-import java.nio.file.FileSystems
 import javax.inject.Inject
 
 
@@ -29,24 +29,10 @@ class GetIgnoreRulesGitAction @Inject constructor() : IGetIgnoreRulesGitAction {
             ignoreLines.addAll(ignoreFile.readLines())
         }
 
-        if (baseConfig != null) {
-            var excludesFilePath = baseConfig.getString("core", null, "excludesFile") ?: ""
+        val excludesFile = baseConfig?.excludesFile
 
-            if (excludesFilePath.isNotEmpty()) {
-                if (excludesFilePath.startsWith("~")) {
-                    excludesFilePath = excludesFilePath.replace("~", System.getProperty("user.home").orEmpty())
-                }
-
-                val excludesFile = FileSystems
-                    .getDefault()
-                    .getPath(excludesFilePath)
-                    .normalize()
-                    .toFile()
-
-                if (excludesFile.exists() && excludesFile.isFile) {
-                    ignoreLines.addAll(excludesFile.readLines())
-                }
-            }
+        if (excludesFile != null && excludesFile.exists() && excludesFile.isFile) {
+            ignoreLines.addAll(excludesFile.readLines())
         }
 
         ignoreLines.map { FastIgnoreRule(it) }
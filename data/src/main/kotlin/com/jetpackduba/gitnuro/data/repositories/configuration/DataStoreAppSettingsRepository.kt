@@ -48,6 +48,7 @@ private val avatarProviderPreference get() = stringPreferencesKey("avatar_provid
 
 private val fastForwardMergePreference get() = booleanPreferencesKey("fast_forward_merge")
 private val autoStashOnMergePreference get() = booleanPreferencesKey("auto_stash_on_merge")
+private val changeJournalPreference get() = booleanPreferencesKey("change_journal")
 private val pullWithRebasePreference get() = booleanPreferencesKey("pull_with_rebase")
 private val pushWithLeasePreference get() = booleanPreferencesKey("push_with_lease")
 private val cloneDefaultDirectoryPreference get() = stringPreferencesKey("clone_default_directory")
@@ -114,6 +115,7 @@ class DataStoreAppSettingsRepository @Inject constructor(
     override val pushWithLease get() = preferences.data[pushWithLeasePreference]
     override val fastForwardMerge get() = preferences.data[fastForwardMergePreference]
     override val autoStashOnMerge get() = preferences.data[autoStashOnMergePreference]
+    override val changeJournal get() = preferences.data[changeJournalPreference]
     override val cloneDefaultDirectory get() = preferences.data[cloneDefaultDirectoryPreference]
 
 
@@ -136,6 +138,7 @@ class DataStoreAppSettingsRepository @Inject constructor(
         preferences.apply {
             when (appConfig) {
                 is AppConfig.AutoStashOnMerge -> setValue(autoStashOnMergePreference, appConfig.value)
+                is AppConfig.ChangeJournal -> setValue(changeJournalPreference, appConfig.value)
                 is AppConfig.CloneDefaultDirectory -> setValue(cloneDefaultDirectoryPreference, appConfig.value)
                 is AppConfig.DateFormatCustomFormat -> setValue(dateFormatCustomFormatPreference, appConfig.value)
                 is AppConfig.DateFormatIs24h -> setValue(dateFormatIs24hPreference, appConfig.value)
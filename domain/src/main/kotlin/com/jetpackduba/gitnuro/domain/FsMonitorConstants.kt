@@ -0,0 +1,10 @@
+package com.jetpackduba.gitnuro.domain
+
+object FsMonitorConstants {
+    const val SECTION = "core"
+    const val FIELD_HOOK = "fsmonitor"
+    const val FIELD_HOOK_VERSION = "fsmonitorHookVersion"
+
+    const val HOOK_NAME = "gitnuro-fsmonitor-hook"
+    const val HOOK_VERSION = 2
+}
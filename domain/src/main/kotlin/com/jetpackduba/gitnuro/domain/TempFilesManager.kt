@@ -4,6 +4,7 @@ import com.jetpackduba.gitnuro.common.OS
 import com.jetpackduba.gitnuro.common.currentOs
 import com.jetpackduba.gitnuro.common.printError
 import com.jetpackduba.gitnuro.domain.extensions.openDirectory
+import com.jetpackduba.gitnuro.domain.extensions.sha256
 import java.io.File
 import javax.inject.Inject
 import javax.inject.Singleton
@@ -57,4 +58,22 @@ class AppFilesManager @Inject constructor() {
 
         return appFolder
     }
+
+    /**
+     * Location of the `core.fsmonitor` hook, outside of [TempFilesManager.tempDir] as its path is stored in the git
+     * config of the repositories using it and must outlive the session.
+     */
+    fun getFsMonitorHookFile(): File {
+        val binFolder = getAppFolder().openDirectory("bin")
+        return File(binFolder, FsMonitorConstants.HOOK_NAME)
+    }
+
+    /**
+     * Location of the change journal of the repository, named after the hash of its path so each repository has its
+     * own one without writing into the repository.
+     */
+    fun getJournalFile(repositoryPath: String): File {
+        val journalsFolder = getAppFolder().openDirectory("journals")
+        return File(journalsFolder, repositoryPath.sha256)
+    }
 }
\ No newline at end of file
@@ -26,6 +26,23 @@ class CredentialsStateManager @Inject constructor() {
         return requestAwaitingCredentials(CredentialsRequest.SshCredentialsRequest(isRetry, password.orEmpty()))
     }
 
+    suspend fun requestSshHostKeyConfirmation(
+        host: String,
+        port: Int,
+        fingerprint: String,
+        isChanged: Boolean,
+    ): CredentialsAccepted.SshHostKeyAccepted {
+        return requestAwaitingCredentials(CredentialsRequest.SshHostKeyRequest(host, port, fingerprint, isChanged))
+    }
+
+    suspend fun requestSshInteractiveAnswers(
+        name: String,
+        instruction: String,
+        prompts: List<CredentialsRequest.SshInteractivePrompt>,
+    ): CredentialsAccepted.SshInteractiveAuthAccepted {
+        return requestAwaitingCredentials(CredentialsRequest.SshInteractiveAuthRequest(name, instruction, prompts))
+    }
+
     suspend fun requestGpgCredentials(isRetry: Boolean, password: String): CredentialsAccepted.GpgCredentialsAccepted {
         return requestAwaitingCredentials(CredentialsRequest.GpgCredentialsRequest(isRetry, password))
     }
@@ -46,6 +63,14 @@ class CredentialsStateManager @Inject constructor() {
         credentialsState.value = CredentialsAccepted.SshCredentialsAccepted(password)
     }
 
+    fun sshHostKeyAccepted() {
+        credentialsState.value = CredentialsAccepted.SshHostKeyAccepted
+    }
+
+    fun sshInteractiveAuthAccepted(answers: List<String>) {
+        credentialsState.value = CredentialsAccepted.SshInteractiveAuthAccepted(answers)
+    }
+
     fun gpgCredentialsAccepted(password: String) {
         credentialsState.value = CredentialsAccepted.GpgCredentialsAccepted(password)
     }
@@ -81,6 +106,8 @@ sealed interface CredentialsState {
 
 sealed interface CredentialsAccepted : CredentialsState {
     data class SshCredentialsAccepted(val password: String) : CredentialsAccepted
+    data object SshHostKeyAccepted : CredentialsAccepted
+    data class SshInteractiveAuthAccepted(val answers: List<String>) : CredentialsAccepted
     data class GpgCredentialsAccepted(val password: String) : CredentialsAccepted
     data class HttpCredentialsAccepted(val user: String, val password: String) : CredentialsAccepted
     data class LfsCredentialsAccepted(val user: String, val password: String) : CredentialsAccepted {
@@ -96,6 +123,21 @@ sealed interface CredentialsRequest : CredentialsState {
     @Immutable
     data class SshCredentialsRequest(val isRetry: Boolean, val password: String) : CredentialsRequest
     @Immutable
+    data class SshHostKeyRequest(
+        val host: String,
+        val port: Int,
+        val fingerprint: String,
+        val isChanged: Boolean,
+    ) : CredentialsRequest
+    @Immutable
+    data class SshInteractiveAuthRequest(
+        val name: String,
+        val instruction: String,
+        val prompts: List<SshInteractivePrompt>,
+    ) : CredentialsRequest
+    @Immutable
+    data class SshInteractivePrompt(val prompt: String, val echo: Boolean)
+    @Immutable
     data class GpgCredentialsRequest(val isRetry: Boolean, val password: String) : CredentialsRequest
     data object HttpCredentialsRequest : CredentialsRequest
     data object LfsCredentialsRequest : CredentialsRequest
@@ -1,3 +1,3 @@
 package com.jetpackduba.gitnuro.domain.exceptions
 
-class SshException(message: String) : GitnuroException(message)
\ No newline at end of file
+class SshException(message: String, cause: Exception? = null) : GitnuroException(message, cause)
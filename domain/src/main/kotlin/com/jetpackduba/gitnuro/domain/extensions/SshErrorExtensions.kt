@@ -0,0 +1,40 @@
+package com.jetpackduba.gitnuro.domain.extensions
+
+import com.jetpackduba.gitnuro.HostKeyStatus
+import com.jetpackduba.gitnuro.TimeoutKind
+import com.jetpackduba.gitnuro.domain.exceptions.SshException
+import com.jetpackduba.gitnuro.SshException as SshError
+
+/**
+ * Runs a call to the native SSH library, turning its errors into an [SshException] with a message that can be shown
+ * to the user. The original error is kept as the cause.
+ */
+inline fun <T> rethrowSshError(block: () -> T): T {
+    return try {
+        block()
+    } catch (ex: SshError) {
+        throw SshException(ex.userMessage, ex)
+    }
+}
+
+val SshError.userMessage: String
+    get() = when (this) {
+        is SshError.Dns -> "Could not resolve host $host: $reason"
+        is SshError.ConnectionRefused -> "Connection to $host:$port was refused"
+        is SshError.Timeout -> when (kind) {
+            TimeoutKind.CONNECT -> "Connection to $host:$port timed out"
+            TimeoutKind.AUTHENTICATION -> "Authentication to $host:$port timed out"
+            TimeoutKind.READ -> "Waiting for data from $host:$port timed out"
+        }
+        is SshError.UntrustedHostKey -> "The host key of $host:$port ($fingerprint) was not trusted"
+        is SshError.HostKeyMismatch -> if (status == HostKeyStatus.OTHER_TYPE) {
+            "The host key of $host:$port ($fingerprint) is of a different type than the known one. If this is expected, remove the old key from your known hosts"
+        } else {
+            "The host key of $host:$port has changed ($fingerprint). If this is expected, remove the old key from your known hosts"
+        }
+
+        is SshError.AuthFailure -> "Authentication failed: $reason"
+        is SshError.ChannelFailure -> "SSH channel $operation failed: $reason"
+        is SshError.Configuration -> "SSH $option option failed: $reason"
+        is SshError.Io -> "SSH connection error: $reason"
+    }
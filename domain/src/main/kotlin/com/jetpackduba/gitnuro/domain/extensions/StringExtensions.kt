@@ -1,7 +1,6 @@
 package com.jetpackduba.gitnuro.domain.extensions
 
 import com.jetpackduba.gitnuro.common.systemSeparator
-import com.jetpackduba.gitnuro.domain.exceptions.SshException
 import java.security.MessageDigest
 
 @OptIn(ExperimentalStdlibApi::class)
@@ -61,12 +60,6 @@ fun String.lowercaseContains(other: String): Boolean {
     return this.lowercase().contains(other.lowercase().trim())
 }
 
-fun String.throwIfSshMessage() {
-    if (this.isNotEmpty()) {
-        throw SshException(this)
-    }
-}
-
 fun String.removeGitSuffix() = this.removeSuffix(systemSeparator)
     .removeSuffix(".git")
     .removeSuffix(systemSeparator)
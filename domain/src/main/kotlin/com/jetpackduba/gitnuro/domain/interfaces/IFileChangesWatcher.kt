@@ -1,5 +1,6 @@
 package com.jetpackduba.gitnuro.domain.interfaces
 
+import com.jetpackduba.gitnuro.JournalQueryResult
 import com.jetpackduba.gitnuro.domain.errors.Either
 import com.jetpackduba.gitnuro.domain.errors.FSWatchError
 import com.jetpackduba.gitnuro.domain.models.WatcherEvent
@@ -8,8 +9,16 @@ import kotlinx.coroutines.flow.SharedFlow
 import org.eclipse.jgit.lib.Repository
 
 interface IFileChangesWatcher {
-    fun addPathToWatch(path: String, isRecursive: Boolean)
+    fun addPathToWatch(path: String, isRecursive: Boolean, isHighPriority: Boolean = false)
     fun removePathFromWatch(path: String)
+    fun trackIgnoreRules(worktreePath: String, gitDirPath: String)
+    fun rescan()
+    fun refreshWatchedDirectories()
+    fun enableJournal(journalPath: String, worktreePath: String): Boolean
+    fun journalClock(): String?
+    fun journalChangesSince(clock: String): JournalQueryResult?
+    fun startFsMonitor(gitDirPath: String): Boolean
+    fun stopFsMonitor()
 
     suspend fun observeEvents(): Flow<WatcherEvent>
 
@@ -0,0 +1,8 @@
+package com.jetpackduba.gitnuro.domain.interfaces
+
+import com.jetpackduba.gitnuro.domain.errors.Either
+import com.jetpackduba.gitnuro.domain.errors.GitError
+
+interface ILoadFsMonitorHookGitAction {
+    suspend operator fun invoke(repositoryPath: String): Either<String?, GitError>
+}
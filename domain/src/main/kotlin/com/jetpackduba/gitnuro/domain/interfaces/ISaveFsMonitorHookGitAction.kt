@@ -0,0 +1,14 @@
+package com.jetpackduba.gitnuro.domain.interfaces
+
+import com.jetpackduba.gitnuro.domain.errors.Either
+import com.jetpackduba.gitnuro.domain.errors.GitError
+
+interface ISaveFsMonitorHookGitAction {
+    /**
+     * Sets [hookPath] as `core.fsmonitor` hook of the repository, or removes the hook if it's null.
+     */
+    suspend operator fun invoke(
+        repositoryPath: String,
+        hookPath: String?,
+    ): Either<Unit, GitError>
+}
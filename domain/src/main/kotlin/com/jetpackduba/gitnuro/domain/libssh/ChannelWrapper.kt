@@ -3,7 +3,7 @@ package com.jetpackduba.gitnuro.domain.libssh
 import com.jetpackduba.gitnuro.Channel
 import com.jetpackduba.gitnuro.Session
 import com.jetpackduba.gitnuro.domain.exceptions.SshException
-import com.jetpackduba.gitnuro.domain.extensions.throwIfSshMessage
+import com.jetpackduba.gitnuro.domain.extensions.rethrowSshError
 import com.jetpackduba.gitnuro.domain.libssh.streams.SshChannelInputErrStream
 import com.jetpackduba.gitnuro.domain.libssh.streams.SshChannelInputStream
 import com.jetpackduba.gitnuro.domain.libssh.streams.SshChannelOutputStream
@@ -20,11 +20,11 @@ class ChannelWrapper internal constructor(sshSession: Session) {
     val errorOutputStream = SshChannelInputErrStream(channel)
 
     fun openSession() {
-        channel.openSession()
+        rethrowSshError { channel.openSession() }
     }
 
     fun requestExec(commandName: String) {
-        channel.requestExec(commandName).throwIfSshMessage()
+        rethrowSshError { channel.requestExec(commandName) }
     }
 
     fun isOpen(): Boolean {
@@ -35,7 +35,7 @@ class ChannelWrapper internal constructor(sshSession: Session) {
         closeMutex.acquire()
         try {
             if (!isClosed) {
-                channel.closeChannel().throwIfSshMessage()
+                rethrowSshError { channel.closeChannel() }
                 channel.close()
                 isClosed = true
             }
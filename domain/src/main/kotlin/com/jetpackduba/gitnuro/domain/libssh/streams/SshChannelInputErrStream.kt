@@ -1,7 +1,7 @@
 package com.jetpackduba.gitnuro.domain.libssh.streams
 
 import com.jetpackduba.gitnuro.Channel
-import com.jetpackduba.gitnuro.domain.exceptions.SshException
+import com.jetpackduba.gitnuro.domain.extensions.rethrowSshError
 import java.io.InputStream
 
 class SshChannelInputErrStream(private val sshChannel: Channel) : InputStream() {
@@ -9,8 +9,7 @@ class SshChannelInputErrStream(private val sshChannel: Channel) : InputStream()
 
     override fun read(): Int {
         return if (sshChannel.pollHasBytes(true)) {
-            val read = sshChannel.read(true, 1L.toULong())
-                ?: throw SshException("Could not read result from SSH channel. Please check your network connectivity and try again.")
+            val read = rethrowSshError { sshChannel.read(true, 1L.toULong()) }
 
             val byteArray = read.data
 
@@ -1,13 +1,12 @@
 package com.jetpackduba.gitnuro.domain.libssh.streams
 
 import com.jetpackduba.gitnuro.Channel
-import com.jetpackduba.gitnuro.domain.exceptions.SshException
+import com.jetpackduba.gitnuro.domain.extensions.rethrowSshError
 import java.io.InputStream
 
 class SshChannelInputStream(private val sshChannel: Channel) : InputStream() {
     override fun read(b: ByteArray, off: Int, len: Int): Int {
-        val result = sshChannel.read(false, len.toULong())
-            ?: throw SshException("Could not read result from SSH channel. Please check your network connectivity and try again.")
+        val result = rethrowSshError { sshChannel.read(false, len.toULong()) }
 
         if (result.readCount == 0.toULong()) {
             return -1
@@ -25,8 +24,7 @@ class SshChannelInputStream(private val sshChannel: Channel) : InputStream() {
 
     override fun read(): Int {
 
-        val result = sshChannel.read(false, 1L.toULong())
-            ?: throw SshException("Could not read result from SSH channel. Please check your network connectivity and try again.")
+        val result = rethrowSshError { sshChannel.read(false, 1L.toULong()) }
 
         if (result.readCount == 0.toULong()) {
             return -1
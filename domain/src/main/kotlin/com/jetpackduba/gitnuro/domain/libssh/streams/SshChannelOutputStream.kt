@@ -1,7 +1,7 @@
 package com.jetpackduba.gitnuro.domain.libssh.streams
 
 import com.jetpackduba.gitnuro.Channel
-import com.jetpackduba.gitnuro.domain.extensions.throwIfSshMessage
+import com.jetpackduba.gitnuro.domain.extensions.rethrowSshError
 import java.io.OutputStream
 
 class SshChannelOutputStream(private val sshChannel: Channel) : OutputStream() {
@@ -11,7 +11,7 @@ class SshChannelOutputStream(private val sshChannel: Channel) : OutputStream() {
     }
 
     override fun write(b: ByteArray) {
-        sshChannel.writeBytes(b).throwIfSshMessage()
+        rethrowSshError { sshChannel.writeBytes(b) }
     }
 
     override fun close() {
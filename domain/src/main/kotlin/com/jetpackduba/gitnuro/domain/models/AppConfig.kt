@@ -9,6 +9,7 @@ sealed interface AppConfig {
     data class PushWithLease(val value: Boolean) : AppConfig
     data class FastForwardMerge(val value: Boolean) : AppConfig
     data class AutoStashOnMerge(val value: Boolean) : AppConfig
+    data class ChangeJournal(val value: Boolean) : AppConfig
     data class UseProxy(val value: Boolean) : AppConfig
     data class ProxyUseAuth(val value: Boolean) : AppConfig
     data class ProxyProxyType(val value: ProxyType) : AppConfig
@@ -1,8 +1,12 @@
 package com.jetpackduba.gitnuro.domain.models
 
+import com.jetpackduba.gitnuro.BatchInfo
+import com.jetpackduba.gitnuro.BulkChange
 import com.jetpackduba.gitnuro.FileChanged
 
 sealed interface WatcherEvent {
     data class WatchInitError(val code: Int) : WatcherEvent
-    data class ChangesDetected(val changes: List<FileChanged>) : WatcherEvent
+    data class ChangesDetected(val changes: List<FileChanged>, val batch: BatchInfo) : WatcherEvent
+    data class BulkChangesDetected(val bulkChange: BulkChange, val batch: BatchInfo) : WatcherEvent
+    data object IgnoreRulesChanged : WatcherEvent
 }
\ No newline at end of file
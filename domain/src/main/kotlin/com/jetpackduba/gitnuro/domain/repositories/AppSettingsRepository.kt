@@ -29,6 +29,7 @@ interface AppSettingsRepository {
     val pushWithLease: Flow<Boolean?>
     val fastForwardMerge: Flow<Boolean?>
     val autoStashOnMerge: Flow<Boolean?>
+    val changeJournal: Flow<Boolean?>
     val cloneDefaultDirectory: Flow<String?>
 
     // Network
@@ -35,6 +35,7 @@ class AppSettingsService @Inject constructor(
     val pushWithLease: Flow<Boolean> get() = appSettingsRepository.pushWithLease.defaultIfNull { DEFAULT_PUSH_WITH_LEASE }
     val fastForwardMerge: Flow<Boolean> get() = appSettingsRepository.fastForwardMerge.defaultIfNull { DEFAULT_FAST_FORWARD_MERGE }
     val autoStashOnMerge: Flow<Boolean> get() = appSettingsRepository.autoStashOnMerge.defaultIfNull { DEFAULT_AUTO_STASH_ON_MERGE }
+    val changeJournal: Flow<Boolean> get() = appSettingsRepository.changeJournal.defaultIfNull { DEFAULT_CHANGE_JOURNAL }
     val cloneDefaultDirectory: Flow<String?> get() = appSettingsRepository.cloneDefaultDirectory
     val useProxy: Flow<Boolean> get() = appSettingsRepository.useProxy.defaultIfNull { DEFAULT_USE_PROXY }
     val proxyUseAuth: Flow<Boolean> get() = appSettingsRepository.proxyUseAuth.defaultIfNull { DEFAULT_PROXY_USE_AUTH }
@@ -63,6 +64,7 @@ class AppSettingsService @Inject constructor(
         const val DEFAULT_PUSH_WITH_LEASE = true
         const val DEFAULT_FAST_FORWARD_MERGE = true
         const val DEFAULT_AUTO_STASH_ON_MERGE = true
+        const val DEFAULT_CHANGE_JOURNAL = false
         const val DEFAULT_USE_PROXY = false
         const val DEFAULT_PROXY_USE_AUTH = false
         val DEFAULT_PROXY_TYPE = ProxyType.HTTP
@@ -0,0 +1,27 @@
+package com.jetpackduba.gitnuro.domain.usecases
+
+import com.jetpackduba.gitnuro.domain.AppFilesManager
+import com.jetpackduba.gitnuro.domain.errors.Either
+import com.jetpackduba.gitnuro.domain.errors.GitError
+import com.jetpackduba.gitnuro.domain.errors.RepositoryPathNotSetError
+import com.jetpackduba.gitnuro.domain.errors.mapOk
+import com.jetpackduba.gitnuro.domain.interfaces.ILoadFsMonitorHookGitAction
+import com.jetpackduba.gitnuro.domain.repositories.RepositoryDataRepository
+import javax.inject.Inject
+
+/**
+ * Whether the repository has opted in to answering git's fsmonitor queries from the watcher, by having the
+ * gitnuro hook as `core.fsmonitor`.
+ */
+class LoadFsMonitorEnabledUseCase @Inject constructor(
+    private val repositoryDataRepository: RepositoryDataRepository,
+    private val loadFsMonitorHookGitAction: ILoadFsMonitorHookGitAction,
+    private val appFilesManager: AppFilesManager,
+) {
+    suspend operator fun invoke(): Either<Boolean, GitError> {
+        val repositoryPath = repositoryDataRepository.repositoryPath ?: return Either.Err(RepositoryPathNotSetError)
+        val hookPath = appFilesManager.getFsMonitorHookFile().absolutePath
+
+        return loadFsMonitorHookGitAction(repositoryPath).mapOk { it == hookPath }
+    }
+}
@@ -4,6 +4,7 @@ import com.jetpackduba.gitnuro.FileType
 import com.jetpackduba.gitnuro.common.printDebug
 import com.jetpackduba.gitnuro.common.printError
 import com.jetpackduba.gitnuro.common.systemSeparator
+import com.jetpackduba.gitnuro.domain.AppFilesManager
 import com.jetpackduba.gitnuro.domain.TabCoroutineScope
 import com.jetpackduba.gitnuro.domain.errors.okOrNull
 import com.jetpackduba.gitnuro.domain.interfaces.IFileChangesWatcher
@@ -11,6 +12,9 @@ import com.jetpackduba.gitnuro.domain.interfaces.IGetStatusGitAction
 import com.jetpackduba.gitnuro.domain.models.WatcherEvent
 import com.jetpackduba.gitnuro.domain.repositories.RepositoryDataRepository
 import com.jetpackduba.gitnuro.domain.repositories.RepositoryStateRepository
+import com.jetpackduba.gitnuro.domain.services.AppSettingsService
+import kotlinx.coroutines.delay
+import kotlinx.coroutines.flow.collectLatest
 import kotlinx.coroutines.flow.first
 import kotlinx.coroutines.launch
 import java.io.File
@@ -28,7 +32,14 @@ class ObserveRepositoryToRefreshUseCase @Inject constructor(
     private val refreshDataUseCase: RefreshDataUseCase,
     private val repositoryStateRepository: RepositoryStateRepository,
     private val getStatusGitAction: IGetStatusGitAction,
+    private val loadFsMonitorEnabledUseCase: LoadFsMonitorEnabledUseCase,
+    private val appSettingsService: AppSettingsService,
+    private val appFilesManager: AppFilesManager,
 ) {
+    /** Journal clock of the last changes refreshed, see [refreshSkippedChanges]. */
+    @Volatile
+    private var handledClock: String? = null
+
     /**
      * Sometimes external apps can run filesystem multiple operations in a fraction of a second.
      * To prevent excessive updates, we add a slight delay between updates emission to prevent slowing down
@@ -52,9 +63,14 @@ class ObserveRepositoryToRefreshUseCase @Inject constructor(
                                 printDebug(TAG, "Changes detected: ${event.changes.toList()}")
 
                                 if (canRefreshData()) {
+                                    handledClock = fileChangesWatcher.journalClock()
                                     val hasGitDirChanged = event.changes.any { it.path.startsWith(repositoryPath) }
 
-                                    updateWatchedDirectories(event, repositoryPath, worktreeDir + systemSeparator)
+                                    val directories = event.changes
+                                        .filter { it.fileType == FileType.DIRECTORY }
+                                        .map { it.path }
+
+                                    updateWatchedDirectories(directories, repositoryPath, worktreeDir + systemSeparator)
 
                                     if (hasGitDirChanged) {
                                         refreshDataUseCase(DataToRefresh.ALL)
@@ -66,6 +82,36 @@ class ObserveRepositoryToRefreshUseCase @Inject constructor(
                                 }
                             }
 
+                            is WatcherEvent.BulkChangesDetected -> {
+                                printDebug(TAG, "Bulk change detected: ${event.bulkChange.totalCount} paths in ${event.bulkChange.directories.count()} directories")
+
+                                if (canRefreshData()) {
+                                    handledClock = fileChangesWatcher.journalClock()
+
+                                    // Without the summaries there is no way to tell which directories changed
+                                    val directories = event.bulkChange.directories.map { it.path }
+
+                                    if (directories.isEmpty()) {
+                                        fileChangesWatcher.refreshWatchedDirectories()
+                                    } else {
+                                        updateWatchedDirectories(directories, repositoryPath, worktreeDir + systemSeparator)
+                                    }
+
+                                    refreshDataUseCase(DataToRefresh.ALL)
+                                } else {
+                                    printDebug(TAG, "Ignoring detected bulk change because the time diff since last change is too short or currently running other tasks")
+                                }
+                            }
+
+                            is WatcherEvent.IgnoreRulesChanged -> {
+                                printDebug(TAG, "Ignore rules changed")
+
+                                if (canRefreshData()) {
+                                    handledClock = fileChangesWatcher.journalClock()
+                                    refreshDataUseCase(DataToRefresh.STATUS)
+                                }
+                            }
+
                             is WatcherEvent.WatchInitError -> {
                                 printDebug(TAG, "Watch init error: ${event.code}")
                             }
@@ -73,34 +119,66 @@ class ObserveRepositoryToRefreshUseCase @Inject constructor(
                     }
             }
 
-            fileChangesWatcher.addPathToWatch(worktreeDir, false)
-            fileChangesWatcher.addPathToWatch(repositoryPath, false)
-            fileChangesWatcher.addPathToWatch("$repositoryPath${systemSeparator}refs", true)
-            fileChangesWatcher.addPathToWatch("$repositoryPath${systemSeparator}modules", true)
+            // Changes skipped while a task was running are found in the journal once it's done
+            launch {
+                repositoryStateRepository.currentTask.collectLatest { task ->
+                    if (task == null) {
+                        delay(REFRESH_TIME_SINCE_LAST_OPERATION)
+                        refreshSkippedChanges(repositoryPath)
+                    }
+                }
+            }
 
-            val status = getStatusGitAction(repositoryPath).okOrNull()
+            // Lets git reuse the changes seen by the watcher instead of scanning the worktree, only in the
+            // repositories that have the hook set as core.fsmonitor. Answering its queries requires the journal.
+            val isFsMonitorEnabled = loadFsMonitorEnabledUseCase().okOrNull() == true
+            val isJournalEnabled = appSettingsService.changeJournal.first() || isFsMonitorEnabled
 
-            val worktreeDirFile = File(worktreeDir)
+            if (isJournalEnabled) {
+                val journalPath = appFilesManager.getJournalFile(repositoryPath).absolutePath
 
-            val dirs = getDirsToWatch(
-                worktreeDir = worktreeDir + systemSeparator,
-                excludedRelativePaths = HashSet(listOf(".git")),
-                dirFile = worktreeDirFile,
-                ignoreList = status?.ignored.orEmpty()
-            )
+                if (fileChangesWatcher.enableJournal(journalPath, worktreeDir)) {
+                    handledClock = fileChangesWatcher.journalClock()
 
-            if (status != null) {
-                for (child in dirs) {
-                    if (!status.ignored.contains(child.absolutePath.removePrefix(repositoryPath))) {
-                        fileChangesWatcher.addPathToWatch(child.absolutePath, false)
+                    if (isFsMonitorEnabled && !fileChangesWatcher.startFsMonitor(repositoryPath)) {
+                        printDebug(TAG, "The fsmonitor endpoint could not be started")
                     }
+                } else {
+                    printError(TAG, "The change journal could not be enabled")
                 }
             }
+
+            fileChangesWatcher.addPathToWatch(worktreeDir, false)
+            fileChangesWatcher.addPathToWatch(repositoryPath, false, isHighPriority = true)
+            fileChangesWatcher.addPathToWatch("$repositoryPath${systemSeparator}refs", true, isHighPriority = true)
+            fileChangesWatcher.addPathToWatch("$repositoryPath${systemSeparator}modules", true)
+
+            fileChangesWatcher.trackIgnoreRules(worktreeDir, repositoryPath)
         }.invokeOnCompletion {
             fileChangesWatcher.close()
         }
     }
 
+    private suspend fun refreshSkippedChanges(repositoryPath: String) {
+        val clock = handledClock ?: return
+        val changes = fileChangesWatcher.journalChangesSince(clock) ?: return
+
+        if (!changes.isFreshInstance && changes.changedPaths.isEmpty()) {
+            return
+        }
+
+        if (canRefreshData()) {
+            printDebug(TAG, "Refreshing ${changes.changedPaths.count()} changes skipped while running a task")
+            handledClock = changes.clock
+
+            if (changes.isFreshInstance || changes.changedPaths.any { it.startsWith(repositoryPath) }) {
+                refreshDataUseCase(DataToRefresh.ALL)
+            } else {
+                refreshDataUseCase(DataToRefresh.STATUS, DataToRefresh.LOG, DataToRefresh.REPO_STATE)
+            }
+        }
+    }
+
     private suspend fun canRefreshData(): Boolean {
         val canRefresh = if (repositoryStateRepository.currentTask.value != null) {
             false
@@ -119,17 +197,13 @@ class ObserveRepositoryToRefreshUseCase @Inject constructor(
     }
 
     private suspend fun updateWatchedDirectories(
-        event: WatcherEvent.ChangesDetected,
+        directories: List<String>,
         repositoryPath: String,
         worktreeDirPath: String,
     ) {
-        val directories = event
-            .changes
-            .filter { it.fileType == FileType.DIRECTORY }
-
         if (directories.isNotEmpty()) {
             val groupedDirs = directories
-                .groupBy { File(it.path).exists() }
+                .groupBy { File(it).exists() }
 
             val newDirs = groupedDirs[true].orEmpty()
             val removedDirs = groupedDirs[false].orEmpty()
@@ -137,47 +211,19 @@ class ObserveRepositoryToRefreshUseCase @Inject constructor(
             if (newDirs.isNotEmpty()) {
                 val status = getStatusGitAction(
                     repositoryPath,
-                    newDirs.map { it.path.removePrefix(worktreeDirPath) },
+                    newDirs.map { it.removePrefix(worktreeDirPath) },
                 ).okOrNull()
 
                 for (dir in newDirs) {
-                    if (status != null && !status.ignored.contains(dir.path.removePrefix(worktreeDirPath))) {
-                        fileChangesWatcher.addPathToWatch(dir.path, false)
+                    if (status != null && !status.ignored.contains(dir.removePrefix(worktreeDirPath))) {
+                        fileChangesWatcher.addPathToWatch(dir, false)
                     }
                 }
-
-                for (dir in removedDirs) {
-                    fileChangesWatcher.removePathFromWatch(dir.path)
-                }
             }
-        }
-    }
 
-    private fun getDirsToWatch(
-        worktreeDir: String,
-        excludedRelativePaths: HashSet<String>,
-        dirFile: File,
-        ignoreList: List<String>
-    ): List<File> {
-        val childrenDirs = try {
-            dirFile.listFiles { file -> file.isDirectory }
-        } catch (e: Exception) {
-            printError(TAG, e.message.orEmpty(), e)
-            emptyArray()
-        }
-            .filter {
-                val relativePath = it.absolutePath.removePrefix(worktreeDir)
-                !ignoreList.contains(relativePath) &&
-                        !excludedRelativePaths.contains(relativePath)
+            for (dir in removedDirs) {
+                fileChangesWatcher.removePathFromWatch(dir)
             }
-
-        return childrenDirs + childrenDirs.flatMap {
-            getDirsToWatch(
-                worktreeDir,
-                excludedRelativePaths,
-                it,
-                ignoreList
-            )
         }
     }
 }
@@ -0,0 +1,16 @@
+package com.jetpackduba.gitnuro.domain.usecases
+
+import com.jetpackduba.gitnuro.domain.interfaces.IFileChangesWatcher
+import javax.inject.Inject
+
+/**
+ * Compares the watched files against the disk, reporting the changes the watcher may have missed while the system
+ * was suspended.
+ */
+class RescanWatchedFilesUseCase @Inject constructor(
+    private val fileChangesWatcher: IFileChangesWatcher,
+) {
+    operator fun invoke() {
+        fileChangesWatcher.rescan()
+    }
+}
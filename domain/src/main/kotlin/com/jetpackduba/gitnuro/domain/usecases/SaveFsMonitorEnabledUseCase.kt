@@ -0,0 +1,66 @@
+package com.jetpackduba.gitnuro.domain.usecases
+
+import com.jetpackduba.gitnuro.common.printError
+import com.jetpackduba.gitnuro.domain.AppFilesManager
+import com.jetpackduba.gitnuro.domain.errors.Either
+import com.jetpackduba.gitnuro.domain.errors.GenericError
+import com.jetpackduba.gitnuro.domain.errors.GitError
+import com.jetpackduba.gitnuro.domain.errors.RepositoryPathNotSetError
+import com.jetpackduba.gitnuro.domain.errors.okOrNull
+import com.jetpackduba.gitnuro.domain.interfaces.IFileChangesWatcher
+import com.jetpackduba.gitnuro.domain.interfaces.ISaveFsMonitorHookGitAction
+import com.jetpackduba.gitnuro.domain.repositories.RepositoryDataRepository
+import javax.inject.Inject
+
+private const val TAG = "SaveFsMonitorEnabledUseCase"
+
+/**
+ * Sets or removes the gitnuro hook as `core.fsmonitor` of the repository and starts or stops answering its queries.
+ */
+class SaveFsMonitorEnabledUseCase @Inject constructor(
+    private val repositoryDataRepository: RepositoryDataRepository,
+    private val saveFsMonitorHookGitAction: ISaveFsMonitorHookGitAction,
+    private val fileChangesWatcher: IFileChangesWatcher,
+    private val appFilesManager: AppFilesManager,
+    private val getWorktreeUseCase: GetWorktreeUseCase,
+) {
+    suspend operator fun invoke(isEnabled: Boolean): Either<Unit, GitError> {
+        val repositoryPath = repositoryDataRepository.repositoryPath ?: return Either.Err(RepositoryPathNotSetError)
+        val hookFile = appFilesManager.getFsMonitorHookFile()
+
+        if (isEnabled && !hookFile.canExecute()) {
+            return Either.Err(GenericError("The fsmonitor hook is not available on this platform"))
+        }
+
+        val hookPath = if (isEnabled) hookFile.absolutePath else null
+
+        val result = saveFsMonitorHookGitAction(repositoryPath, hookPath)
+
+        if (result is Either.Ok) {
+            if (isEnabled) {
+                startFsMonitor(repositoryPath)
+            } else {
+                fileChangesWatcher.stopFsMonitor()
+            }
+        }
+
+        return result
+    }
+
+    private suspend fun startFsMonitor(repositoryPath: String) {
+        // The queries are answered from the journal, which is only enabled by default if the setting is on
+        if (fileChangesWatcher.journalClock() == null) {
+            val worktree = getWorktreeUseCase().okOrNull() ?: return
+            val journalPath = appFilesManager.getJournalFile(repositoryPath).absolutePath
+
+            if (!fileChangesWatcher.enableJournal(journalPath, worktree)) {
+                printError(TAG, "The change journal could not be enabled")
+                return
+            }
+        }
+
+        if (!fileChangesWatcher.startFsMonitor(repositoryPath)) {
+            printError(TAG, "The fsmonitor endpoint could not be started")
+        }
+    }
+}
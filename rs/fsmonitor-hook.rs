@@ -0,0 +1,85 @@
+//! `core.fsmonitor` hook (protocol version 2) that forwards git's queries to the fsmonitor
+//! endpoint started by Gitnuro, for git versions without builtin fsmonitor daemon support.
+//!
+//! Usage: `git config core.fsmonitor /path/to/gitnuro-fsmonitor-hook`
+
+#[cfg(unix)]
+#[path = "src/fsmonitor_protocol.rs"]
+mod fsmonitor_protocol;
+
+#[cfg(unix)]
+fn main() {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+
+    use fsmonitor_protocol::{FSMONITOR_SOCKET_NAME, read_packetized, write_packetized};
+
+    fn find_git_dir() -> Option<PathBuf> {
+        if let Some(git_dir) = std::env::var_os("GIT_DIR") {
+            return Some(PathBuf::from(git_dir));
+        }
+
+        let current_dir = std::env::current_dir().ok()?;
+
+        for directory in current_dir.ancestors() {
+            let dot_git = directory.join(".git");
+
+            if dot_git.is_dir() {
+                return Some(dot_git);
+            }
+
+            // Worktrees and submodules use a file pointing to the actual git directory
+            if let Ok(content) = std::fs::read_to_string(&dot_git) {
+                let git_dir = content.trim().strip_prefix("gitdir: ")?;
+                return Some(directory.join(git_dir));
+            }
+        }
+
+        None
+    }
+
+    fn query(token: &str) -> std::io::Result<Vec<u8>> {
+        let git_dir = find_git_dir().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "Git directory not found")
+        })?;
+
+        let mut stream = UnixStream::connect(git_dir.join(FSMONITOR_SOCKET_NAME))?;
+        write_packetized(&mut stream, token.as_bytes())?;
+        read_packetized(&mut stream)
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).map(String::as_str) != Some("2") {
+        eprintln!("gitnuro-fsmonitor-hook only supports fsmonitor protocol version 2");
+        std::process::exit(1);
+    }
+
+    let token = args.get(2).map(String::as_str).unwrap_or_default();
+
+    match query(token) {
+        Ok(response) => {
+            let mut stdout = std::io::stdout();
+
+            if stdout
+                .write_all(&response)
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            // A failing hook makes git fall back to scanning the worktree
+            eprintln!("gitnuro-fsmonitor-hook: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("gitnuro-fsmonitor-hook is not supported on this platform");
+    std::process::exit(1);
+}
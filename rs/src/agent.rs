@@ -0,0 +1,264 @@
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::host_key;
+use crate::ssh_error::SshError;
+
+const SSH_AUTH_SOCK: &str = "SSH_AUTH_SOCK";
+
+#[cfg(windows)]
+const DEFAULT_WINDOWS_AGENT_PIPE: &str = r"\\.\pipe\openssh-ssh-agent";
+
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+
+/// Agent messages are small, anything bigger means the other end isn't an agent.
+const MAX_AGENT_MESSAGE_LENGTH: u32 = 256 * 1024;
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct AgentIdentity {
+    pub key_type: String,
+    /// SHA256 fingerprint of the key, in the format printed by OpenSSH.
+    pub fingerprint: String,
+    pub comment: String,
+}
+
+/// ssh-agent reachable at `SSH_AUTH_SOCK` (or the OpenSSH pipe on Windows), queried directly as
+/// libssh doesn't expose the keys it holds.
+#[derive(uniffi::Object)]
+pub struct SshAgent {
+    socket_path: Option<PathBuf>,
+}
+
+#[uniffi::export]
+impl SshAgent {
+    /// Uses `SSH_AUTH_SOCK` if no path is given.
+    #[uniffi::constructor]
+    pub fn new(socket_path: Option<String>) -> SshAgent {
+        let socket_path = socket_path.map(PathBuf::from).or_else(default_agent_path);
+
+        SshAgent { socket_path }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.socket_path.is_some()
+    }
+
+    pub fn identities(&self) -> Result<Vec<AgentIdentity>, SshError> {
+        let socket_path = self.socket_path.as_ref().ok_or_else(|| SshError::Io {
+            reason: format!("No SSH agent is running, {SSH_AUTH_SOCK} is not set"),
+        })?;
+
+        let mut stream = connect(socket_path)?;
+
+        write_message(&mut stream, &[SSH_AGENTC_REQUEST_IDENTITIES])?;
+        let response = read_message(&mut stream)?;
+
+        parse_identities(&response).ok_or_else(|| SshError::Io {
+            reason: "The SSH agent sent an invalid identities answer".to_string(),
+        })
+    }
+}
+
+/// Keys held by the ssh-agent at `SSH_AUTH_SOCK`, the ones [crate::Session::agent_auth] offers.
+#[uniffi::export]
+pub fn list_agent_identities() -> Result<Vec<AgentIdentity>, SshError> {
+    SshAgent::new(None).identities()
+}
+
+fn default_agent_path() -> Option<PathBuf> {
+    let socket_path = std::env::var_os(SSH_AUTH_SOCK).filter(|path| !path.is_empty());
+
+    #[cfg(windows)]
+    let socket_path = socket_path.or_else(|| Some(DEFAULT_WINDOWS_AGENT_PIPE.into()));
+
+    socket_path.map(PathBuf::from)
+}
+
+#[cfg(unix)]
+fn connect(socket_path: &PathBuf) -> std::io::Result<std::os::unix::net::UnixStream> {
+    std::os::unix::net::UnixStream::connect(socket_path)
+}
+
+/// Named pipes are opened as regular files.
+#[cfg(not(unix))]
+fn connect(socket_path: &PathBuf) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(socket_path)
+}
+
+/// Messages are prefixed by their length as a big endian u32.
+fn write_message(stream: &mut impl Write, message: &[u8]) -> std::io::Result<()> {
+    stream.write_all(&(message.len() as u32).to_be_bytes())?;
+    stream.write_all(message)?;
+    stream.flush()
+}
+
+fn read_message(stream: &mut impl Read) -> Result<Vec<u8>, SshError> {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length);
+
+    if length > MAX_AGENT_MESSAGE_LENGTH {
+        return Err(SshError::Io {
+            reason: format!("The SSH agent sent a message of {length} bytes"),
+        });
+    }
+
+    let mut message = vec![0u8; length as usize];
+    stream.read_exact(&mut message)?;
+
+    Ok(message)
+}
+
+/// `byte SSH_AGENT_IDENTITIES_ANSWER, uint32 count, (string key_blob, string comment) * count`,
+/// where the key blob starts with the key type.
+fn parse_identities(message: &[u8]) -> Option<Vec<AgentIdentity>> {
+    let (&message_type, mut remaining) = message.split_first()?;
+
+    if message_type != SSH_AGENT_IDENTITIES_ANSWER {
+        return None;
+    }
+
+    let count = read_u32(&mut remaining)?;
+    let mut identities = Vec::new();
+
+    for _ in 0..count {
+        let key_blob = read_string(&mut remaining)?;
+        let comment = read_string(&mut remaining)?;
+        let mut key_blob_fields = key_blob;
+        let key_type = read_string(&mut key_blob_fields)?;
+
+        identities.push(AgentIdentity {
+            key_type: String::from_utf8_lossy(key_type).into_owned(),
+            fingerprint: host_key::fingerprint_of_blob(key_blob),
+            comment: String::from_utf8_lossy(comment).into_owned(),
+        });
+    }
+
+    Some(identities)
+}
+
+fn read_u32(data: &mut &[u8]) -> Option<u32> {
+    let (value, remaining) = data.split_first_chunk::<4>()?;
+    *data = remaining;
+
+    Some(u32::from_be_bytes(*value))
+}
+
+fn read_string<'a>(data: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let length = read_u32(data)? as usize;
+
+    if data.len() < length {
+        return None;
+    }
+
+    let (value, remaining) = data.split_at(length);
+    *data = remaining;
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+
+    use super::*;
+
+    const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+    const FINGERPRINT: &str = "SHA256:+DiY3wvvV6TuJJhbpZisF/zLDA0zPMSvHdkr4UvCOqU";
+
+    fn push_string(message: &mut Vec<u8>, value: &[u8]) {
+        message.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        message.extend_from_slice(value);
+    }
+
+    fn identities_answer(comments: &[&str]) -> Vec<u8> {
+        let key_blob = STANDARD.decode(KEY).unwrap();
+        let mut message = vec![SSH_AGENT_IDENTITIES_ANSWER];
+        message.extend_from_slice(&(comments.len() as u32).to_be_bytes());
+
+        for comment in comments {
+            push_string(&mut message, &key_blob);
+            push_string(&mut message, comment.as_bytes());
+        }
+
+        message
+    }
+
+    #[test]
+    fn parses_identities_answers() {
+        let identities = parse_identities(&identities_answer(&["work@laptop", "yubikey"])).unwrap();
+
+        assert_eq!(identities.len(), 2);
+        assert_eq!(identities[0].key_type, "ssh-ed25519");
+        assert_eq!(identities[0].fingerprint, FINGERPRINT);
+        assert_eq!(identities[0].comment, "work@laptop");
+        assert_eq!(identities[1].comment, "yubikey");
+
+        assert!(
+            parse_identities(&identities_answer(&[]))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_answers() {
+        let answer = identities_answer(&["work@laptop"]);
+
+        // SSH_AGENT_FAILURE
+        assert!(parse_identities(&[5]).is_none());
+        assert!(parse_identities(&[]).is_none());
+        assert!(parse_identities(&answer[..answer.len() - 1]).is_none());
+
+        // More identities announced than sent
+        let mut answer = answer;
+        answer[4] = 2;
+        assert!(parse_identities(&answer).is_none());
+    }
+
+    #[test]
+    fn rejects_oversized_messages() {
+        let mut stream: &[u8] = &(MAX_AGENT_MESSAGE_LENGTH + 1).to_be_bytes();
+
+        assert!(matches!(
+            read_message(&mut stream),
+            Err(SshError::Io { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn queries_identities_from_the_agent_socket() {
+        use std::os::unix::net::UnixListener;
+
+        let socket_path =
+            std::env::temp_dir().join(format!("gitnuro-agent-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        let agent = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            assert_eq!(
+                read_message(&mut stream).unwrap(),
+                [SSH_AGENTC_REQUEST_IDENTITIES]
+            );
+
+            write_message(&mut stream, &identities_answer(&["work@laptop"])).unwrap();
+        });
+
+        let agent_client = SshAgent::new(Some(socket_path.to_string_lossy().to_string()));
+        let identities = agent_client.identities().unwrap();
+        agent.join().unwrap();
+
+        assert_eq!(identities.len(), 1);
+        assert_eq!(identities[0].fingerprint, FINGERPRINT);
+
+        std::fs::remove_file(socket_path).unwrap();
+    }
+}
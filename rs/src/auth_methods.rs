@@ -0,0 +1,34 @@
+use libssh_rs::AuthMethods;
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AuthMethod {
+    Password,
+    PublicKey,
+    HostBased,
+    KeyboardInteractive,
+    GssapiMic,
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct ServerAuthInfo {
+    /// The server accepted the `none` method, so no authentication is needed.
+    pub authenticated: bool,
+    /// Methods the server allows to continue the authentication.
+    pub methods: Vec<AuthMethod>,
+    /// Message the server sends before authenticating, usually a legal notice.
+    pub banner: Option<String>,
+}
+
+pub fn to_auth_methods(auth_methods: AuthMethods) -> Vec<AuthMethod> {
+    [
+        (AuthMethods::PUBLIC_KEY, AuthMethod::PublicKey),
+        (AuthMethods::INTERACTIVE, AuthMethod::KeyboardInteractive),
+        (AuthMethods::PASSWORD, AuthMethod::Password),
+        (AuthMethods::HOST_BASED, AuthMethod::HostBased),
+        (AuthMethods::GSSAPI_MIC, AuthMethod::GssapiMic),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| auth_methods.contains(*flag))
+    .map(|(_, method)| method)
+    .collect()
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use notify::EventKind;
+
+use crate::FileChanged;
+
+/// Ordering information of a batch sent to [crate::WatchDirectoryNotifier].
+#[derive(uniffi::Record, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BatchInfo {
+    /// Increases by one with every batch sent by the watcher, starting at 1.
+    pub sequence: u64,
+    /// Time the first event of the batch was received, in milliseconds since the Unix epoch.
+    pub first_event_timestamp: u64,
+    /// Time the last event of the batch was received, in milliseconds since the Unix epoch.
+    pub last_event_timestamp: u64,
+}
+
+/// Changes waiting to be sent, along with the time range in which they were received.
+#[derive(Default)]
+pub struct PendingBatch {
+    pub changes: HashMap<FileChanged, Vec<EventKind>>,
+    first_event_timestamp: Option<u128>,
+    last_event_timestamp: u128,
+}
+
+impl PendingBatch {
+    pub fn add(&mut self, file_changed: FileChanged, event_kind: EventKind, timestamp: u128) {
+        self.changes
+            .entry(file_changed)
+            .or_default()
+            .push(event_kind);
+
+        self.first_event_timestamp.get_or_insert(timestamp);
+        self.last_event_timestamp = self.last_event_timestamp.max(timestamp);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Time left until the batch should be sent: once no change has been received for
+    /// `debounce`, or at most `max_delay` after the first one. [None] if there's nothing to send.
+    pub fn remaining_until_due(
+        &self,
+        current_time: u128,
+        debounce: u128,
+        max_delay: u128,
+    ) -> Option<u128> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let first_event_timestamp = self.first_event_timestamp.unwrap_or_default();
+        let due_time =
+            (self.last_event_timestamp + debounce).min(first_event_timestamp + max_delay);
+
+        Some(due_time.saturating_sub(current_time))
+    }
+
+    /// Moves every change of `other` into this batch, widening the time range accordingly.
+    pub fn merge(&mut self, other: &mut PendingBatch) {
+        for (file_changed, mut event_kinds) in other.changes.drain() {
+            self.changes
+                .entry(file_changed)
+                .or_default()
+                .append(&mut event_kinds);
+        }
+
+        if let Some(other_first) = other.first_event_timestamp.take() {
+            self.first_event_timestamp = Some(
+                self.first_event_timestamp
+                    .map_or(other_first, |first| first.min(other_first)),
+            );
+        }
+
+        self.last_event_timestamp = self.last_event_timestamp.max(other.last_event_timestamp);
+        other.clear();
+    }
+
+    pub fn info(&self, sequence: u64) -> BatchInfo {
+        BatchInfo {
+            sequence,
+            first_event_timestamp: self.first_event_timestamp.unwrap_or_default() as u64,
+            last_event_timestamp: self.last_event_timestamp as u64,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+        self.first_event_timestamp = None;
+        self.last_event_timestamp = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::CreateKind;
+
+    use super::*;
+    use crate::FileType;
+
+    fn file_changed(path: &str) -> FileChanged {
+        FileChanged {
+            path: path.to_string(),
+            file_type: FileType::File,
+        }
+    }
+
+    #[test]
+    fn is_due_after_the_debounce_or_the_max_delay() {
+        let mut batch = PendingBatch::default();
+        assert_eq!(batch.remaining_until_due(0, 50, 200), None);
+
+        batch.add(
+            file_changed("/repo/.git/index.lock"),
+            EventKind::Create(CreateKind::File),
+            100,
+        );
+        assert_eq!(batch.remaining_until_due(120, 50, 200), Some(30));
+        assert_eq!(batch.remaining_until_due(160, 50, 200), Some(0));
+
+        // Changes received continuously are still sent once the max delay is reached
+        batch.add(
+            file_changed("/repo/.git/HEAD"),
+            EventKind::Create(CreateKind::File),
+            280,
+        );
+        assert_eq!(batch.remaining_until_due(290, 50, 200), Some(10));
+    }
+}
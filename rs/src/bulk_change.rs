@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::FileChanged;
+
+/// Default amount of paths in a single batch above which a bulk change is reported instead of
+/// every path. Switching branches in most repositories stays below it, while checkouts of huge
+/// trees or tools regenerating whole directories don't send every path to the app.
+pub const DEFAULT_BULK_CHANGE_THRESHOLD: u32 = 10_000;
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BulkChangeMode {
+    /// Report one summary per parent directory with the amount of paths changed in it.
+    DirectorySummaries,
+    /// Only report the total amount of paths changed.
+    SingleMarker,
+}
+
+#[derive(uniffi::Record, Debug, Clone, Eq, PartialEq)]
+pub struct DirectoryChangeSummary {
+    pub path: String,
+    pub count: u64,
+}
+
+#[derive(uniffi::Record, Debug, Clone, Eq, PartialEq)]
+pub struct BulkChange {
+    pub total_count: u64,
+    /// Empty when using [BulkChangeMode::SingleMarker] or when there are so many directories
+    /// that summarizing them would still exceed the threshold.
+    pub directories: Vec<DirectoryChangeSummary>,
+}
+
+impl BulkChange {
+    /// Summarizes the paths by directory. If there are more than `max_directories`, only the
+    /// total amount is kept.
+    pub fn from_paths(paths: &[FileChanged], max_directories: usize) -> BulkChange {
+        let directories = summarize_by_directory(paths);
+
+        BulkChange {
+            total_count: paths.len() as u64,
+            directories: if directories.len() > max_directories {
+                Vec::new()
+            } else {
+                directories
+            },
+        }
+    }
+
+    /// Adds the changes of `other` to this one. Directory summaries are only kept if both have
+    /// them and the result doesn't exceed `max_directories`.
+    pub fn merge(&mut self, other: BulkChange, max_directories: usize) {
+        self.total_count += other.total_count;
+
+        if self.directories.is_empty() || other.directories.is_empty() {
+            self.directories.clear();
+            return;
+        }
+
+        let mut counts: HashMap<String, u64> = self
+            .directories
+            .drain(..)
+            .map(|directory| (directory.path, directory.count))
+            .collect();
+
+        for directory in other.directories {
+            *counts.entry(directory.path).or_insert(0) += directory.count;
+        }
+
+        if counts.len() <= max_directories {
+            self.directories = counts
+                .into_iter()
+                .map(|(path, count)| DirectoryChangeSummary { path, count })
+                .collect();
+
+            self.directories.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BulkChangeSettings {
+    pub threshold: Option<u32>,
+    pub mode: BulkChangeMode,
+}
+
+impl Default for BulkChangeSettings {
+    fn default() -> Self {
+        BulkChangeSettings {
+            threshold: Some(DEFAULT_BULK_CHANGE_THRESHOLD),
+            mode: BulkChangeMode::DirectorySummaries,
+        }
+    }
+}
+
+impl BulkChangeSettings {
+    /// Returns a [BulkChange] when the amount of paths exceeds the configured threshold, or
+    /// [None] if the paths should be sent one by one.
+    pub fn summarize(&self, paths: &[FileChanged]) -> Option<BulkChange> {
+        let threshold = self.threshold? as usize;
+
+        if paths.len() <= threshold {
+            return None;
+        }
+
+        let total_count = paths.len() as u64;
+
+        let directories = match self.mode {
+            BulkChangeMode::DirectorySummaries => {
+                let directories = summarize_by_directory(paths);
+
+                if directories.len() > threshold {
+                    Vec::new()
+                } else {
+                    directories
+                }
+            }
+            BulkChangeMode::SingleMarker => Vec::new(),
+        };
+
+        Some(BulkChange {
+            total_count,
+            directories,
+        })
+    }
+}
+
+fn summarize_by_directory(paths: &[FileChanged]) -> Vec<DirectoryChangeSummary> {
+    let mut counts = HashMap::<&Path, u64>::new();
+
+    for file_changed in paths {
+        let path = Path::new(file_changed.path.as_str());
+        let directory = path.parent().unwrap_or(path);
+
+        *counts.entry(directory).or_insert(0) += 1;
+    }
+
+    let mut directories: Vec<DirectoryChangeSummary> = counts
+        .into_iter()
+        .map(|(path, count)| DirectoryChangeSummary {
+            path: path.to_string_lossy().into_owned(),
+            count,
+        })
+        .collect();
+
+    directories.sort_by(|a, b| a.path.cmp(&b.path));
+
+    directories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileType;
+
+    fn file_changed(path: &str) -> FileChanged {
+        FileChanged {
+            path: path.to_string(),
+            file_type: FileType::File,
+        }
+    }
+
+    fn settings(threshold: u32, mode: BulkChangeMode) -> BulkChangeSettings {
+        BulkChangeSettings {
+            threshold: Some(threshold),
+            mode,
+        }
+    }
+
+    #[test]
+    fn summarizes_only_above_the_threshold() {
+        let paths: Vec<FileChanged> = (0..3)
+            .map(|i| file_changed(&format!("/repo/{i}")))
+            .collect();
+
+        assert_eq!(
+            settings(3, BulkChangeMode::DirectorySummaries).summarize(&paths),
+            None
+        );
+        assert_eq!(
+            settings(2, BulkChangeMode::DirectorySummaries)
+                .summarize(&paths)
+                .map(|bulk_change| bulk_change.total_count),
+            Some(3)
+        );
+
+        let disabled = BulkChangeSettings {
+            threshold: None,
+            mode: BulkChangeMode::DirectorySummaries,
+        };
+        assert_eq!(disabled.summarize(&paths), None);
+    }
+
+    #[test]
+    fn counts_the_changes_of_every_directory() {
+        let paths = [
+            file_changed("/repo/src/a.rs"),
+            file_changed("/repo/src/b.rs"),
+            file_changed("/repo/docs/index.md"),
+            file_changed("/repo/src/c.rs"),
+        ];
+
+        let bulk_change = settings(3, BulkChangeMode::DirectorySummaries)
+            .summarize(&paths)
+            .unwrap();
+
+        assert_eq!(bulk_change.total_count, 4);
+        assert_eq!(
+            bulk_change.directories,
+            vec![
+                DirectoryChangeSummary {
+                    path: "/repo/docs".to_string(),
+                    count: 1,
+                },
+                DirectoryChangeSummary {
+                    path: "/repo/src".to_string(),
+                    count: 3,
+                },
+            ]
+        );
+
+        let marker = settings(3, BulkChangeMode::SingleMarker)
+            .summarize(&paths)
+            .unwrap();
+
+        assert_eq!(marker.total_count, 4);
+        assert!(marker.directories.is_empty());
+    }
+
+    #[test]
+    fn drops_the_summaries_when_there_are_too_many_directories() {
+        let paths: Vec<FileChanged> = (0..4)
+            .map(|i| file_changed(&format!("/repo/{i}/file")))
+            .collect();
+
+        let bulk_change = settings(3, BulkChangeMode::DirectorySummaries)
+            .summarize(&paths)
+            .unwrap();
+
+        assert_eq!(bulk_change.total_count, 4);
+        assert!(bulk_change.directories.is_empty());
+    }
+
+    #[test]
+    fn merging_adds_the_counts_of_the_same_directory() {
+        let mut bulk_change = BulkChange::from_paths(
+            &[file_changed("/repo/src/a.rs"), file_changed("/repo/b.rs")],
+            10,
+        );
+
+        bulk_change.merge(
+            BulkChange::from_paths(&[file_changed("/repo/src/c.rs")], 10),
+            10,
+        );
+
+        assert_eq!(bulk_change.total_count, 3);
+        assert_eq!(
+            bulk_change.directories,
+            vec![
+                DirectoryChangeSummary {
+                    path: "/repo".to_string(),
+                    count: 1,
+                },
+                DirectoryChangeSummary {
+                    path: "/repo/src".to_string(),
+                    count: 2,
+                },
+            ]
+        );
+    }
+}
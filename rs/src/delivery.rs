@@ -0,0 +1,354 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::batch::BatchInfo;
+use crate::bulk_change::BulkChange;
+use crate::metrics::MetricsCounters;
+use crate::{FileChanged, WatchDirectoryNotifier, current_time_as_millis};
+
+/// Default amount of notifications waiting for the consumer before new batches are merged into
+/// the pending ones.
+pub const DEFAULT_DELIVERY_QUEUE_CAPACITY: u32 = 8;
+
+/// Default amount of paths a single batch can contain before it's reported as a bulk change.
+pub const DEFAULT_MAX_BATCH_SIZE: u32 = 50_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeliverySettings {
+    pub queue_capacity: u32,
+    pub max_batch_size: u32,
+}
+
+impl Default for DeliverySettings {
+    fn default() -> Self {
+        DeliverySettings {
+            queue_capacity: DEFAULT_DELIVERY_QUEUE_CAPACITY,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+        }
+    }
+}
+
+pub enum Batch {
+    Changes(Vec<FileChanged>, BatchInfo),
+    BulkChange(BulkChange, BatchInfo),
+}
+
+pub enum Notification {
+    Batch(Batch),
+    IgnoreRulesChanged,
+}
+
+struct QueueState {
+    pending: VecDeque<Notification>,
+    closed: bool,
+}
+
+/// Bounded queue between the watch loop and the notifier, so a slow consumer neither stalls the
+/// loop nor lets the changes pile up. When the queue is full, new batches are merged into the
+/// newest pending notification if it's a batch, so they are never reordered relative to an
+/// [Notification::IgnoreRulesChanged], or replace it if it's one.
+pub struct DeliveryQueue {
+    state: Mutex<QueueState>,
+    available: Condvar,
+    settings: DeliverySettings,
+    metrics: Arc<MetricsCounters>,
+}
+
+impl DeliveryQueue {
+    pub fn new(settings: DeliverySettings, metrics: Arc<MetricsCounters>) -> DeliveryQueue {
+        DeliveryQueue {
+            state: Mutex::new(QueueState {
+                pending: VecDeque::new(),
+                closed: false,
+            }),
+            available: Condvar::new(),
+            settings,
+            metrics,
+        }
+    }
+
+    pub fn push(&self, notification: Notification) {
+        let mut state = self.state.lock().unwrap();
+
+        match notification {
+            // A single pending notification is enough for the consumer to reload the rules, and
+            // a full queue has batches that reload the status anyway
+            Notification::IgnoreRulesChanged => {
+                let already_pending = state
+                    .pending
+                    .iter()
+                    .any(|pending| matches!(pending, Notification::IgnoreRulesChanged));
+
+                if !already_pending && !self.is_full(&state) {
+                    state.pending.push_back(notification);
+                }
+            }
+            Notification::Batch(batch) => {
+                let batch = self.limit_size(batch);
+                let is_full = self.is_full(&state);
+
+                let batch = match state.pending.pop_back() {
+                    Some(Notification::Batch(newest_batch)) if is_full => {
+                        self.metrics.merged_batches.fetch_add(1, Ordering::Relaxed);
+                        self.merge(newest_batch, batch)
+                    }
+                    // Delivering a batch already makes the consumer reload the status, so it takes
+                    // the place of the marker instead of growing the queue past its capacity
+                    Some(Notification::IgnoreRulesChanged) if is_full => batch,
+                    newest => {
+                        state.pending.extend(newest);
+                        batch
+                    }
+                };
+
+                state.pending.push_back(Notification::Batch(batch));
+            }
+        }
+
+        self.available.notify_one();
+    }
+
+    fn is_full(&self, state: &QueueState) -> bool {
+        state.pending.len() >= self.settings.queue_capacity.max(1) as usize
+    }
+
+    /// No more notifications will be pushed. Pending ones are still delivered.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.available.notify_all();
+    }
+
+    /// Delivers notifications until the queue is closed and empty.
+    pub fn deliver_all(&self, notifier: &dyn WatchDirectoryNotifier) {
+        while let Some(notification) = self.pop() {
+            self.record_delivery(&notification);
+
+            match notification {
+                Notification::Batch(Batch::Changes(paths, batch_info)) => {
+                    notifier.detected_change(paths, batch_info)
+                }
+                Notification::Batch(Batch::BulkChange(bulk_change, batch_info)) => {
+                    notifier.detected_bulk_change(bulk_change, batch_info)
+                }
+                Notification::IgnoreRulesChanged => notifier.ignore_rules_changed(),
+            }
+        }
+    }
+
+    fn record_delivery(&self, notification: &Notification) {
+        let (size, batch_info) = match notification {
+            Notification::Batch(Batch::Changes(paths, batch_info)) => {
+                (paths.len() as u64, batch_info)
+            }
+            Notification::Batch(Batch::BulkChange(bulk_change, batch_info)) => {
+                (bulk_change.total_count, batch_info)
+            }
+            Notification::IgnoreRulesChanged => return,
+        };
+
+        let latency = current_time_as_millis()
+            .saturating_sub(batch_info.first_event_timestamp as u128) as u64;
+
+        self.metrics.record_batch_sent(size, latency);
+    }
+
+    fn pop(&self) -> Option<Notification> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(notification) = state.pending.pop_front() {
+                return Some(notification);
+            }
+
+            if state.closed {
+                return None;
+            }
+
+            state = self.available.wait(state).unwrap();
+        }
+    }
+
+    /// Batches with more paths than the maximum batch size are turned into a bulk change.
+    fn limit_size(&self, batch: Batch) -> Batch {
+        match batch {
+            Batch::Changes(paths, batch_info)
+                if paths.len() > self.settings.max_batch_size as usize =>
+            {
+                self.metrics
+                    .dropped_paths
+                    .fetch_add(paths.len() as u64, Ordering::Relaxed);
+
+                Batch::BulkChange(self.to_bulk_change(&paths), batch_info)
+            }
+            batch => batch,
+        }
+    }
+
+    fn merge(&self, pending: Batch, incoming: Batch) -> Batch {
+        match (pending, incoming) {
+            (
+                Batch::Changes(mut paths, pending_info),
+                Batch::Changes(incoming_paths, incoming_info),
+            ) => {
+                let known_paths: HashSet<FileChanged> = paths.iter().cloned().collect();
+
+                paths.extend(
+                    incoming_paths
+                        .into_iter()
+                        .filter(|file_changed| !known_paths.contains(file_changed)),
+                );
+
+                self.limit_size(Batch::Changes(
+                    paths,
+                    merge_batch_info(pending_info, incoming_info),
+                ))
+            }
+            (pending, incoming) => {
+                let (mut bulk_change, pending_info) = self.convert_to_bulk_change(pending);
+                let (incoming_bulk_change, incoming_info) = self.convert_to_bulk_change(incoming);
+
+                bulk_change.merge(incoming_bulk_change, self.settings.max_batch_size as usize);
+
+                Batch::BulkChange(bulk_change, merge_batch_info(pending_info, incoming_info))
+            }
+        }
+    }
+
+    fn convert_to_bulk_change(&self, batch: Batch) -> (BulkChange, BatchInfo) {
+        match batch {
+            Batch::Changes(paths, batch_info) => {
+                self.metrics
+                    .dropped_paths
+                    .fetch_add(paths.len() as u64, Ordering::Relaxed);
+
+                (self.to_bulk_change(&paths), batch_info)
+            }
+            Batch::BulkChange(bulk_change, batch_info) => (bulk_change, batch_info),
+        }
+    }
+
+    fn to_bulk_change(&self, paths: &[FileChanged]) -> BulkChange {
+        BulkChange::from_paths(paths, self.settings.max_batch_size as usize)
+    }
+}
+
+/// The merged batch takes the sequence number of the newest one, so the consumer sees a gap in
+/// the sequence for every merge.
+fn merge_batch_info(older: BatchInfo, newer: BatchInfo) -> BatchInfo {
+    BatchInfo {
+        sequence: newer.sequence,
+        first_event_timestamp: older.first_event_timestamp.min(newer.first_event_timestamp),
+        last_event_timestamp: older.last_event_timestamp.max(newer.last_event_timestamp),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileType;
+
+    fn changes(paths: &[&str], sequence: u64) -> Notification {
+        let paths = paths
+            .iter()
+            .map(|path| FileChanged {
+                path: path.to_string(),
+                file_type: FileType::File,
+            })
+            .collect();
+
+        Notification::Batch(Batch::Changes(
+            paths,
+            BatchInfo {
+                sequence,
+                first_event_timestamp: sequence,
+                last_event_timestamp: sequence,
+            },
+        ))
+    }
+
+    fn queue(queue_capacity: u32) -> DeliveryQueue {
+        DeliveryQueue::new(
+            DeliverySettings {
+                queue_capacity,
+                max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            },
+            Arc::new(MetricsCounters::default()),
+        )
+    }
+
+    fn pending_paths(queue: &DeliveryQueue) -> Vec<Option<Vec<String>>> {
+        queue
+            .state
+            .lock()
+            .unwrap()
+            .pending
+            .iter()
+            .map(|notification| match notification {
+                Notification::Batch(Batch::Changes(paths, _)) => {
+                    Some(paths.iter().map(|path| path.path.clone()).collect())
+                }
+                Notification::Batch(Batch::BulkChange(..)) => Some(Vec::new()),
+                Notification::IgnoreRulesChanged => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_into_the_newest_batch_when_full() {
+        let queue = queue(2);
+        queue.push(changes(&["a"], 0));
+        queue.push(changes(&["b"], 1));
+        queue.push(changes(&["b", "c"], 2));
+
+        assert_eq!(
+            pending_paths(&queue),
+            [
+                Some(vec!["a".to_string()]),
+                Some(vec!["b".to_string(), "c".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn never_merges_batches_across_ignore_rules_changes() {
+        let queue = queue(3);
+        queue.push(changes(&["a"], 0));
+        queue.push(Notification::IgnoreRulesChanged);
+        queue.push(changes(&["b"], 1));
+        queue.push(Notification::IgnoreRulesChanged);
+        queue.push(changes(&["c"], 2));
+
+        assert_eq!(
+            pending_paths(&queue),
+            [
+                Some(vec!["a".to_string()]),
+                None,
+                Some(vec!["b".to_string(), "c".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn stays_within_capacity_around_ignore_rules_changes() {
+        let batch_queue = queue(2);
+        batch_queue.push(changes(&["a"], 0));
+        batch_queue.push(Notification::IgnoreRulesChanged);
+        batch_queue.push(changes(&["b"], 1));
+
+        assert_eq!(
+            pending_paths(&batch_queue),
+            [Some(vec!["a".to_string()]), Some(vec!["b".to_string()])]
+        );
+
+        batch_queue.push(Notification::IgnoreRulesChanged);
+
+        assert_eq!(pending_paths(&batch_queue).len(), 2);
+
+        let single_queue = queue(1);
+        single_queue.push(Notification::IgnoreRulesChanged);
+        single_queue.push(changes(&["a"], 0));
+
+        assert_eq!(pending_paths(&single_queue), [Some(vec!["a".to_string()])]);
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use std::time::Instant;
+
+/// Default interval at which paths on network or FUSE filesystems are polled.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Filesystems whose changes are not (or not always) reported by the native backend, as they can
+/// be modified by other hosts or by a userspace process.
+const POLLED_FILESYSTEM_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "davfs",
+    "fuse",
+    "fuseblk",
+    // macOS
+    "afpfs",
+    "webdav",
+    "macfuse",
+    "osxfuse",
+];
+
+/// How long the parsed `/proc/self/mountinfo` is reused before reading it again, so adding
+/// watches doesn't read it every time while still noticing new mounts.
+#[cfg(target_os = "linux")]
+const MOUNT_TABLE_MAX_AGE: Duration = Duration::from_secs(30);
+
+#[cfg(target_os = "linux")]
+static MOUNT_TABLE: Mutex<Option<MountTable>> = Mutex::new(None);
+
+#[cfg(target_os = "linux")]
+struct MountTable {
+    read_at: Instant,
+    /// Mount points and their filesystem types.
+    mounts: Vec<(PathBuf, String)>,
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WatchBackend {
+    /// Events are received from the OS (inotify, FSEvents, ReadDirectoryChangesW...).
+    Native,
+    /// The path is on a network or FUSE filesystem, so it's periodically scanned for changes.
+    Polling,
+}
+
+pub fn backend_for(path: &Path) -> WatchBackend {
+    match filesystem_type(path) {
+        Some(filesystem_type) if is_polled_filesystem(&filesystem_type) => WatchBackend::Polling,
+        _ => WatchBackend::Native,
+    }
+}
+
+fn is_polled_filesystem(filesystem_type: &str) -> bool {
+    POLLED_FILESYSTEM_TYPES.contains(&filesystem_type) || filesystem_type.starts_with("fuse.")
+}
+
+/// Type of the filesystem the path is mounted on, according to the mount point that contains it.
+#[cfg(target_os = "linux")]
+fn filesystem_type(path: &Path) -> Option<String> {
+    let path = std::fs::canonicalize(path).ok()?;
+    let mut mount_table = MOUNT_TABLE.lock().unwrap();
+
+    let is_outdated = mount_table
+        .as_ref()
+        .is_none_or(|mount_table| mount_table.read_at.elapsed() > MOUNT_TABLE_MAX_AGE);
+
+    if is_outdated {
+        let mount_info = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+
+        *mount_table = Some(MountTable {
+            read_at: Instant::now(),
+            mounts: mount_info
+                .lines()
+                .filter_map(parse_mount_info_line)
+                .map(|(mount_point, filesystem_type)| (PathBuf::from(mount_point), filesystem_type))
+                .collect(),
+        });
+    }
+
+    mount_table
+        .as_ref()?
+        .mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+        .map(|(_, filesystem_type)| filesystem_type.clone())
+}
+
+/// Type of the filesystem the path is on, as reported by statfs(2).
+#[cfg(target_os = "macos")]
+fn filesystem_type(path: &Path) -> Option<String> {
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    let filesystem_type = unsafe { CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+
+    Some(filesystem_type.to_string_lossy().into_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn filesystem_type(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Returns the mount point and filesystem type of a `/proc/self/mountinfo` line, which looks like
+/// `36 35 98:0 /mnt1 /mnt/parent rw,noatime master:1 - ext3 /dev/root rw,errors=continue`.
+#[cfg(target_os = "linux")]
+fn parse_mount_info_line(line: &str) -> Option<(String, String)> {
+    let (mount_fields, filesystem_fields) = line.split_once(" - ")?;
+    let mount_point = mount_fields.split(' ').nth(4)?;
+    let filesystem_type = filesystem_fields.split(' ').next()?;
+
+    Some((unescape_octal(mount_point), filesystem_type.to_string()))
+}
+
+/// Spaces, tabs, newlines and backslashes are escaped as `\ooo` in mount points.
+#[cfg(target_os = "linux")]
+fn unescape_octal(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|octal| std::str::from_utf8(octal).ok())
+            .and_then(|octal| u8::from_str_radix(octal, 8).ok());
+
+        match escaped {
+            Some(byte) => {
+                unescaped.push(byte);
+                i += 4;
+            }
+            None => {
+                unescaped.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polls_network_and_fuse_filesystems() {
+        assert!(is_polled_filesystem("nfs4"));
+        assert!(is_polled_filesystem("fuse.sshfs"));
+        assert!(is_polled_filesystem("smbfs"));
+        assert!(!is_polled_filesystem("ext4"));
+        assert!(!is_polled_filesystem("apfs"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parses_mount_info_lines() {
+        assert_eq!(
+            parse_mount_info_line(
+                r"36 35 98:0 /mnt1 /mnt/my\040share rw,noatime master:1 - cifs //server/share rw"
+            ),
+            Some(("/mnt/my share".to_string(), "cifs".to_string()))
+        );
+        assert_eq!(parse_mount_info_line("36 35 98:0 /mnt1"), None);
+    }
+}
@@ -0,0 +1,273 @@
+use std::io;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use std::{fs, thread};
+
+use crate::fsmonitor_protocol::{FSMONITOR_SOCKET_NAME, read_packetized, write_packetized};
+use crate::ignore_rules::IgnoreTracking;
+use crate::journal::SharedJournal;
+use crate::logger;
+use crate::watched_roots::WatchedRoots;
+
+/// Prefix of the files created in the git directory to make sure every change made before a
+/// query has been received by the watcher before answering it.
+pub const COOKIE_PREFIX: &str = ".gitnuro-fsmonitor-cookie-";
+
+const COOKIE_TIMEOUT: Duration = Duration::from_secs(1);
+const COOKIE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const TAG: &str = "FsMonitor";
+
+/// Answers git's fsmonitor (protocol v2) queries from the change journal, through the same unix
+/// socket protocol as `git fsmonitor--daemon`: the request is the token of the last query as a
+/// pkt-line stream, the response is the new token and the changed paths, all NUL terminated.
+pub struct FsMonitorServer {
+    socket_path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FsMonitorServer {
+    /// `roots` tell whether the cookies created in `git_dir` will be reported by the watcher,
+    /// and together with `ignore_tracking`, which worktree directories are not watched at all.
+    pub fn start(
+        git_dir: PathBuf,
+        journal: SharedJournal,
+        roots: Arc<RwLock<WatchedRoots>>,
+        ignore_tracking: Arc<RwLock<Option<IgnoreTracking>>>,
+    ) -> io::Result<FsMonitorServer> {
+        let socket_path = git_dir.join(FSMONITOR_SOCKET_NAME);
+
+        if socket_path.exists() {
+            if UnixStream::connect(&socket_path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "Another fsmonitor daemon is already running for this repository",
+                ));
+            }
+
+            // Left behind by a process that didn't shut down properly
+            fs::remove_file(&socket_path)?;
+        }
+
+        let listener = UnixListener::bind(&socket_path)?;
+        listener.set_nonblocking(true)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let thread = thread::spawn(move || {
+            let cookie_counter = AtomicU64::new(0);
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let watched = Watched {
+                            git_dir: &git_dir,
+                            roots: &roots,
+                            ignore_tracking: &ignore_tracking,
+                            cookie_counter: &cookie_counter,
+                        };
+
+                        // Errors only concern this client, which falls back to scanning the worktree
+                        let _ = handle_client(stream, &journal, &watched);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                    Err(e) => {
+                        logger::error(TAG, format!("Accepting a client failed: {e:?}"));
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                }
+            }
+        });
+
+        Ok(FsMonitorServer {
+            socket_path,
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for FsMonitorServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
+
+pub fn is_cookie_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with(COOKIE_PREFIX))
+}
+
+/// What the watcher receives changes from, shared with the server thread.
+struct Watched<'a> {
+    git_dir: &'a Path,
+    roots: &'a RwLock<WatchedRoots>,
+    ignore_tracking: &'a RwLock<Option<IgnoreTracking>>,
+    cookie_counter: &'a AtomicU64,
+}
+
+impl Watched<'_> {
+    /// Directories of the worktree whose changes are not received, so git has to check them
+    /// itself, or [None] if that can't be told and the whole worktree has to be checked.
+    fn unwatched_directories(&self, worktree: &Path) -> Option<Vec<PathBuf>> {
+        let roots = self.roots.read().unwrap();
+
+        if roots
+            .root_containing(worktree)
+            .is_some_and(|root| root.is_recursive)
+        {
+            return Some(Vec::new());
+        }
+
+        let ignore_tracking = self.ignore_tracking.read().unwrap();
+        let ignore_tracking = ignore_tracking.as_ref()?;
+
+        if !roots.covers(worktree) {
+            return None;
+        }
+
+        Some(
+            ignore_tracking
+                .ignored_directories
+                .iter()
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+fn handle_client(
+    mut stream: UnixStream,
+    journal: &SharedJournal,
+    watched: &Watched,
+) -> io::Result<()> {
+    // The listener is non-blocking, but clients are served synchronously
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let request = read_packetized(&mut stream)?;
+    let request = String::from_utf8_lossy(&request);
+    let token = request.trim_end_matches('\0');
+
+    let response = match token {
+        // Commands of git's builtin daemon that don't apply to us
+        "quit" | "flush" => Vec::new(),
+        token => {
+            wait_for_cookie(journal, watched);
+            build_response(token, journal, watched)
+        }
+    };
+
+    write_packetized(&mut stream, &response)
+}
+
+/// Creates a cookie file and waits until the watcher reports it, so changes made right before the
+/// query are included in the answer. Nothing is waited for if the git directory isn't watched, as
+/// the cookie would never be reported.
+fn wait_for_cookie(journal: &SharedJournal, watched: &Watched) {
+    if !watched.roots.read().unwrap().covers(watched.git_dir) {
+        return;
+    }
+
+    let Some(start_tick) = journal.read().unwrap().as_ref().map(|j| j.tick()) else {
+        return;
+    };
+
+    let cookie_name = format!(
+        "{COOKIE_PREFIX}{}-{}",
+        std::process::id(),
+        watched.cookie_counter.fetch_add(1, Ordering::Relaxed)
+    );
+    let cookie_path = watched.git_dir.join(cookie_name);
+
+    if fs::write(&cookie_path, []).is_err() {
+        return;
+    }
+
+    let cookie = cookie_path.to_string_lossy().into_owned();
+    let start = Instant::now();
+
+    while start.elapsed() < COOKIE_TIMEOUT {
+        let seen = journal
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_none_or(|j| j.has_change_since(start_tick, &cookie));
+
+        if seen {
+            break;
+        }
+
+        thread::sleep(COOKIE_POLL_INTERVAL);
+    }
+
+    let _ = fs::remove_file(&cookie_path);
+}
+
+fn build_response(token: &str, journal: &SharedJournal, watched: &Watched) -> Vec<u8> {
+    let journal = journal.read().unwrap();
+
+    let Some(journal) = journal.as_ref() else {
+        // Without journal the only safe answer is that everything may have changed
+        return b"\0/\0".to_vec();
+    };
+
+    let result = journal.changes_since(token);
+    let worktree = journal.worktree();
+
+    let mut response = Vec::new();
+    response.extend_from_slice(result.clock.as_bytes());
+    response.push(0);
+
+    let unwatched_directories = match watched.unwatched_directories(worktree) {
+        Some(directories) if !result.is_fresh_instance => directories,
+        _ => {
+            response.extend_from_slice(b"/\0");
+            return response;
+        }
+    };
+
+    let changed_paths = result.changed_paths.iter().map(PathBuf::from);
+
+    for path in unwatched_directories.into_iter().chain(changed_paths) {
+        let Ok(relative_path) = path.strip_prefix(worktree) else {
+            continue;
+        };
+
+        let relative_path = relative_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if relative_path.is_empty() || relative_path == ".git" || relative_path.starts_with(".git/")
+        {
+            continue;
+        }
+
+        response.extend_from_slice(relative_path.as_bytes());
+
+        // Directories are flagged with a trailing slash so git invalidates everything below
+        if path.is_dir() {
+            response.push(b'/');
+        }
+
+        response.push(0);
+    }
+
+    response
+}
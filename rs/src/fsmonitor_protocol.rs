@@ -0,0 +1,77 @@
+//! Parts of the `git fsmonitor--daemon` protocol shared by the fsmonitor server and the
+//! `gitnuro-fsmonitor-hook` binary, which includes this file directly as it can't link the
+//! library.
+
+use std::io;
+use std::io::{Read, Write};
+
+/// Same location git uses for its builtin daemon, so `core.fsmonitor=true` finds it.
+pub const FSMONITOR_SOCKET_NAME: &str = "fsmonitor--daemon.ipc";
+
+const MAX_PACKET_DATA_LEN: usize = 65516;
+const FLUSH_PACKET: &[u8] = b"0000";
+
+/// Reads pkt-lines until a flush packet and returns their data.
+pub fn read_packetized(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+
+    loop {
+        let mut length_hex = [0u8; 4];
+        stream.read_exact(&mut length_hex)?;
+
+        let length = std::str::from_utf8(&length_hex)
+            .ok()
+            .and_then(|hex| usize::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid pkt-line length"))?;
+
+        // Flush packet
+        if length == 0 {
+            return Ok(data);
+        }
+
+        if length < 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid pkt-line length",
+            ));
+        }
+
+        let start = data.len();
+        data.resize(start + length - 4, 0);
+        stream.read_exact(&mut data[start..])?;
+    }
+}
+
+/// Writes `data` as pkt-lines followed by a flush packet.
+pub fn write_packetized(stream: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    for chunk in data.chunks(MAX_PACKET_DATA_LEN) {
+        write!(stream, "{:04x}", chunk.len() + 4)?;
+        stream.write_all(chunk)?;
+    }
+
+    stream.write_all(FLUSH_PACKET)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_packetized_data() {
+        let data: Vec<u8> = (0..MAX_PACKET_DATA_LEN + 10).map(|i| i as u8).collect();
+        let mut stream = Vec::new();
+        write_packetized(&mut stream, &data).unwrap();
+
+        assert_eq!(&stream[..4], b"fff0");
+        assert!(stream.ends_with(FLUSH_PACKET));
+        assert_eq!(read_packetized(&mut stream.as_slice()).unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_invalid_lengths() {
+        assert!(read_packetized(&mut b"0003".as_slice()).is_err());
+        assert!(read_packetized(&mut b"zzzz".as_slice()).is_err());
+        assert!(read_packetized(&mut b"0009abc".as_slice()).is_err());
+    }
+}
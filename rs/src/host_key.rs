@@ -0,0 +1,163 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use libssh_rs::{KnownHosts, PublicKeyHashType};
+use sha2::{Digest, Sha256};
+
+use crate::session_options::StrictHostKeyChecking;
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HostKeyStatus {
+    /// The server key matches the one stored in known_hosts.
+    Known,
+    /// The server isn't in known_hosts (or the file doesn't exist yet).
+    Unknown,
+    /// The server is in known_hosts with a different key, which could mean someone is
+    /// intercepting the connection.
+    Changed,
+    /// The server is in known_hosts, but only with a key of another type.
+    OtherType,
+}
+
+impl From<KnownHosts> for HostKeyStatus {
+    fn from(known_hosts: KnownHosts) -> Self {
+        match known_hosts {
+            KnownHosts::Ok => HostKeyStatus::Known,
+            KnownHosts::NotFound | KnownHosts::Unknown => HostKeyStatus::Unknown,
+            KnownHosts::Changed => HostKeyStatus::Changed,
+            KnownHosts::Other => HostKeyStatus::OtherType,
+        }
+    }
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct HostKeyInfo {
+    pub host: String,
+    pub port: u16,
+    /// SHA256 fingerprint of the server key, in the format printed by OpenSSH
+    /// (`SHA256:` followed by the unpadded base64 hash).
+    pub fingerprint: String,
+    pub status: HostKeyStatus,
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HostKeyDecision {
+    Reject,
+    /// Trust the key for this connection only.
+    AcceptOnce,
+    /// Trust the key and add it to known_hosts. For a [HostKeyStatus::Changed] key, the stored
+    /// keys of the server are replaced, so it should only be returned when the user explicitly
+    /// asked for it.
+    AcceptAndSave,
+}
+
+#[uniffi::export(callback_interface)]
+pub trait HostKeyVerifier: Send + Sync {
+    /// Called when the server key isn't known, so the user can check its fingerprint.
+    fn verify_host_key(&self, host_key: HostKeyInfo) -> HostKeyDecision;
+}
+
+/// Decision taken without asking the [HostKeyVerifier], if the policy allows it.
+pub fn decision_for_policy(
+    policy: StrictHostKeyChecking,
+    status: HostKeyStatus,
+) -> Option<HostKeyDecision> {
+    match (policy, status) {
+        (_, HostKeyStatus::Known) => Some(HostKeyDecision::AcceptOnce),
+        (StrictHostKeyChecking::Yes, _) => Some(HostKeyDecision::Reject),
+        (StrictHostKeyChecking::Ask, _) => None,
+        (StrictHostKeyChecking::AcceptNew, HostKeyStatus::Unknown) => {
+            Some(HostKeyDecision::AcceptAndSave)
+        }
+        (StrictHostKeyChecking::AcceptNew, _) => Some(HostKeyDecision::Reject),
+        (StrictHostKeyChecking::No, _) => Some(HostKeyDecision::AcceptOnce),
+    }
+}
+
+pub fn fingerprint(key: &libssh_rs::SshKey) -> Result<String, libssh_rs::Error> {
+    let hash = key.get_public_key_hash(PublicKeyHashType::Sha256)?;
+
+    Ok(format_fingerprint(&hash))
+}
+
+/// Fingerprint of a public key blob, such as the decoded key of a known_hosts entry.
+pub fn fingerprint_of_blob(key_blob: &[u8]) -> String {
+    format_fingerprint(&Sha256::digest(key_blob))
+}
+
+fn format_fingerprint(sha256_hash: &[u8]) -> String {
+    format!("SHA256:{}", STANDARD_NO_PAD.encode(sha256_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICIES: [StrictHostKeyChecking; 4] = [
+        StrictHostKeyChecking::Yes,
+        StrictHostKeyChecking::Ask,
+        StrictHostKeyChecking::AcceptNew,
+        StrictHostKeyChecking::No,
+    ];
+
+    const NOT_KNOWN_STATUSES: [HostKeyStatus; 3] = [
+        HostKeyStatus::Unknown,
+        HostKeyStatus::Changed,
+        HostKeyStatus::OtherType,
+    ];
+
+    #[test]
+    fn known_keys_are_accepted_without_saving_them_again() {
+        for policy in POLICIES {
+            assert_eq!(
+                decision_for_policy(policy, HostKeyStatus::Known),
+                Some(HostKeyDecision::AcceptOnce)
+            );
+        }
+    }
+
+    #[test]
+    fn strict_checking_rejects_every_key_not_known() {
+        for status in NOT_KNOWN_STATUSES {
+            assert_eq!(
+                decision_for_policy(StrictHostKeyChecking::Yes, status),
+                Some(HostKeyDecision::Reject)
+            );
+        }
+    }
+
+    #[test]
+    fn ask_leaves_every_key_not_known_to_the_verifier() {
+        for status in NOT_KNOWN_STATUSES {
+            assert_eq!(
+                decision_for_policy(StrictHostKeyChecking::Ask, status),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn accept_new_only_saves_unknown_keys() {
+        assert_eq!(
+            decision_for_policy(StrictHostKeyChecking::AcceptNew, HostKeyStatus::Unknown),
+            Some(HostKeyDecision::AcceptAndSave)
+        );
+        assert_eq!(
+            decision_for_policy(StrictHostKeyChecking::AcceptNew, HostKeyStatus::Changed),
+            Some(HostKeyDecision::Reject)
+        );
+        assert_eq!(
+            decision_for_policy(StrictHostKeyChecking::AcceptNew, HostKeyStatus::OtherType),
+            Some(HostKeyDecision::Reject)
+        );
+    }
+
+    #[test]
+    fn disabled_checking_never_saves_keys() {
+        for status in NOT_KNOWN_STATUSES {
+            assert_eq!(
+                decision_for_policy(StrictHostKeyChecking::No, status),
+                Some(HostKeyDecision::AcceptOnce)
+            );
+        }
+    }
+}
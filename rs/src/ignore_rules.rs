@@ -0,0 +1,672 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::{fs, thread};
+
+const GITIGNORE_FILE_NAME: &str = ".gitignore";
+const GIT_DIR_NAME: &str = ".git";
+
+/// Ignore rules of a worktree, built from the global excludes file, `info/exclude` and every
+/// `.gitignore` found in non-ignored directories.
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    worktree: PathBuf,
+    /// Sorted from lowest to highest precedence, so the last matching pattern wins.
+    files: Vec<IgnoreFile>,
+}
+
+#[derive(Debug)]
+struct IgnoreFile {
+    /// Directory the patterns are relative to, as a worktree-relative path with `/` separators.
+    /// Empty for the worktree root, `info/exclude` and the global excludes file.
+    base: String,
+    patterns: Vec<IgnorePattern>,
+}
+
+#[derive(Debug)]
+struct IgnorePattern {
+    glob: String,
+    negated: bool,
+    dir_only: bool,
+    /// Patterns with a slash are matched against the whole relative path, the rest only
+    /// against the file name.
+    anchored: bool,
+}
+
+impl IgnoreRules {
+    fn new(worktree: PathBuf) -> IgnoreRules {
+        IgnoreRules {
+            worktree,
+            files: Vec::new(),
+        }
+    }
+
+    fn add_file(&mut self, path: &Path, base: String) {
+        let Ok(content) = fs::read_to_string(path) else {
+            return;
+        };
+
+        let patterns: Vec<IgnorePattern> = content.lines().filter_map(parse_pattern).collect();
+
+        if !patterns.is_empty() {
+            self.files.push(IgnoreFile { base, patterns });
+        }
+    }
+
+    /// Checks if the path itself matches the rules, without looking at its parent directories.
+    fn matches(&self, relative_path: &str, is_dir: bool) -> bool {
+        let mut ignored = false;
+
+        for file in &self.files {
+            let path_in_base = if file.base.is_empty() {
+                relative_path
+            } else {
+                match relative_path
+                    .strip_prefix(file.base.as_str())
+                    .and_then(|p| p.strip_prefix('/'))
+                {
+                    Some(path_in_base) => path_in_base,
+                    None => continue,
+                }
+            };
+
+            for pattern in &file.patterns {
+                if pattern.matches(path_in_base, is_dir) {
+                    ignored = !pattern.negated;
+                }
+            }
+        }
+
+        ignored
+    }
+
+    /// A path is ignored if it or any of its parent directories is ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(relative_path) = to_relative_path(&self.worktree, path) else {
+            return false;
+        };
+
+        let mut prefix_end = 0;
+
+        while let Some(separator) = relative_path[prefix_end..].find('/') {
+            let parent = &relative_path[..prefix_end + separator];
+
+            if self.matches(parent, true) {
+                return true;
+            }
+
+            prefix_end += separator + 1;
+        }
+
+        self.matches(&relative_path, is_dir)
+    }
+}
+
+impl IgnorePattern {
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        if self.anchored {
+            wildmatch(self.glob.as_bytes(), path.as_bytes())
+        } else {
+            let file_name = path.rsplit('/').next().unwrap_or(path);
+            wildmatch(self.glob.as_bytes(), file_name.as_bytes())
+        }
+    }
+}
+
+fn parse_pattern(line: &str) -> Option<IgnorePattern> {
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut pattern = trim_unescaped_trailing_spaces(line);
+
+    // A leading backslash escapes a literal "!" or "#"
+    let negated = pattern.starts_with('!');
+    if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+        pattern = &pattern[1..];
+    }
+
+    let dir_only = pattern.ends_with('/');
+    if dir_only {
+        pattern = pattern.trim_end_matches('/');
+    }
+
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let anchored = pattern.contains('/');
+    let glob = pattern.strip_prefix('/').unwrap_or(pattern).to_string();
+
+    Some(IgnorePattern {
+        glob,
+        negated,
+        dir_only,
+        anchored,
+    })
+}
+
+fn trim_unescaped_trailing_spaces(line: &str) -> &str {
+    let mut end = line.len();
+
+    while end > 0 && line.as_bytes()[end - 1] == b' ' {
+        if end > 1 && line.as_bytes()[end - 2] == b'\\' {
+            break;
+        }
+        end -= 1;
+    }
+
+    &line[..end]
+}
+
+/// Glob matching following git's `wildmatch` rules for paths: `*` and `?` don't match `/`,
+/// while `**` between slashes matches any amount of directories.
+fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    let Some(&first) = pattern.first() else {
+        return text.is_empty();
+    };
+
+    match first {
+        b'*' if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+
+            if let Some(rest) = rest.strip_prefix(b"/") {
+                // "**/" matches zero or more directories
+                wildmatch(rest, text)
+                    || text
+                        .iter()
+                        .enumerate()
+                        .any(|(i, c)| *c == b'/' && wildmatch(rest, &text[i + 1..]))
+            } else {
+                (0..=text.len()).any(|i| wildmatch(rest, &text[i..]))
+            }
+        }
+        b'*' => {
+            let rest = &pattern[1..];
+
+            for i in 0..=text.len() {
+                if wildmatch(rest, &text[i..]) {
+                    return true;
+                }
+
+                if i < text.len() && text[i] == b'/' {
+                    break;
+                }
+            }
+
+            false
+        }
+        b'?' => !text.is_empty() && text[0] != b'/' && wildmatch(&pattern[1..], &text[1..]),
+        b'[' => match (text.first(), match_class(pattern, text.first().copied())) {
+            (Some(_), Some((true, length))) => wildmatch(&pattern[length..], &text[1..]),
+            (_, Some((false, _))) | (None, _) => false,
+            // Unterminated classes are matched literally
+            (Some(c), None) => *c == b'[' && wildmatch(&pattern[1..], &text[1..]),
+        },
+        b'\\' if pattern.len() > 1 => {
+            !text.is_empty() && text[0] == pattern[1] && wildmatch(&pattern[2..], &text[1..])
+        }
+        c => !text.is_empty() && text[0] == c && wildmatch(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Matches a bracket expression at the start of the pattern. Returns whether the character
+/// matched and the length of the expression, or [None] if the expression is not terminated.
+fn match_class(pattern: &[u8], c: Option<u8>) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some(b'!') | Some(b'^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+
+    loop {
+        let current = *pattern.get(i)?;
+
+        if current == b']' && !first {
+            break;
+        }
+
+        first = false;
+
+        let (start, next) = if current == b'\\' {
+            (*pattern.get(i + 1)?, i + 2)
+        } else {
+            (current, i + 1)
+        };
+
+        if pattern.get(next) == Some(&b'-') && pattern.get(next + 1).is_some_and(|e| *e != b']') {
+            let end = pattern[next + 1];
+            matched |= c.is_some_and(|c| c != b'/' && start <= c && c <= end);
+            i = next + 2;
+        } else {
+            matched |= c == Some(start);
+            i = next;
+        }
+    }
+
+    Some((matched != negated && c != Some(b'/'), i + 1))
+}
+
+fn to_relative_path(worktree: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(worktree).ok()?;
+
+    let components: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    if components.is_empty() {
+        None
+    } else {
+        Some(components.join("/"))
+    }
+}
+
+/// Location of the global excludes file when `core.excludesFile` is not set.
+fn default_excludes_file() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("git").join("ignore"))
+}
+
+/// Keeps the ignore rules of a worktree up to date with the files they are read from.
+#[derive(Debug)]
+pub struct IgnoreTracking {
+    worktree: PathBuf,
+    git_dir: PathBuf,
+    excludes_file: Option<PathBuf>,
+    rules: IgnoreRules,
+    /// Non-ignored directories of the worktree according to the last scan.
+    pub directories: HashSet<PathBuf>,
+    /// Ignored directories found next to [IgnoreTracking::directories]. Their contents are
+    /// neither traversed nor watched.
+    pub ignored_directories: HashSet<PathBuf>,
+}
+
+pub struct DirectoriesDiff {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+/// Rules and directories found by walking the worktree, see [IgnoreScanner::scan].
+pub struct IgnoreScan {
+    rules: IgnoreRules,
+    directories: HashSet<PathBuf>,
+    ignored_directories: HashSet<PathBuf>,
+}
+
+/// Locations the ignore rules of a worktree are read from, kept apart from [IgnoreTracking] so
+/// the worktree can be walked without holding it.
+#[derive(Debug, Clone)]
+pub struct IgnoreScanner {
+    worktree: PathBuf,
+    git_dir: PathBuf,
+    excludes_file: Option<PathBuf>,
+}
+
+impl IgnoreTracking {
+    pub fn new(worktree: PathBuf, git_dir: PathBuf, excludes_file: Option<PathBuf>) -> Self {
+        let excludes_file = excludes_file.or_else(default_excludes_file);
+
+        let mut tracking = IgnoreTracking {
+            rules: IgnoreRules::new(worktree.clone()),
+            worktree,
+            git_dir,
+            excludes_file,
+            directories: HashSet::new(),
+            ignored_directories: HashSet::new(),
+        };
+
+        tracking.apply(tracking.scanner().scan());
+
+        tracking
+    }
+
+    pub fn scanner(&self) -> IgnoreScanner {
+        IgnoreScanner {
+            worktree: self.worktree.clone(),
+            git_dir: self.git_dir.clone(),
+            excludes_file: self.excludes_file.clone(),
+        }
+    }
+
+    /// Directories that contain the files ignore rules are read from, other than the worktree
+    /// directories themselves.
+    pub fn rule_directories(&self) -> Vec<PathBuf> {
+        let mut directories = vec![self.git_dir.join("info")];
+
+        if let Some(parent) = self.excludes_file.as_ref().and_then(|f| f.parent()) {
+            directories.push(parent.to_path_buf());
+        }
+
+        directories
+            .into_iter()
+            .filter(|directory| directory.is_dir())
+            .collect()
+    }
+
+    /// Whether changes to the path should be hidden from the consumer. Paths in the git
+    /// directory and the rules files themselves are never considered ignored, while the other
+    /// files next to the global excludes file always are, as its directory is only watched for it.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path.starts_with(&self.git_dir) || self.is_rules_file(path) {
+            return false;
+        }
+
+        self.is_next_to_excludes_file(path) || self.rules.is_ignored(path, is_dir)
+    }
+
+    fn is_next_to_excludes_file(&self, path: &Path) -> bool {
+        let excludes_directory = self.excludes_file.as_ref().and_then(|f| f.parent());
+
+        excludes_directory.is_some_and(|directory| {
+            path.parent() == Some(directory) && !directory.starts_with(&self.worktree)
+        })
+    }
+
+    pub fn is_rules_file(&self, path: &Path) -> bool {
+        (path.starts_with(&self.worktree)
+            && path
+                .file_name()
+                .is_some_and(|name| name == GITIGNORE_FILE_NAME))
+            || path == self.git_dir.join("info").join("exclude")
+            || self.excludes_file.as_deref() == Some(path)
+    }
+
+    /// Replaces the rules with the ones of a newer scan and returns which directories should start
+    /// or stop being watched.
+    pub fn apply(&mut self, scan: IgnoreScan) -> DirectoriesDiff {
+        let added = scan
+            .directories
+            .difference(&self.directories)
+            .cloned()
+            .collect();
+        let removed = self
+            .directories
+            .difference(&scan.directories)
+            .cloned()
+            .collect();
+
+        self.rules = scan.rules;
+        self.directories = scan.directories;
+        self.ignored_directories = scan.ignored_directories;
+
+        DirectoriesDiff { added, removed }
+    }
+}
+
+impl IgnoreScanner {
+    /// Walks the worktree loading every `.gitignore` and returns the resulting rules, the
+    /// directories that are not ignored and the ignored ones found while walking them. Ignored
+    /// directories are not traversed, as git does.
+    pub fn scan(&self) -> IgnoreScan {
+        let mut rules = IgnoreRules::new(self.worktree.clone());
+
+        if let Some(excludes_file) = &self.excludes_file {
+            rules.add_file(excludes_file, String::new());
+        }
+
+        rules.add_file(&self.git_dir.join("info").join("exclude"), String::new());
+
+        let mut directories = HashSet::new();
+        let mut ignored_directories = HashSet::new();
+        let mut pending = vec![self.worktree.clone()];
+
+        while let Some(directory) = pending.pop() {
+            let base = to_relative_path(&self.worktree, &directory).unwrap_or_default();
+            rules.add_file(&directory.join(GITIGNORE_FILE_NAME), base);
+
+            let Ok(entries) = fs::read_dir(&directory) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+
+                if !is_dir || entry.file_name() == GIT_DIR_NAME {
+                    continue;
+                }
+
+                let path = entry.path();
+
+                let Some(relative_path) = to_relative_path(&self.worktree, &path) else {
+                    continue;
+                };
+
+                if rules.matches(&relative_path, true) {
+                    ignored_directories.insert(path);
+                } else {
+                    directories.insert(path.clone());
+                    pending.push(path);
+                }
+            }
+        }
+
+        IgnoreScan {
+            rules,
+            directories,
+            ignored_directories,
+        }
+    }
+}
+
+/// Walks the worktree on a background thread when ignore rules change, so the watch loop keeps
+/// receiving events while a large worktree is read.
+pub struct BackgroundIgnoreScan {
+    sender: Sender<IgnoreScan>,
+    receiver: Receiver<IgnoreScan>,
+    is_running: bool,
+    /// Set when the rules change again during a walk, which may have read them before the change.
+    pending: Option<IgnoreScanner>,
+}
+
+impl Default for BackgroundIgnoreScan {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+
+        BackgroundIgnoreScan {
+            sender,
+            receiver,
+            is_running: false,
+            pending: None,
+        }
+    }
+}
+
+impl BackgroundIgnoreScan {
+    pub fn request(&mut self, scanner: IgnoreScanner) {
+        if self.is_running {
+            self.pending = Some(scanner);
+        } else {
+            self.start(scanner);
+        }
+    }
+
+    /// Result of the last walk once it finishes. Results made stale by a later request are
+    /// dropped and the walk is started again.
+    pub fn take_finished(&mut self) -> Option<IgnoreScan> {
+        let scan = self.receiver.try_recv().ok()?;
+        self.is_running = false;
+
+        match self.pending.take() {
+            Some(scanner) => {
+                self.start(scanner);
+                None
+            }
+            None => Some(scan),
+        }
+    }
+
+    fn start(&mut self, scanner: IgnoreScanner) {
+        let sender = self.sender.clone();
+        self.is_running = true;
+
+        thread::spawn(move || {
+            let _ = sender.send(scanner.scan());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        wildmatch(pattern.as_bytes(), text.as_bytes())
+    }
+
+    fn temp_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("gitnuro-ignore-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn wildmatch_stars_stay_in_a_directory() {
+        assert!(matches("*.log", "debug.log"));
+        assert!(matches("*", ""));
+        assert!(!matches("*.log", "logs/debug.log"));
+        assert!(matches("build/*.o", "build/main.o"));
+        assert!(!matches("build/*.o", "build/obj/main.o"));
+        assert!(matches("?.txt", "a.txt"));
+        assert!(!matches("?", "/"));
+        assert!(!matches("a?b", "a/b"));
+    }
+
+    #[test]
+    fn wildmatch_double_stars_cross_directories() {
+        assert!(matches("**/target", "target"));
+        assert!(matches("**/target", "rs/target"));
+        assert!(matches("**/target", "a/b/target"));
+        assert!(matches("docs/**", "docs/a/b.md"));
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+        assert!(!matches("a/**/b", "a/x/yb"));
+    }
+
+    #[test]
+    fn wildmatch_escapes_and_classes() {
+        assert!(matches("\\*.txt", "*.txt"));
+        assert!(!matches("\\*.txt", "a.txt"));
+        assert!(matches("file[0-9].txt", "file7.txt"));
+        assert!(!matches("file[0-9].txt", "filex.txt"));
+        assert!(matches("file[!0-9].txt", "filex.txt"));
+        // Unterminated classes are literal
+        assert!(matches("file[0", "file[0"));
+    }
+
+    #[test]
+    fn matches_bracket_expressions() {
+        assert_eq!(match_class(b"[abc]", Some(b'b')), Some((true, 5)));
+        assert_eq!(match_class(b"[abc]", Some(b'd')), Some((false, 5)));
+        assert_eq!(match_class(b"[a-c]x", Some(b'c')), Some((true, 5)));
+        assert_eq!(match_class(b"[^a-c]", Some(b'c')), Some((false, 6)));
+        assert_eq!(match_class(b"[!a-c]", Some(b'z')), Some((true, 6)));
+
+        // A leading "]" is part of the class, "-" at the end is literal
+        assert_eq!(match_class(b"[]a]", Some(b']')), Some((true, 4)));
+        assert_eq!(match_class(b"[a-]", Some(b'-')), Some((true, 4)));
+        assert_eq!(match_class(b"[\\]]", Some(b']')), Some((true, 4)));
+
+        // Slashes never match, even negated
+        assert_eq!(match_class(b"[!a]", Some(b'/')), Some((false, 4)));
+        assert_eq!(match_class(b"[-0]", Some(b'/')), Some((false, 4)));
+
+        assert_eq!(match_class(b"[abc", Some(b'a')), None);
+    }
+
+    #[test]
+    fn parses_patterns() {
+        let pattern = parse_pattern("/build/").unwrap();
+        assert_eq!(pattern.glob, "build");
+        assert!(pattern.dir_only);
+        assert!(pattern.anchored);
+        assert!(!pattern.negated);
+
+        let pattern = parse_pattern("!*.keep").unwrap();
+        assert_eq!(pattern.glob, "*.keep");
+        assert!(pattern.negated);
+        assert!(!pattern.anchored);
+
+        let pattern = parse_pattern("\\!important").unwrap();
+        assert_eq!(pattern.glob, "!important");
+        assert!(!pattern.negated);
+
+        assert_eq!(parse_pattern("\\#notes").unwrap().glob, "#notes");
+        assert_eq!(parse_pattern("trailing   ").unwrap().glob, "trailing");
+        assert_eq!(parse_pattern("space\\ ").unwrap().glob, "space\\ ");
+        assert!(parse_pattern("docs/*.md").unwrap().anchored);
+
+        assert!(parse_pattern("").is_none());
+        assert!(parse_pattern("# comment").is_none());
+        assert!(parse_pattern("/").is_none());
+        assert!(parse_pattern("   ").is_none());
+    }
+
+    #[test]
+    fn last_matching_pattern_wins() {
+        let worktree = temp_directory("precedence");
+        fs::write(
+            worktree.join(GITIGNORE_FILE_NAME),
+            "*.log\n!keep.log\n/target/\n",
+        )
+        .unwrap();
+        fs::create_dir_all(worktree.join("target/debug")).unwrap();
+
+        let tracking = IgnoreTracking::new(worktree.clone(), worktree.join(".git"), None);
+
+        assert!(tracking.is_ignored(&worktree.join("debug.log"), false));
+        assert!(!tracking.is_ignored(&worktree.join("keep.log"), false));
+        assert!(tracking.is_ignored(&worktree.join("target/debug/app"), false));
+        assert!(!tracking.is_ignored(&worktree.join(GITIGNORE_FILE_NAME), false));
+        assert!(!tracking.directories.contains(&worktree.join("target")));
+        assert!(
+            tracking
+                .ignored_directories
+                .contains(&worktree.join("target"))
+        );
+        assert!(
+            !tracking
+                .ignored_directories
+                .contains(&worktree.join("target/debug"))
+        );
+
+        fs::remove_dir_all(worktree).unwrap();
+    }
+
+    #[test]
+    fn ignores_other_files_next_to_the_excludes_file() {
+        let worktree = temp_directory("worktree");
+        let config_directory = temp_directory("config");
+        let excludes_file = config_directory.join("ignore");
+        fs::write(&excludes_file, "*.swp\n").unwrap();
+
+        let tracking = IgnoreTracking::new(
+            worktree.clone(),
+            worktree.join(".git"),
+            Some(excludes_file.clone()),
+        );
+
+        assert!(tracking.rule_directories().contains(&config_directory));
+        assert!(!tracking.is_ignored(&excludes_file, false));
+        assert!(tracking.is_rules_file(&excludes_file));
+        assert!(tracking.is_ignored(&config_directory.join("config"), false));
+        assert!(tracking.is_ignored(&worktree.join("main.rs.swp"), false));
+        assert!(!tracking.is_ignored(&worktree.join("main.rs"), false));
+
+        fs::remove_dir_all(worktree).unwrap();
+        fs::remove_dir_all(config_directory).unwrap();
+    }
+}
@@ -0,0 +1,36 @@
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct InteractivePrompt {
+    pub prompt: String,
+    /// Whether the answer can be shown while typing. Passwords and codes have it disabled.
+    pub echo: bool,
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct InteractiveAuthRequest {
+    pub name: String,
+    pub instruction: String,
+    pub prompts: Vec<InteractivePrompt>,
+}
+
+#[uniffi::export(callback_interface)]
+pub trait InteractiveAuthPrompter: Send + Sync {
+    /// Returns an answer per prompt, in the same order, or [None] to cancel the authentication.
+    fn answer_prompts(&self, request: InteractiveAuthRequest) -> Option<Vec<String>>;
+}
+
+impl From<libssh_rs::InteractiveAuthInfo> for InteractiveAuthRequest {
+    fn from(info: libssh_rs::InteractiveAuthInfo) -> Self {
+        InteractiveAuthRequest {
+            name: info.name,
+            instruction: info.instruction,
+            prompts: info
+                .prompts
+                .into_iter()
+                .map(|prompt| InteractivePrompt {
+                    prompt: prompt.prompt,
+                    echo: prompt.echo,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::snapshot::{self, EntryStat};
+
+const JOURNAL_HEADER: &str = "gitnuro-journal 1";
+
+/// Amount of changed paths kept in the journal. Queries for clocks older than the oldest change
+/// kept are answered as a fresh instance.
+const MAX_JOURNAL_CHANGES: usize = 50_000;
+
+/// The journal is shared with the fsmonitor server, which answers queries from its own thread.
+pub type SharedJournal = Arc<RwLock<Option<ChangeJournal>>>;
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct JournalQueryResult {
+    /// Clock to use in the next query.
+    pub clock: String,
+    /// True when the journal can't tell what changed since the given clock (unknown clock or
+    /// history already discarded), so the caller has to assume everything changed.
+    pub is_fresh_instance: bool,
+    pub changed_paths: Vec<String>,
+}
+
+/// Watchman-like change journal persisted across app restarts. Every batch of changes advances
+/// the clock, so consumers can ask which paths changed since a clock they obtained earlier.
+///
+/// Along with the recent changes, a stat snapshot of the worktree is stored. When the journal is
+/// opened again, only the recorded paths are stat-ed (directories are only listed if their mtime
+/// changed), so if nothing changed while the app was closed, the tree doesn't have to be walked.
+pub struct ChangeJournal {
+    file_path: PathBuf,
+    worktree: PathBuf,
+    epoch: u64,
+    tick: u64,
+    /// Changes after this tick are complete.
+    oldest_tick: u64,
+    changes: VecDeque<(u64, String)>,
+    /// Tick of the last save, or [None] if the journal has never been saved.
+    saved_tick: Option<u64>,
+}
+
+impl ChangeJournal {
+    /// Opens the journal stored at `file_path`, or creates a new one if it doesn't exist or
+    /// belongs to a different worktree. Changes made to the worktree since it was saved are
+    /// recorded right away.
+    pub fn open(file_path: PathBuf, worktree: PathBuf) -> io::Result<ChangeJournal> {
+        let (mut journal, saved_entries) = match load(&file_path, &worktree) {
+            Ok(Some(loaded)) => loaded,
+            Ok(None) => (ChangeJournal::new(file_path, worktree), HashMap::new()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                (ChangeJournal::new(file_path, worktree), HashMap::new())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let changes = changes_since_snapshot(&saved_entries);
+
+        // Changes made while the app was closed
+        if !changes.is_empty() {
+            journal.record(changes.iter().map(|path| path.as_str()));
+        }
+
+        Ok(journal)
+    }
+
+    fn new(file_path: PathBuf, worktree: PathBuf) -> ChangeJournal {
+        let epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+
+        ChangeJournal {
+            file_path,
+            worktree,
+            epoch,
+            tick: 0,
+            oldest_tick: 0,
+            changes: VecDeque::new(),
+            saved_tick: None,
+        }
+    }
+
+    pub fn clock(&self) -> String {
+        format!("c:{}:{}", self.epoch, self.tick)
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn worktree(&self) -> &Path {
+        &self.worktree
+    }
+
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.saved_tick != Some(self.tick)
+    }
+
+    pub fn has_change_since(&self, tick: u64, path: &str) -> bool {
+        self.changes
+            .iter()
+            .rev()
+            .take_while(|(change_tick, _)| *change_tick > tick)
+            .any(|(_, change_path)| change_path == path)
+    }
+
+    /// Records a batch of changed paths, advancing the clock.
+    pub fn record<'a>(&mut self, paths: impl Iterator<Item = &'a str>) {
+        self.tick += 1;
+
+        for path in paths {
+            self.changes.push_back((self.tick, path.to_string()));
+        }
+
+        while self.changes.len() > MAX_JOURNAL_CHANGES {
+            if let Some((tick, _)) = self.changes.pop_front() {
+                // The batch of this tick is now incomplete
+                self.oldest_tick = tick;
+            }
+        }
+    }
+
+    pub fn changes_since(&self, clock: &str) -> JournalQueryResult {
+        let since_tick = parse_clock(clock)
+            .filter(|(epoch, tick)| {
+                *epoch == self.epoch && *tick >= self.oldest_tick && *tick <= self.tick
+            })
+            .map(|(_, tick)| tick);
+
+        let Some(since_tick) = since_tick else {
+            return JournalQueryResult {
+                clock: self.clock(),
+                is_fresh_instance: true,
+                changed_paths: Vec::new(),
+            };
+        };
+
+        let mut seen = HashSet::new();
+
+        let changed_paths = self
+            .changes
+            .iter()
+            .filter(|(tick, _)| *tick > since_tick)
+            .filter(|(_, path)| seen.insert(path.as_str()))
+            .map(|(_, path)| path.clone())
+            .collect();
+
+        JournalQueryResult {
+            clock: self.clock(),
+            is_fresh_instance: false,
+            changed_paths,
+        }
+    }
+
+    /// Writes the journal to disk along with the given stat snapshot of the worktree.
+    pub fn save(&mut self, entries: &HashMap<PathBuf, EntryStat>) -> io::Result<()> {
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first so a crash doesn't leave a truncated journal behind
+        let temp_path = self.file_path.with_extension("tmp");
+        let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+
+        writeln!(writer, "{JOURNAL_HEADER}")?;
+        writeln!(writer, "worktree {}", self.worktree.to_string_lossy())?;
+        writeln!(writer, "epoch {}", self.epoch)?;
+        writeln!(writer, "tick {}", self.tick)?;
+        writeln!(writer, "oldest {}", self.oldest_tick)?;
+
+        for (tick, path) in &self.changes {
+            if !path.contains('\n') {
+                writeln!(writer, "change {tick} {path}")?;
+            }
+        }
+
+        for (path, stat) in entries {
+            let path = path.to_string_lossy();
+
+            if path.contains('\n') {
+                continue;
+            }
+
+            let entry_type = if stat.is_dir { 'd' } else { 'f' };
+            let modified = stat
+                .modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| format!("{}.{}", time.as_secs(), time.subsec_nanos()))
+                .unwrap_or_else(|| "-".to_string());
+
+            writeln!(writer, "entry {entry_type} {modified} {} {path}", stat.len)?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(temp_path, &self.file_path)?;
+        self.saved_tick = Some(self.tick);
+
+        Ok(())
+    }
+}
+
+fn parse_clock(clock: &str) -> Option<(u64, u64)> {
+    let mut parts = clock.strip_prefix("c:")?.split(':');
+    let epoch = parts.next()?.parse().ok()?;
+    let tick = parts.next()?.parse().ok()?;
+
+    Some((epoch, tick))
+}
+
+type LoadedJournal = (ChangeJournal, HashMap<PathBuf, EntryStat>);
+
+/// Returns [None] if the file is not a journal of this worktree.
+fn load(file_path: &Path, worktree: &Path) -> io::Result<Option<LoadedJournal>> {
+    let reader = BufReader::new(fs::File::open(file_path)?);
+    let mut lines = reader.lines();
+
+    if lines.next().transpose()?.as_deref() != Some(JOURNAL_HEADER) {
+        return Ok(None);
+    }
+
+    let mut journal = ChangeJournal::new(file_path.to_path_buf(), worktree.to_path_buf());
+    let mut entries = HashMap::new();
+
+    for line in lines {
+        let line = line?;
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+
+        match key {
+            "worktree" if Path::new(value) != worktree => return Ok(None),
+            "epoch" => journal.epoch = value.parse().unwrap_or_default(),
+            "tick" => journal.tick = value.parse().unwrap_or_default(),
+            "oldest" => journal.oldest_tick = value.parse().unwrap_or_default(),
+            "change" => {
+                if let Some((tick, path)) = value.split_once(' ')
+                    && let Ok(tick) = tick.parse()
+                {
+                    journal.changes.push_back((tick, path.to_string()));
+                }
+            }
+            "entry" => {
+                if let Some((path, stat)) = parse_entry(value) {
+                    entries.insert(path, stat);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    journal.saved_tick = Some(journal.tick);
+
+    Ok(Some((journal, entries)))
+}
+
+fn parse_entry(value: &str) -> Option<(PathBuf, EntryStat)> {
+    let mut parts = value.splitn(4, ' ');
+    let is_dir = parts.next()? == "d";
+    let modified = match parts.next()? {
+        "-" => None,
+        modified => {
+            let (secs, nanos) = modified.split_once('.')?;
+            let duration = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+            Some(UNIX_EPOCH + duration)
+        }
+    };
+    let len = parts.next()?.parse().ok()?;
+    let path = PathBuf::from(parts.next()?);
+
+    Some((
+        path,
+        EntryStat {
+            modified,
+            len,
+            is_dir,
+        },
+    ))
+}
+
+/// Compares the saved snapshot against the disk. Directories are only listed when their mtime
+/// changed, as that's the only way entries could have been added to them. Directories created
+/// in the meantime are reported as a whole instead of walking them.
+fn changes_since_snapshot(saved_entries: &HashMap<PathBuf, EntryStat>) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    for (path, saved_stat) in saved_entries {
+        let Some(current_stat) = snapshot::stat(path) else {
+            changes.push(path.to_string_lossy().into_owned());
+            continue;
+        };
+
+        if current_stat == *saved_stat {
+            continue;
+        }
+
+        if current_stat.is_dir && saved_stat.is_dir {
+            let Ok(dir_entries) = fs::read_dir(path) else {
+                continue;
+            };
+
+            for entry in dir_entries.flatten() {
+                let entry_path = entry.path();
+
+                if !saved_entries.contains_key(&entry_path) {
+                    changes.push(entry_path.to_string_lossy().into_owned());
+                }
+            }
+        } else {
+            changes.push(path.to_string_lossy().into_owned());
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("gitnuro-journal-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn answers_changes_since_a_clock() {
+        let mut journal = ChangeJournal::new(PathBuf::from("journal"), PathBuf::from("worktree"));
+        journal.record(["a.rs", "b.rs"].into_iter());
+        let clock = journal.clock();
+        journal.record(["b.rs", "c.rs", "b.rs"].into_iter());
+
+        let result = journal.changes_since(&clock);
+        assert!(!result.is_fresh_instance);
+        assert_eq!(result.changed_paths, ["b.rs", "c.rs"]);
+        assert_eq!(result.clock, journal.clock());
+
+        assert!(journal.changes_since("c:1:0").is_fresh_instance);
+        assert!(journal.changes_since("invalid").is_fresh_instance);
+    }
+
+    #[test]
+    fn finds_changes_made_while_closed() {
+        let directory = temp_directory("reopen");
+        let worktree = directory.join("worktree");
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(worktree.join("kept.rs"), "").unwrap();
+        fs::write(worktree.join("modified.rs"), "").unwrap();
+
+        let journal_path = directory.join("journal");
+        let mut journal = ChangeJournal::open(journal_path.clone(), worktree.clone()).unwrap();
+        assert!(journal.has_unsaved_changes());
+
+        let entries = ["kept.rs", "modified.rs"]
+            .map(|name| worktree.join(name))
+            .into_iter()
+            .map(|path| (path.clone(), snapshot::stat(&path).unwrap()))
+            .collect();
+
+        journal.save(&entries).unwrap();
+        assert!(!journal.has_unsaved_changes());
+        let clock = journal.clock();
+
+        fs::write(worktree.join("modified.rs"), "fn main() {}").unwrap();
+
+        let journal = ChangeJournal::open(journal_path, worktree.clone()).unwrap();
+        let result = journal.changes_since(&clock);
+
+        assert!(!result.is_fresh_instance);
+        assert_eq!(
+            result.changed_paths,
+            [worktree.join("modified.rs").to_string_lossy()]
+        );
+        assert!(journal.has_unsaved_changes());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,654 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::{DEFAULT_SSH_PORT, host_key, ssh_config};
+
+const HASHED_HOST_PREFIX: &str = "|1|";
+const HASH_SALT_LENGTH: usize = 20;
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum KnownHostsError {
+    #[error("Accessing the known_hosts file failed: {reason}")]
+    Io { reason: String },
+    #[error("Invalid host key: {reason}")]
+    InvalidKey { reason: String },
+}
+
+impl From<std::io::Error> for KnownHostsError {
+    fn from(error: std::io::Error) -> Self {
+        KnownHostsError::Io {
+            reason: error.to_string(),
+        }
+    }
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KnownHostMarker {
+    None,
+    /// `@cert-authority`: the key is a CA trusted to sign the host certificates.
+    CertAuthority,
+    /// `@revoked`: the key must never be accepted.
+    Revoked,
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct KnownHostEntry {
+    /// Line of the entry in the file, starting at 1.
+    pub line: u32,
+    pub marker: KnownHostMarker,
+    /// Host patterns of the entry. Hashed hosts are kept as they are in the file
+    /// (`|1|salt|hash`), as the host name can't be recovered from them.
+    pub hosts: Vec<String>,
+    pub is_hashed: bool,
+    pub key_type: String,
+    /// Base64 encoded public key.
+    pub key: String,
+    /// SHA256 fingerprint of the key, in the format printed by OpenSSH.
+    pub fingerprint: String,
+    pub comment: Option<String>,
+}
+
+#[derive(uniffi::Record, Debug, Clone)]
+pub struct KnownHostConflict {
+    pub path: String,
+    pub entry: KnownHostEntry,
+}
+
+/// known_hosts file in the OpenSSH format, as read and written by libssh. The file is read again
+/// on every call, as it can be modified by other processes.
+#[derive(uniffi::Object)]
+pub struct KnownHosts {
+    path: PathBuf,
+}
+
+#[uniffi::export]
+impl KnownHosts {
+    /// Uses `~/.ssh/known_hosts` if no path is given.
+    #[uniffi::constructor]
+    pub fn new(path: Option<String>) -> KnownHosts {
+        let path = path
+            .map(PathBuf::from)
+            .unwrap_or_else(default_known_hosts_path);
+
+        KnownHosts { path }
+    }
+
+    pub fn path(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    pub fn entries(&self) -> Result<Vec<KnownHostEntry>, KnownHostsError> {
+        let lines = self.read_lines()?;
+
+        Ok(lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| parse_line(index, line))
+            .collect())
+    }
+
+    /// Entries that apply to `host` on `port`, including hashed and wildcard ones.
+    pub fn lookup(&self, host: String, port: u16) -> Result<Vec<KnownHostEntry>, KnownHostsError> {
+        let host_name = host_name(&host, port);
+
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|entry| matches_host(&entry.hosts, &host_name))
+            .collect())
+    }
+
+    /// First entry of `host` on `port` with a key that isn't `key`, which is the one libssh
+    /// reports as changed.
+    pub fn find_conflict(
+        &self,
+        host: String,
+        port: u16,
+        key_type: String,
+        key: String,
+    ) -> Result<Option<KnownHostConflict>, KnownHostsError> {
+        let entries = self.lookup(host, port)?;
+        let entry = entries.into_iter().find(|entry| {
+            entry.marker == KnownHostMarker::None && entry.key_type == key_type && entry.key != key
+        });
+
+        Ok(entry.map(|entry| KnownHostConflict {
+            path: self.path(),
+            entry,
+        }))
+    }
+
+    /// Appends a key for `host` on `port`. With `hashed`, the host name is stored as
+    /// HMAC-SHA1 with a random salt, as `HashKnownHosts yes` does.
+    pub fn add(
+        &self,
+        host: String,
+        port: u16,
+        key_type: String,
+        key: String,
+        hashed: bool,
+    ) -> Result<KnownHostEntry, KnownHostsError> {
+        if let Err(e) = STANDARD.decode(&key) {
+            return Err(KnownHostsError::InvalidKey {
+                reason: e.to_string(),
+            });
+        }
+
+        let host_name = host_name(&host, port);
+        let host_pattern = if hashed {
+            hash_host(&host_name)?
+        } else {
+            host_name
+        };
+
+        let mut lines = self.read_lines()?;
+        let index = lines.len();
+        let line = format!("{host_pattern} {key_type} {key}");
+
+        lines.push(line.clone());
+        self.write_lines(&lines)?;
+
+        parse_line(index, &line).ok_or_else(|| KnownHostsError::InvalidKey {
+            reason: format!("Unable to parse \"{line}\""),
+        })
+    }
+
+    /// Removes `host` on `port` from the file, so its key is asked again on the next connection.
+    /// Lines listing other hosts keep them. Returns the amount of entries changed.
+    ///
+    /// Only the exact host name (or its hash) is removed, wildcard patterns may cover other hosts.
+    /// `@revoked` and `@cert-authority` lines are never changed, as removing them would trust keys
+    /// again.
+    pub fn remove(&self, host: String, port: u16) -> Result<u32, KnownHostsError> {
+        let host_name = host_name(&host, port);
+        let mut lines = self.read_lines()?;
+        let mut changed_entries = 0;
+
+        lines.retain_mut(|line| {
+            let Some(entry) = parse_line(0, line) else {
+                return true;
+            };
+
+            if entry.marker != KnownHostMarker::None {
+                return true;
+            }
+
+            let remaining_hosts: Vec<&String> = entry
+                .hosts
+                .iter()
+                .filter(|pattern| !is_exact_host(pattern, &host_name))
+                .collect();
+
+            if remaining_hosts.len() == entry.hosts.len() {
+                return true;
+            }
+
+            changed_entries += 1;
+
+            if remaining_hosts.is_empty() {
+                return false;
+            }
+
+            let patterns = remaining_hosts
+                .iter()
+                .map(|pattern| pattern.as_str())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            *line = line.replacen(&entry.hosts.join(","), &patterns, 1);
+
+            true
+        });
+
+        if changed_entries > 0 {
+            self.write_lines(&lines)?;
+        }
+
+        Ok(changed_entries)
+    }
+
+    /// Removes the entry at `line`, such as a conflict reported by [KnownHosts::find_conflict].
+    pub fn remove_line(&self, line: u32) -> Result<bool, KnownHostsError> {
+        let mut lines = self.read_lines()?;
+        let index = line.saturating_sub(1) as usize;
+
+        if line == 0 || index >= lines.len() || parse_line(index, &lines[index]).is_none() {
+            return Ok(false);
+        }
+
+        lines.remove(index);
+        self.write_lines(&lines)?;
+
+        Ok(true)
+    }
+}
+
+impl KnownHosts {
+    fn read_lines(&self) -> Result<Vec<String>, KnownHostsError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content.lines().map(str::to_string).collect()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes to a temporary file first, so the file is never left half written.
+    fn write_lines(&self, lines: &[String]) -> Result<(), KnownHostsError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut content = lines.join("\n");
+        content.push('\n');
+
+        let temporary_path = self.path.with_extension("gitnuro-tmp");
+        fs::write(&temporary_path, content)?;
+        fs::rename(&temporary_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+fn default_known_hosts_path() -> PathBuf {
+    ssh_config::ssh_directory().join("known_hosts")
+}
+
+/// Hosts on a port other than the default one are stored as `[host]:port`.
+fn host_name(host: &str, port: u16) -> String {
+    let host = host.to_lowercase();
+
+    if port == DEFAULT_SSH_PORT {
+        host
+    } else {
+        format!("[{host}]:{port}")
+    }
+}
+
+/// Parses a `[marker] hosts key-type key [comment]` line. Returns [None] for comments, empty or
+/// malformed lines.
+fn parse_line(index: usize, line: &str) -> Option<KnownHostEntry> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace();
+    let mut hosts = fields.next()?;
+
+    let marker = match hosts {
+        "@cert-authority" => KnownHostMarker::CertAuthority,
+        "@revoked" => KnownHostMarker::Revoked,
+        _ => KnownHostMarker::None,
+    };
+
+    if marker != KnownHostMarker::None {
+        hosts = fields.next()?;
+    }
+
+    let key_type = fields.next()?;
+    let key = fields.next()?;
+    let key_blob = STANDARD.decode(key).ok()?;
+    let comment = fields.collect::<Vec<_>>().join(" ");
+
+    Some(KnownHostEntry {
+        line: index as u32 + 1,
+        marker,
+        hosts: hosts.split(',').map(str::to_string).collect(),
+        is_hashed: hosts.starts_with(HASHED_HOST_PREFIX),
+        key_type: key_type.to_string(),
+        key: key.to_string(),
+        fingerprint: host_key::fingerprint_of_blob(&key_blob),
+        comment: if comment.is_empty() {
+            None
+        } else {
+            Some(comment)
+        },
+    })
+}
+
+/// Whether any of the patterns matches `host_name`, unless a negated one (`!pattern`) does.
+pub fn matches_host(patterns: &[String], host_name: &str) -> bool {
+    let mut matches = false;
+
+    for pattern in patterns {
+        if let Some(negated_pattern) = pattern.strip_prefix('!') {
+            if matches_pattern(negated_pattern, host_name) {
+                return false;
+            }
+        } else if matches_pattern(pattern, host_name) {
+            matches = true;
+        }
+    }
+
+    matches
+}
+
+/// Whether `pattern` is `host_name` itself, or its hash, rather than a pattern matching it.
+fn is_exact_host(pattern: &str, host_name: &str) -> bool {
+    match pattern.strip_prefix(HASHED_HOST_PREFIX) {
+        Some(hashed_pattern) => matches_hashed(hashed_pattern, host_name),
+        None => pattern.to_lowercase() == host_name,
+    }
+}
+
+fn matches_pattern(pattern: &str, host_name: &str) -> bool {
+    match pattern.strip_prefix(HASHED_HOST_PREFIX) {
+        Some(hashed_pattern) => matches_hashed(hashed_pattern, host_name),
+        None => matches_wildcard(pattern.to_lowercase().as_bytes(), host_name.as_bytes()),
+    }
+}
+
+/// `salt|hash`, where hash is the HMAC-SHA1 of the host name keyed with the salt.
+fn matches_hashed(hashed_pattern: &str, host_name: &str) -> bool {
+    let Some((salt, hash)) = hashed_pattern.split_once('|') else {
+        return false;
+    };
+
+    let (Ok(salt), Ok(hash)) = (STANDARD.decode(salt), STANDARD.decode(hash)) else {
+        return false;
+    };
+
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(&salt) else {
+        return false;
+    };
+
+    mac.update(host_name.as_bytes());
+    mac.verify_slice(&hash).is_ok()
+}
+
+/// `*` matches any amount of characters and `?` a single one.
+fn matches_wildcard(pattern: &[u8], value: &[u8]) -> bool {
+    match (pattern.first(), value.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            matches_wildcard(&pattern[1..], value)
+                || (!value.is_empty() && matches_wildcard(pattern, &value[1..]))
+        }
+        (Some(b'?'), Some(_)) => matches_wildcard(&pattern[1..], &value[1..]),
+        (Some(p), Some(v)) if p == v => matches_wildcard(&pattern[1..], &value[1..]),
+        _ => false,
+    }
+}
+
+fn hash_host(host_name: &str) -> Result<String, KnownHostsError> {
+    let mut salt = [0u8; HASH_SALT_LENGTH];
+
+    getrandom::fill(&mut salt).map_err(|e| KnownHostsError::Io {
+        reason: format!("Unable to generate a salt: {e}"),
+    })?;
+
+    let mut mac = Hmac::<Sha1>::new_from_slice(&salt).expect("HMAC accepts any key length");
+    mac.update(host_name.as_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    Ok(format!(
+        "{HASHED_HOST_PREFIX}{}|{}",
+        STANDARD.encode(salt),
+        STANDARD.encode(hash)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+    const FINGERPRINT: &str = "SHA256:+DiY3wvvV6TuJJhbpZisF/zLDA0zPMSvHdkr4UvCOqU";
+
+    /// `[git.example.com]:2222` hashed with the bytes 0 to 19 as salt.
+    const HASHED_HOST: &str = "|1|AAECAwQFBgcICQoLDA0ODxAREhM=|iB8ji+G7JiWchxZqOjryGAnIiTo=";
+
+    fn known_hosts_with(name: &str, lines: &[String]) -> KnownHosts {
+        let path =
+            std::env::temp_dir().join(format!("gitnuro-known-hosts-{name}-{}", std::process::id()));
+
+        let known_hosts = KnownHosts { path };
+        known_hosts.write_lines(lines).unwrap();
+        known_hosts
+    }
+
+    fn hosts_of(known_hosts: &KnownHosts) -> Vec<Vec<String>> {
+        known_hosts
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.hosts)
+            .collect()
+    }
+
+    #[test]
+    fn parses_entries_with_markers_and_comments() {
+        let entry = parse_line(
+            4,
+            &format!("github.com,140.82.121.4 ssh-ed25519 {KEY} a comment"),
+        )
+        .unwrap();
+
+        assert_eq!(entry.line, 5);
+        assert_eq!(entry.marker, KnownHostMarker::None);
+        assert_eq!(entry.hosts, vec!["github.com", "140.82.121.4"]);
+        assert!(!entry.is_hashed);
+        assert_eq!(entry.key_type, "ssh-ed25519");
+        assert_eq!(entry.fingerprint, FINGERPRINT);
+        assert_eq!(entry.comment.as_deref(), Some("a comment"));
+
+        let revoked = parse_line(0, &format!("@revoked * ssh-ed25519 {KEY}")).unwrap();
+        assert_eq!(revoked.marker, KnownHostMarker::Revoked);
+        assert_eq!(revoked.hosts, vec!["*"]);
+        assert_eq!(revoked.comment, None);
+
+        let authority = parse_line(
+            0,
+            &format!("@cert-authority *.example.com ssh-ed25519 {KEY}"),
+        );
+        assert_eq!(authority.unwrap().marker, KnownHostMarker::CertAuthority);
+
+        let hashed = parse_line(0, &format!("{HASHED_HOST} ssh-ed25519 {KEY}")).unwrap();
+        assert!(hashed.is_hashed);
+    }
+
+    #[test]
+    fn skips_comments_and_malformed_lines() {
+        assert!(parse_line(0, "").is_none());
+        assert!(parse_line(0, "   # github.com ssh-ed25519 AAAA").is_none());
+        assert!(parse_line(0, "github.com ssh-ed25519").is_none());
+        assert!(parse_line(0, "github.com ssh-ed25519 not-base64!").is_none());
+    }
+
+    #[test]
+    fn stores_non_default_ports_between_brackets() {
+        assert_eq!(host_name("GitHub.com", DEFAULT_SSH_PORT), "github.com");
+        assert_eq!(host_name("git.example.com", 2222), "[git.example.com]:2222");
+    }
+
+    #[test]
+    fn matches_wildcards_negations_and_hashes() {
+        let patterns = |patterns: &[&str]| -> Vec<String> {
+            patterns.iter().map(|pattern| pattern.to_string()).collect()
+        };
+
+        assert!(matches_host(
+            &patterns(&["*.example.com"]),
+            "git.example.com"
+        ));
+        assert!(!matches_host(&patterns(&["*.example.com"]), "example.com"));
+        assert!(matches_host(
+            &patterns(&["git?.example.com"]),
+            "git1.example.com"
+        ));
+        assert!(!matches_host(
+            &patterns(&["*.example.com", "!git.example.com"]),
+            "git.example.com"
+        ));
+        assert!(matches_host(&patterns(&["GitHub.com"]), "github.com"));
+        assert!(matches_host(
+            &patterns(&[HASHED_HOST]),
+            "[git.example.com]:2222"
+        ));
+        assert!(!matches_host(&patterns(&[HASHED_HOST]), "git.example.com"));
+    }
+
+    #[test]
+    fn looks_up_hosts_on_their_port() {
+        let known_hosts = known_hosts_with(
+            "lookup",
+            &[
+                format!("github.com ssh-ed25519 {KEY}"),
+                format!("{HASHED_HOST} ssh-ed25519 {KEY}"),
+                format!("[*.example.com]:2222 ssh-rsa {KEY}"),
+            ],
+        );
+
+        assert_eq!(
+            known_hosts.lookup("github.com".into(), 22).unwrap().len(),
+            1
+        );
+        assert!(
+            known_hosts
+                .lookup("github.com".into(), 2222)
+                .unwrap()
+                .is_empty()
+        );
+
+        let entries = known_hosts.lookup("git.example.com".into(), 2222).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].line, 2);
+        assert_eq!(entries[1].key_type, "ssh-rsa");
+
+        let conflict = known_hosts
+            .find_conflict("github.com".into(), 22, "ssh-ed25519".into(), "AAAA".into())
+            .unwrap()
+            .unwrap();
+        assert_eq!(conflict.entry.line, 1);
+
+        fs::remove_file(&known_hosts.path).unwrap();
+    }
+
+    #[test]
+    fn adds_hashed_hosts() {
+        let known_hosts = known_hosts_with("add", &[]);
+
+        let entry = known_hosts
+            .add(
+                "git.example.com".into(),
+                2222,
+                "ssh-ed25519".into(),
+                KEY.into(),
+                true,
+            )
+            .unwrap();
+
+        assert!(entry.is_hashed);
+        assert_eq!(entry.fingerprint, FINGERPRINT);
+        assert_eq!(
+            known_hosts
+                .lookup("git.example.com".into(), 2222)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(
+            known_hosts
+                .lookup("git.example.com".into(), 22)
+                .unwrap()
+                .is_empty()
+        );
+
+        let invalid_key = known_hosts.add(
+            "github.com".into(),
+            22,
+            "ssh-ed25519".into(),
+            "!".into(),
+            false,
+        );
+        assert!(matches!(
+            invalid_key,
+            Err(KnownHostsError::InvalidKey { .. })
+        ));
+
+        fs::remove_file(&known_hosts.path).unwrap();
+    }
+
+    #[test]
+    fn removes_only_exact_hosts_and_keeps_markers() {
+        let known_hosts = known_hosts_with(
+            "remove",
+            &[
+                format!("git.example.com,other.example.com ssh-ed25519 {KEY}"),
+                format!("*.example.com ssh-ed25519 {KEY}"),
+                format!("@revoked git.example.com ssh-ed25519 {KEY}"),
+                format!("@cert-authority git.example.com ssh-ed25519 {KEY}"),
+                format!("GIT.example.com ssh-rsa {KEY}"),
+                "# git.example.com".to_string(),
+            ],
+        );
+
+        assert_eq!(known_hosts.remove("git.example.com".into(), 22).unwrap(), 2);
+        assert_eq!(
+            hosts_of(&known_hosts),
+            vec![
+                vec!["other.example.com"],
+                vec!["*.example.com"],
+                vec!["git.example.com"],
+                vec!["git.example.com"],
+            ]
+        );
+
+        let entries = known_hosts.entries().unwrap();
+        assert_eq!(entries[2].marker, KnownHostMarker::Revoked);
+        assert_eq!(entries[3].marker, KnownHostMarker::CertAuthority);
+        assert_eq!(known_hosts.read_lines().unwrap()[4], "# git.example.com");
+
+        fs::remove_file(&known_hosts.path).unwrap();
+    }
+
+    #[test]
+    fn removes_hashed_hosts_and_ports() {
+        let known_hosts = known_hosts_with(
+            "remove-hashed",
+            &[
+                format!("{HASHED_HOST} ssh-ed25519 {KEY}"),
+                format!("[git.example.com]:2222 ssh-rsa {KEY}"),
+                format!("git.example.com ssh-rsa {KEY}"),
+            ],
+        );
+
+        assert_eq!(
+            known_hosts.remove("git.example.com".into(), 2222).unwrap(),
+            2
+        );
+        assert_eq!(hosts_of(&known_hosts), vec![vec!["git.example.com"]]);
+        assert_eq!(
+            known_hosts.remove("git.example.com".into(), 2222).unwrap(),
+            0
+        );
+
+        fs::remove_file(&known_hosts.path).unwrap();
+    }
+
+    #[test]
+    fn removes_single_lines() {
+        let known_hosts = known_hosts_with(
+            "remove-line",
+            &[
+                "# comment".to_string(),
+                format!("github.com ssh-ed25519 {KEY}"),
+            ],
+        );
+
+        assert!(!known_hosts.remove_line(0).unwrap());
+        assert!(!known_hosts.remove_line(1).unwrap());
+        assert!(!known_hosts.remove_line(3).unwrap());
+        assert!(known_hosts.remove_line(2).unwrap());
+        assert_eq!(known_hosts.read_lines().unwrap(), vec!["# comment"]);
+
+        fs::remove_file(&known_hosts.path).unwrap();
+    }
+}
@@ -1,21 +1,72 @@
 extern crate notify;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
+use std::fs;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
 use std::sync::{Arc, LockResult, RwLock, RwLockWriteGuard};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use libssh_rs::{PollStatus, SignAlgorithm, SshKey, SshOption, ssh_sign};
-
-#[allow(unused_imports)]
-use libssh_rs::AuthStatus;
+use libssh_rs::{PollStatus, RawSocket, SignAlgorithm, SshKey, SshOption, ssh_sign};
 use notify::event::{CreateKind, RemoveKind};
-use notify::{
-    Config, Error, ErrorKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+use notify::{Error, ErrorKind, Event, EventKind, RecursiveMode};
+
+use crate::auth_methods::{ServerAuthInfo, to_auth_methods};
+use crate::batch::{BatchInfo, PendingBatch};
+use crate::bulk_change::{BulkChange, BulkChangeMode, BulkChangeSettings};
+use crate::delivery::{Batch, DeliveryQueue, DeliverySettings, Notification};
+use crate::filesystem::{DEFAULT_POLL_INTERVAL, WatchBackend, backend_for};
+#[cfg(unix)]
+use crate::fsmonitor::FsMonitorServer;
+use crate::host_key::{HostKeyDecision, HostKeyInfo, HostKeyStatus, HostKeyVerifier};
+use crate::ignore_rules::{BackgroundIgnoreScan, IgnoreScan, IgnoreTracking};
+use crate::interactive_auth::InteractiveAuthPrompter;
+use crate::journal::{ChangeJournal, JournalQueryResult, SharedJournal};
+use crate::known_hosts::KnownHosts;
+use crate::metrics::{MetricsCounters, WatcherMetrics};
+use crate::registry::WatchSubscriber;
+use crate::session_options::SessionOptions;
+use crate::snapshot::{SUSPEND_DETECTION_GAP_IN_MS, WatchSnapshot};
+use crate::ssh_error::{SshError, TimeoutKind, libssh_error_to_message};
+use crate::subscription::{
+    PathChangeNotifier, PathSubscription, SubscriptionHandle, Subscriptions,
+    dispatch_to_subscriptions, flush_subscriptions, next_subscription_deadline,
 };
+use crate::symlinks::{SymlinkPolicy, SymlinkTracking};
+use crate::watched_roots::{WatchPriority, WatchedRoot, WatchedRoots};
+
+mod agent;
+mod auth_methods;
+mod batch;
+mod bulk_change;
+mod delivery;
+mod filesystem;
+#[cfg(unix)]
+mod fsmonitor;
+#[cfg(unix)]
+mod fsmonitor_protocol;
+mod host_key;
+mod ignore_rules;
+mod interactive_auth;
+mod journal;
+mod known_hosts;
+mod logger;
+mod metrics;
+mod proxy_command;
+mod proxy_jump;
+mod registry;
+mod session_options;
+mod snapshot;
+mod ssh_config;
+mod ssh_error;
+mod subscription;
+mod symlinks;
+mod tunnel;
+mod watched_roots;
 
 uniffi::setup_scaffolding!();
 
@@ -24,10 +75,42 @@ struct FileWatcher {
     keep_watching: RwLock<bool>,
     watcher: RwLock<Option<WatcherHolder>>,
     receiver: RwLock<Option<ReceiverHolder>>,
+    poll_interval: RwLock<Duration>,
+    subscriptions: Subscriptions,
+    next_subscription_id: AtomicU64,
+    next_batch_sequence: AtomicU64,
+    bulk_change_settings: RwLock<BulkChangeSettings>,
+    delivery_settings: RwLock<DeliverySettings>,
+    metrics: Arc<MetricsCounters>,
+    /// Shared with the fsmonitor server, which only waits for its cookies in watched directories.
+    roots: Arc<RwLock<WatchedRoots>>,
+    ignore_tracking: Arc<RwLock<Option<IgnoreTracking>>>,
+    snapshot: RwLock<WatchSnapshot>,
+    symlinks: RwLock<SymlinkTracking>,
+    journal: SharedJournal,
+    #[cfg(unix)]
+    fsmonitor: RwLock<Option<FsMonitorServer>>,
 }
 
 struct WatcherHolder {
-    watcher: Box<dyn Watcher>,
+    subscriber: WatchSubscriber,
+    poll_interval: Duration,
+}
+
+impl WatcherHolder {
+    fn watch(
+        &mut self,
+        path: &Path,
+        recursive_mode: RecursiveMode,
+        backend: WatchBackend,
+    ) -> notify::Result<()> {
+        self.subscriber
+            .watch(path, recursive_mode, backend, self.poll_interval)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        self.subscriber.unwatch(path)
+    }
 }
 
 struct ReceiverHolder {
@@ -65,30 +148,55 @@ impl FileWatcher {
         // Create a channel to receive the events.
         let (sender, receiver) = channel();
 
-        // Create a watcher object, delivering debounced events.
-        // The notification back-end is selected based on the platform.
-        let config = Config::default();
-        config.with_poll_interval(Duration::from_secs(3600));
+        // Native watches are shared with the other file watchers of the process, which only
+        // receive the events of the paths they watch.
+        let subscriber = match WatchSubscriber::new(sender) {
+            Ok(subscriber) => subscriber,
+            // TODO Hardcoded nums should be changed to an enum or sth similar once Kotars supports them
+            Err(e) => return error_to_code(e.kind),
+        };
+
+        let mut watcher_holder = self.watcher.write().unwrap();
+        let mut receiver_holder = self.receiver.write().unwrap();
 
-        let watcher = RecommendedWatcher::new(sender, config);
+        let mut new_watcher_holder = WatcherHolder {
+            subscriber,
+            poll_interval: *self.poll_interval.read().unwrap(),
+        };
 
-        match watcher {
-            Ok(watcher) => {
-                let mut watcher_holder = self.watcher.write().unwrap();
-                let mut receiver_holder = self.receiver.write().unwrap();
+        // When the watcher is restarted, keep watching the same roots and look for
+        // changes that happened while no events could be received.
+        let roots = self.roots.read().unwrap();
 
-                *watcher_holder = Some(WatcherHolder {
-                    watcher: Box::new(watcher),
-                });
-                *receiver_holder = Some(ReceiverHolder { receiver });
-                0
+        if !roots.is_empty() {
+            for (path, root) in roots.iter() {
+                let recursive_mode = to_recursive_mode(root.is_recursive);
+
+                if let Err(e) = new_watcher_holder.watch(path, recursive_mode, root.backend) {
+                    logger::error(
+                        WATCHER_TAG,
+                        format!("Failed to watch {path:?} again after restart: {e:?}"),
+                    );
+                }
             }
-            Err(e) => {
-                // TODO Hardcoded nums should be changed to an enum or sth similar once Kotars supports them
-                let code = error_to_code(e.kind);
-                code
+
+            self.snapshot.write().unwrap().request_full_rescan();
+        }
+
+        for (target, is_recursive) in self.symlinks.read().unwrap().watched_targets() {
+            let recursive_mode = to_recursive_mode(is_recursive);
+
+            if let Err(e) = new_watcher_holder.watch(target, recursive_mode, backend_for(target)) {
+                logger::error(
+                    WATCHER_TAG,
+                    format!("Failed to watch symlink target {target:?} after restart: {e:?}"),
+                );
             }
         }
+
+        *watcher_holder = Some(new_watcher_holder);
+        *receiver_holder = Some(ReceiverHolder { receiver });
+        0
     }
 
     fn watch(&self, notifier: Box<dyn WatchDirectoryNotifier>) {
@@ -102,35 +210,77 @@ impl FileWatcher {
             Some(receiver) => &receiver.receiver,
         };
 
-        let mut paths_cached = HashMap::<FileChanged, Vec<EventKind>>::new();
+        // Batches are delivered from their own thread, so a slow consumer doesn't block the loop
+        let notifier: Arc<dyn WatchDirectoryNotifier> = Arc::from(notifier);
+        let queue = Arc::new(DeliveryQueue::new(
+            *self.delivery_settings.read().unwrap(),
+            self.metrics.clone(),
+        ));
+
+        let delivery_thread = {
+            let queue = queue.clone();
+            let notifier = notifier.clone();
+
+            thread::spawn(move || queue.deliver_all(notifier.as_ref()))
+        };
+
+        let mut paths_cached = PendingBatch::default();
+        let mut priority_paths_cached = PendingBatch::default();
+        let mut ignore_scan = BackgroundIgnoreScan::default();
 
         let mut last_update: u128 = 0;
+        let mut last_event_received: u128 = 0;
+        let mut last_journal_save = current_time_as_millis();
 
         while notifier.should_keep_looping() {
-            match receiver.recv_timeout(Duration::from_millis(WATCH_TIMEOUT)) {
+            // Path subscriptions have a shorter debounce than the repository-wide batch, so wake
+            // up earlier if any of them has pending changes.
+            let loop_time = current_time_as_millis();
+            let timeout = next_subscription_deadline(&self.subscriptions, loop_time)
+                .into_iter()
+                .chain(priority_paths_cached.remaining_until_due(
+                    loop_time,
+                    PRIORITY_DEBOUNCE_IN_MS,
+                    PRIORITY_MAX_DELAY_IN_MS,
+                ))
+                .min()
+                .map(|remaining| remaining.min(WATCH_TIMEOUT as u128) as u64)
+                .unwrap_or(WATCH_TIMEOUT);
+
+            let wait_start = current_time_as_millis();
+            let received = receiver.recv_timeout(Duration::from_millis(timeout));
+
+            if current_time_as_millis().saturating_sub(wait_start)
+                > timeout as u128 + SUSPEND_DETECTION_GAP_IN_MS
+            {
+                // The system was probably suspended
+                self.snapshot.write().unwrap().request_full_rescan();
+            }
+
+            match received {
                 Ok(e) => {
+                    if let Ok(event) = &e {
+                        self.metrics.record_event(&event.kind);
+                    }
+
                     if let Some(paths) = get_paths_from_event_result(&e) {
-                        let paths_without_dirs: Vec<FileChangeEvent> = paths.into_iter().collect();
-
-                        for path in paths_without_dirs.into_iter() {
-                            let is_dir = is_directory_event(&path.event_kind);
-                            let file_type = if is_dir {
-                                FileType::Directory
-                            } else {
-                                FileType::File
-                            };
-
-                            let file_changed = FileChanged {
-                                path: path.path,
-                                file_type,
-                            };
-
-                            match paths_cached.get_mut(&file_changed) {
-                                Some(v) => v.push(path.event_kind),
-                                None => {
-                                    paths_cached.insert(file_changed, vec![path.event_kind]);
-                                }
-                            }
+                        last_event_received = current_time_as_millis();
+
+                        let paths = self.follow_symlink_changes(paths);
+
+                        let directories = self.snapshot.write().unwrap().update(&paths);
+                        self.record_in_journal(&paths);
+
+                        let ignore_rules_changed = self.queue_changes(
+                            paths,
+                            &directories,
+                            &mut paths_cached,
+                            &mut priority_paths_cached,
+                            last_event_received,
+                        );
+
+                        if ignore_rules_changed {
+                            self.request_ignore_scan(&mut ignore_scan);
                         }
 
                         let current_time = current_time_as_millis();
@@ -138,23 +288,93 @@ impl FileWatcher {
                         if last_update != 0
                             && current_time - last_update > MIN_TIME_IN_MS_BETWEEN_REFRESHES
                         {
-                            process_paths_cached(&mut paths_cached, &notifier);
+                            self.flush_batch(&mut paths_cached, &queue);
                             last_update = current_time_as_millis();
                         }
                     }
                 }
                 Err(e) => match e {
                     RecvTimeoutError::Timeout => {
-                        process_paths_cached(&mut paths_cached, &notifier);
-                        last_update = current_time_as_millis();
+                        let current_time = current_time_as_millis();
+
+                        if current_time.saturating_sub(last_event_received) >= WATCH_TIMEOUT as u128
+                        {
+                            self.flush_batch(&mut paths_cached, &queue);
+                            last_update = current_time;
+                        }
+
+                        self.snapshot.write().unwrap().capture_next();
+
+                        if current_time.saturating_sub(last_journal_save)
+                            >= JOURNAL_SAVE_INTERVAL_IN_MS
+                        {
+                            self.save_journal_if_changed();
+                            last_journal_save = current_time;
+                        }
                     }
                     RecvTimeoutError::Disconnected => {
                         println!("Watch error: {:?}", e);
                     }
                 },
             };
+
+            // High priority changes skip the batching window, but are still debounced a bit so
+            // files such as `.git/index.lock`, created and removed right away, are filtered out
+            let priority_remaining = priority_paths_cached.remaining_until_due(
+                current_time_as_millis(),
+                PRIORITY_DEBOUNCE_IN_MS,
+                PRIORITY_MAX_DELAY_IN_MS,
+            );
+
+            if priority_remaining == Some(0) {
+                self.flush_batch(&mut priority_paths_cached, &queue);
+            }
+
+            // Changes found by comparing the snapshot against the disk are sent as a regular batch
+            let catch_up_changes = self.snapshot.write().unwrap().take_pending();
+
+            if !catch_up_changes.is_empty() {
+                let catch_up_changes = self.follow_symlink_changes(catch_up_changes);
+
+                self.record_in_journal(&catch_up_changes);
+
+                let current_time = current_time_as_millis();
+
+                last_event_received = current_time;
+
+                // Snapshot changes already tell directories apart through their event kind
+                let ignore_rules_changed = self.queue_changes(
+                    catch_up_changes,
+                    &HashSet::new(),
+                    &mut paths_cached,
+                    &mut priority_paths_cached,
+                    current_time,
+                );
+
+                if ignore_rules_changed {
+                    self.request_ignore_scan(&mut ignore_scan);
+                }
+
+                paths_cached.merge(&mut priority_paths_cached);
+            }
+
+            if let Some(scan) = ignore_scan.take_finished() {
+                self.apply_ignore_scan(scan);
+                queue.push(Notification::IgnoreRulesChanged);
+            }
+
+            flush_subscriptions(&self.subscriptions, current_time_as_millis());
+        }
+
+        queue.close();
+
+        if delivery_thread.join().is_err() {
+            logger::error(WATCHER_TAG, "Delivery thread panicked".to_string());
         }
 
+        // Failures are already reported by save_journal
+        self.save_journal();
+
         // // TODO If unwatch fails it's probably because we no longer have access to it. We probably don't care about it but double check in the future
         // let _ = watcher.unwatch(Path::new(path.as_str()));
 
@@ -162,30 +382,60 @@ impl FileWatcher {
     }
 
     fn add_watch(&self, path: String, is_recursive: bool) -> i32 {
+        self.add_watch_with_priority(path, is_recursive, WatchPriority::Normal)
+    }
+
+    /// Same as [FileWatcher::add_watch], but events under a [WatchPriority::High] path are sent
+    /// after a short debounce instead of waiting for the shared batching window.
+    fn add_watch_with_priority(
+        &self,
+        path: String,
+        is_recursive: bool,
+        priority: WatchPriority,
+    ) -> i32 {
         let mut watcher_holder = self.watcher.write().unwrap();
         let watcher = match watcher_holder.as_mut() {
             None => {
                 println!("Watcher not initialized");
                 return 1; // TODO Provide better error
             }
-            Some(watcher) => &mut watcher.watcher,
+            Some(watcher) => watcher,
         };
 
+        // Paths on network or FUSE filesystems are polled, as changes made by other hosts or by
+        // the filesystem process itself are not reported by the OS.
+        let backend = backend_for(Path::new(path.as_str()));
+
         // Add a path to be watched. All files and directories at that path and
         // below will be monitored for changes.
-
-        let recursive_mode = if is_recursive {
-            RecursiveMode::Recursive
-        } else {
-            RecursiveMode::NonRecursive
-        };
-
-        let res = watcher.watch(Path::new(path.as_str()), recursive_mode);
+        let res = watcher.watch(
+            Path::new(path.as_str()),
+            to_recursive_mode(is_recursive),
+            backend,
+        );
 
         if let Err(e) = res {
             // TODO Hardcoded nums should be changed to an enum or sth similar once Kotars supports them
             error_to_code(e.kind)
         } else {
+            let path = PathBuf::from(path);
+
+            self.snapshot
+                .write()
+                .unwrap()
+                .add_root(path.clone(), is_recursive);
+
+            self.roots.write().unwrap().insert(
+                path.clone(),
+                WatchedRoot {
+                    is_recursive,
+                    priority,
+                    backend,
+                },
+            );
+
+            drop(watcher_holder);
+            self.watch_symlink_targets(&path, is_recursive);
             0
         }
     }
@@ -198,12 +448,27 @@ impl FileWatcher {
                 println!("Watcher not initialized");
                 return 1; // TODO Provide better error
             }
-            Some(watcher) => &mut watcher.watcher,
+            Some(watcher) => watcher,
         };
 
         // Add a path to be watched. All files and directories at that path and
         // below will be monitored for changes.
         let res = watcher.unwatch(Path::new(path.as_str()));
+        self.roots.write().unwrap().remove(Path::new(path.as_str()));
+
+        for target in self
+            .symlinks
+            .write()
+            .unwrap()
+            .forget(Path::new(path.as_str()))
+        {
+            let _ = watcher.unwatch(&target);
+        }
+
+        self.snapshot
+            .write()
+            .unwrap()
+            .remove_root(Path::new(path.as_str()));
 
         if let Err(e) = res {
             // TODO Hardcoded nums should be changed to an enum or sth similar once Kotars supports them
@@ -218,15 +483,502 @@ impl FileWatcher {
             keep_watching: RwLock::from(true),
             watcher: RwLock::from(None),
             receiver: RwLock::from(None),
+            poll_interval: RwLock::from(DEFAULT_POLL_INTERVAL),
+            subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            next_subscription_id: AtomicU64::new(0),
+            next_batch_sequence: AtomicU64::new(1),
+            bulk_change_settings: RwLock::from(BulkChangeSettings::default()),
+            delivery_settings: RwLock::from(DeliverySettings::default()),
+            metrics: Arc::new(MetricsCounters::default()),
+            roots: Arc::new(RwLock::new(WatchedRoots::default())),
+            ignore_tracking: Arc::new(RwLock::new(None)),
+            snapshot: RwLock::from(WatchSnapshot::default()),
+            symlinks: RwLock::from(SymlinkTracking::default()),
+            journal: Arc::new(RwLock::new(None)),
+            #[cfg(unix)]
+            fsmonitor: RwLock::from(None),
+        }
+    }
+
+    /// Enables the on-disk change journal of the worktree, stored at `journal_path`. Changes made
+    /// to the worktree since the journal was last saved are recorded when it's opened. The journal
+    /// is saved while the watch loop is idle (at most every [JOURNAL_SAVE_INTERVAL_IN_MS]), when
+    /// it finishes or when [FileWatcher::save_journal] is called.
+    fn enable_journal(&self, journal_path: String, worktree_path: String) -> i32 {
+        match ChangeJournal::open(PathBuf::from(journal_path), PathBuf::from(worktree_path)) {
+            Ok(journal) => {
+                *self.journal.write().unwrap() = Some(journal);
+                0
+            }
+            Err(e) => {
+                logger::error(
+                    WATCHER_TAG,
+                    format!("Failed to open the change journal: {e:?}"),
+                );
+                2
+            }
+        }
+    }
+
+    fn save_journal(&self) -> i32 {
+        let mut journal = self.journal.write().unwrap();
+
+        let Some(journal) = journal.as_mut() else {
+            return 0;
+        };
+
+        let entries = self
+            .snapshot
+            .write()
+            .unwrap()
+            .entries_under(journal.worktree());
+
+        match journal.save(&entries) {
+            Ok(_) => 0,
+            Err(e) => {
+                logger::error(
+                    WATCHER_TAG,
+                    format!("Failed to save the change journal: {e:?}"),
+                );
+                2
+            }
         }
     }
 
+    /// Current clock of the journal, or [None] if the journal is not enabled.
+    fn journal_clock(&self) -> Option<String> {
+        self.journal
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|journal| journal.clock())
+    }
+
+    /// Paths changed since the given clock was obtained, or [None] if the journal is not enabled.
+    fn journal_changes_since(&self, clock: String) -> Option<JournalQueryResult> {
+        self.journal
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|journal| journal.changes_since(&clock))
+    }
+
+    /// Starts answering git's fsmonitor queries for the repository from the change journal,
+    /// which has to be enabled first. The endpoint is a unix socket in the git directory that
+    /// speaks the `git fsmonitor--daemon` protocol, used by `core.fsmonitor=true`, or by the
+    /// `gitnuro-fsmonitor-hook` binary set as `core.fsmonitor` hook (protocol version 2) on
+    /// platforms where git has no builtin daemon support. Worktree directories that are not
+    /// watched, such as ignored ones, are reported as changed in every answer, and so is the
+    /// whole worktree while ignore rules are not tracked.
+    ///
+    /// Returns 1 if the journal is not enabled, 2 if the socket can't be created and 3 on
+    /// platforms without unix sockets.
+    fn start_fsmonitor(&self, git_dir_path: String) -> i32 {
+        #[cfg(unix)]
+        {
+            if self.journal.read().unwrap().is_none() {
+                return 1;
+            }
+
+            match FsMonitorServer::start(
+                PathBuf::from(git_dir_path),
+                self.journal.clone(),
+                self.roots.clone(),
+                self.ignore_tracking.clone(),
+            ) {
+                Ok(server) => {
+                    *self.fsmonitor.write().unwrap() = Some(server);
+                    0
+                }
+                Err(_) => 2,
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = git_dir_path;
+            3
+        }
+    }
+
+    fn stop_fsmonitor(&self) {
+        #[cfg(unix)]
+        {
+            *self.fsmonitor.write().unwrap() = None;
+        }
+    }
+
+    /// Compares the watched roots against the snapshot taken from previous events and reports
+    /// any difference as a regular batch. Meant to be called when the system resumes from sleep,
+    /// although long pauses of the watch loop are also detected automatically.
+    fn rescan(&self) {
+        self.snapshot.write().unwrap().request_full_rescan();
+    }
+
+    /// Watches every non-ignored directory of the worktree and keeps the set up to date when
+    /// any `.gitignore`, `info/exclude` or the global excludes file changes. Changes to ignored
+    /// paths are no longer reported. If `excludes_file` is [None], git's default location is
+    /// used.
+    fn track_ignore_rules(
+        &self,
+        worktree_path: String,
+        git_dir_path: String,
+        excludes_file: Option<String>,
+    ) -> i32 {
+        let ignore_tracking = IgnoreTracking::new(
+            PathBuf::from(worktree_path),
+            PathBuf::from(git_dir_path),
+            excludes_file.map(PathBuf::from),
+        );
+
+        let directories_to_watch: Vec<PathBuf> = ignore_tracking
+            .rule_directories()
+            .into_iter()
+            .chain(ignore_tracking.directories.iter().cloned())
+            .collect();
+
+        *self.ignore_tracking.write().unwrap() = Some(ignore_tracking);
+
+        let mut result = 0;
+
+        for directory in directories_to_watch {
+            let code = self.add_watch(directory.to_string_lossy().into_owned(), false);
+
+            if result == 0 {
+                result = code;
+            }
+        }
+
+        result
+    }
+
+    /// Watches the worktree directories created since the last scan and stops watching the
+    /// removed ones, as the paths of a bulk change are not reported one by one. Requires
+    /// [FileWatcher::track_ignore_rules] to have been called, otherwise nothing is done.
+    fn refresh_watched_directories(&self) {
+        self.update_tracked_directories();
+    }
+
+    /// Sets the amount of paths in a single batch above which
+    /// [WatchDirectoryNotifier::detected_bulk_change] is used instead of sending every path.
+    /// A threshold of [None] disables bulk changes. Until this is called, batches of more than
+    /// [bulk_change::DEFAULT_BULK_CHANGE_THRESHOLD] paths are summarized by directory.
+    fn set_bulk_change_threshold(&self, threshold: Option<u32>, mode: BulkChangeMode) {
+        *self.bulk_change_settings.write().unwrap() = BulkChangeSettings { threshold, mode };
+    }
+
+    /// Interval at which paths on network or FUSE filesystems are scanned for changes. The poll
+    /// watcher is shared by every file watcher of the process, so this only takes effect if no
+    /// path has been polled yet.
+    fn set_poll_interval(&self, interval_ms: u64) {
+        *self.poll_interval.write().unwrap() = Duration::from_millis(interval_ms);
+    }
+
+    /// Backend used to receive the changes of a watched path, or [None] if the path is not
+    /// watched.
+    fn watch_backend(&self, path: String) -> Option<WatchBackend> {
+        self.roots
+            .read()
+            .unwrap()
+            .get(Path::new(path.as_str()))
+            .map(|root| root.backend)
+    }
+
+    /// Sets how symlinked directories found under the watched paths are treated, and applies it
+    /// to the paths already watched. Loops are detected and never followed.
+    fn set_symlink_policy(&self, policy: SymlinkPolicy) {
+        let previous_tracking = std::mem::replace(
+            &mut *self.symlinks.write().unwrap(),
+            SymlinkTracking::new(policy),
+        );
+
+        if let Some(watcher) = self.watcher.write().unwrap().as_mut() {
+            for (target, _) in previous_tracking.watched_targets() {
+                let _ = watcher.unwatch(target);
+            }
+        }
+
+        let roots: Vec<(PathBuf, bool)> = self
+            .roots
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(path, root)| (path.clone(), root.is_recursive))
+            .collect();
+
+        for (path, is_recursive) in roots {
+            self.watch_symlink_targets(&path, is_recursive);
+        }
+    }
+
+    /// Limits how many batches can wait for [WatchDirectoryNotifier] and how many paths a batch
+    /// can contain. When the consumer falls behind, new batches are merged into the newest
+    /// pending one, and batches above `max_batch_size` paths are reported as a bulk change.
+    /// Takes effect the next time [FileWatcher::watch] is called.
+    fn set_delivery_limits(&self, queue_capacity: u32, max_batch_size: u32) {
+        *self.delivery_settings.write().unwrap() = DeliverySettings {
+            queue_capacity,
+            max_batch_size,
+        };
+    }
+
+    /// Counters of the events received, filtered and delivered since the watcher was created.
+    fn metrics(&self) -> WatcherMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Sets every counter back to zero, so a measurement only covers the events after it.
+    fn reset_metrics(&self) {
+        self.metrics.reset();
+    }
+
+    /// Subscribes to the changes of a single file or directory (including its children). Changes
+    /// are delivered with their own short debounce, independently of the repository-wide batch
+    /// sent to [WatchDirectoryNotifier]. The path has to be under one of the watched directories.
+    ///
+    /// The subscription lasts until [SubscriptionHandle::unsubscribe] is called or the handle is
+    /// dropped.
+    fn subscribe_path(
+        &self,
+        path: String,
+        notifier: Box<dyn PathChangeNotifier>,
+    ) -> Arc<SubscriptionHandle> {
+        let id = self.next_subscription_id.fetch_add(1, Ordering::Relaxed);
+        let subscription = PathSubscription::new(PathBuf::from(path), notifier);
+
+        self.subscriptions.write().unwrap().insert(id, subscription);
+
+        Arc::new(SubscriptionHandle::new(id, &self.subscriptions))
+    }
+
     fn stop_watching(&self) {
         println!("Keep watching set to false");
         *self.keep_watching.write().unwrap() = false
     }
 }
 
+impl FileWatcher {
+    fn flush_batch(&self, paths_cached: &mut PendingBatch, queue: &DeliveryQueue) {
+        let paths_to_send: Vec<FileChanged> = remove_temporary_files(&mut paths_cached.changes);
+
+        self.metrics.temporary_paths_filtered.fetch_add(
+            (paths_cached.changes.len() - paths_to_send.len()) as u64,
+            Ordering::Relaxed,
+        );
+
+        if paths_to_send.is_empty() {
+            paths_cached.clear();
+            return;
+        }
+
+        let batch_info =
+            paths_cached.info(self.next_batch_sequence.fetch_add(1, Ordering::Relaxed));
+        paths_cached.clear();
+
+        process_paths_cached(
+            paths_to_send,
+            batch_info,
+            queue,
+            &self.bulk_change_settings.read().unwrap(),
+        );
+    }
+
+    fn watch_symlink_targets(&self, directory: &Path, is_recursive: bool) {
+        let targets = self.symlinks.write().unwrap().discover(
+            directory,
+            is_recursive,
+            &self.roots.read().unwrap(),
+        );
+
+        self.watch_targets(targets, is_recursive);
+    }
+
+    fn watch_targets(&self, targets: Vec<PathBuf>, is_recursive: bool) {
+        if targets.is_empty() {
+            return;
+        }
+
+        let mut watcher_holder = self.watcher.write().unwrap();
+
+        let Some(watcher) = watcher_holder.as_mut() else {
+            return;
+        };
+
+        for target in targets {
+            let recursive_mode = to_recursive_mode(is_recursive);
+
+            if let Err(e) = watcher.watch(&target, recursive_mode, backend_for(&target)) {
+                logger::error(
+                    WATCHER_TAG,
+                    format!("Failed to watch symlink target {target:?}: {e:?}"),
+                );
+            }
+        }
+    }
+
+    /// Follows the symlinks created and forgets the removed ones, then maps the events that
+    /// happened inside followed directories to their link paths.
+    fn follow_symlink_changes(&self, events: Vec<FileChangeEvent>) -> Vec<FileChangeEvent> {
+        for event in &events {
+            let path = Path::new(event.path.as_str());
+
+            match event.event_kind {
+                EventKind::Create(_)
+                    if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) =>
+                {
+                    let is_recursive = self
+                        .roots
+                        .read()
+                        .unwrap()
+                        .root_containing(path)
+                        .is_some_and(|root| root.is_recursive);
+
+                    let targets = self.symlinks.write().unwrap().discover_link(
+                        path,
+                        is_recursive,
+                        &self.roots.read().unwrap(),
+                    );
+
+                    self.watch_targets(targets, is_recursive);
+                }
+                EventKind::Remove(_) => {
+                    let targets = self.symlinks.write().unwrap().forget(path);
+
+                    if let Some(watcher) = self.watcher.write().unwrap().as_mut() {
+                        for target in targets {
+                            let _ = watcher.unwatch(&target);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.symlinks.read().unwrap().map_to_links(events)
+    }
+
+    /// Changes are recorded as soon as they are received, instead of when their batch is sent,
+    /// so the fsmonitor can answer with up to date information.
+    /// Saves the journal if changes were recorded since it was last saved, so they are not lost
+    /// if the app is killed.
+    fn save_journal_if_changed(&self) {
+        let has_unsaved_changes = self
+            .journal
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|journal| journal.has_unsaved_changes());
+
+        if has_unsaved_changes {
+            self.save_journal();
+        }
+    }
+
+    fn record_in_journal(&self, events: &[FileChangeEvent]) {
+        if let Some(journal) = self.journal.write().unwrap().as_mut() {
+            journal.record(events.iter().map(|event| event.path.as_str()));
+        }
+    }
+
+    /// Adds the changes to the batch of their priority and to the path subscriptions. Returns
+    /// true if any of them modified the ignore rules.
+    /// `directories` are the event paths known to be directories, see [WatchSnapshot::update].
+    fn queue_changes(
+        &self,
+        events: Vec<FileChangeEvent>,
+        directories: &HashSet<PathBuf>,
+        paths_cached: &mut PendingBatch,
+        priority_paths_cached: &mut PendingBatch,
+        current_time: u128,
+    ) -> bool {
+        let mut ignore_rules_changed = false;
+
+        for path in events.into_iter() {
+            let is_dir = is_directory_event(&path.event_kind);
+            let file_type = if is_dir {
+                FileType::Directory
+            } else {
+                FileType::File
+            };
+
+            let file_changed = FileChanged {
+                path: path.path,
+                file_type,
+            };
+
+            #[cfg(unix)]
+            if fsmonitor::is_cookie_file(Path::new(file_changed.path.as_str())) {
+                continue;
+            }
+
+            if let Some(ignore_tracking) = self.ignore_tracking.read().unwrap().as_ref() {
+                let changed_path = Path::new(file_changed.path.as_str());
+
+                if ignore_tracking.is_rules_file(changed_path) {
+                    ignore_rules_changed = true;
+                } else if ignore_tracking
+                    .is_ignored(changed_path, is_dir || directories.contains(changed_path))
+                {
+                    self.metrics
+                        .ignored_paths_filtered
+                        .fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            }
+
+            dispatch_to_subscriptions(&self.subscriptions, &file_changed, current_time);
+
+            let priority = self
+                .roots
+                .read()
+                .unwrap()
+                .priority_for(Path::new(file_changed.path.as_str()));
+
+            let cache = match priority {
+                WatchPriority::Normal => &mut *paths_cached,
+                WatchPriority::High => &mut *priority_paths_cached,
+            };
+
+            cache.add(file_changed, path.event_kind, current_time);
+        }
+
+        ignore_rules_changed
+    }
+
+    fn request_ignore_scan(&self, ignore_scan: &mut BackgroundIgnoreScan) {
+        if let Some(ignore_tracking) = self.ignore_tracking.read().unwrap().as_ref() {
+            ignore_scan.request(ignore_tracking.scanner());
+        }
+    }
+
+    /// Rescans the worktree with the current ignore rules and updates the watches of the
+    /// directories that appeared or disappeared. Does nothing if ignore rules aren't tracked.
+    fn update_tracked_directories(&self) {
+        let scanner = match self.ignore_tracking.read().unwrap().as_ref() {
+            Some(ignore_tracking) => ignore_tracking.scanner(),
+            None => return,
+        };
+
+        // The walk doesn't hold the lock, as the watch loop reads the rules for every event
+        self.apply_ignore_scan(scanner.scan());
+    }
+
+    fn apply_ignore_scan(&self, scan: IgnoreScan) {
+        let diff = match self.ignore_tracking.write().unwrap().as_mut() {
+            Some(ignore_tracking) => ignore_tracking.apply(scan),
+            None => return,
+        };
+
+        for directory in diff.added {
+            self.add_watch(directory.to_string_lossy().into_owned(), false);
+        }
+
+        for directory in diff.removed {
+            self.remove_watch(directory.to_string_lossy().into_owned());
+        }
+    }
+}
+
 fn remove_temporary_files(changes: &mut HashMap<FileChanged, Vec<EventKind>>) -> Vec<FileChanged> {
     let paths: Vec<FileChanged> = changes
         .iter()
@@ -246,9 +998,12 @@ fn remove_temporary_files(changes: &mut HashMap<FileChanged, Vec<EventKind>>) ->
             if let (Some(index_created), Some(index_removed)) = (index_created, index_removed)
                 && index_created < index_removed
             {
-                println!(
-                    "Removing entry {} as it looks like a temporary file.",
-                    key.path
+                logger::debug(
+                    WATCHER_TAG,
+                    format!(
+                        "Removing entry {} as it looks like a temporary file.",
+                        key.path
+                    ),
                 );
                 None
             } else {
@@ -268,18 +1023,44 @@ fn is_directory_event(kind: &EventKind) -> bool {
 }
 
 fn process_paths_cached(
-    paths_cached: &mut HashMap<FileChanged, Vec<EventKind>>,
-    notifier: &Box<dyn WatchDirectoryNotifier>,
+    paths_to_send: Vec<FileChanged>,
+    batch_info: BatchInfo,
+    queue: &DeliveryQueue,
+    bulk_change_settings: &BulkChangeSettings,
 ) {
-    let paths_to_send: Vec<FileChanged> = remove_temporary_files(paths_cached);
-    paths_cached.clear();
-
-    if !paths_to_send.is_empty() {
-        println!(
-            "Sending a total of {} paths cached to Kotlin side",
-            paths_to_send.len()
+    if let Some(bulk_change) = bulk_change_settings.summarize(&paths_to_send) {
+        logger::debug(
+            WATCHER_TAG,
+            format!(
+                "Sending a bulk change of {} paths in {} directories to Kotlin side",
+                bulk_change.total_count,
+                bulk_change.directories.len()
+            ),
+        );
+        queue.push(Notification::Batch(Batch::BulkChange(
+            bulk_change,
+            batch_info,
+        )));
+    } else if !paths_to_send.is_empty() {
+        logger::debug(
+            WATCHER_TAG,
+            format!(
+                "Sending a total of {} paths cached to Kotlin side",
+                paths_to_send.len()
+            ),
         );
-        notifier.detected_change(paths_to_send);
+        queue.push(Notification::Batch(Batch::Changes(
+            paths_to_send,
+            batch_info,
+        )));
+    }
+}
+
+fn to_recursive_mode(is_recursive: bool) -> RecursiveMode {
+    if is_recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
     }
 }
 
@@ -292,6 +1073,10 @@ fn current_time_as_millis() -> u128 {
 
 const MIN_TIME_IN_MS_BETWEEN_REFRESHES: u128 = 500;
 const WATCH_TIMEOUT: u64 = 500;
+const JOURNAL_SAVE_INTERVAL_IN_MS: u128 = 30_000;
+const PRIORITY_DEBOUNCE_IN_MS: u128 = 50;
+const PRIORITY_MAX_DELAY_IN_MS: u128 = 200;
+const WATCHER_TAG: &str = "FileWatcher";
 
 fn error_to_code(error_kind: ErrorKind) -> i32 {
     match error_kind {
@@ -353,11 +1138,16 @@ pub struct FileChangeEvent {
 #[uniffi::export(callback_interface)]
 pub trait WatchDirectoryNotifier: Send + Sync + Debug {
     fn should_keep_looping(&self) -> bool;
-    fn detected_change(&self, paths: Vec<FileChanged>);
+    fn detected_change(&self, paths: Vec<FileChanged>, batch: BatchInfo);
+    fn detected_bulk_change(&self, bulk_change: BulkChange, batch: BatchInfo);
+    fn ignore_rules_changed(&self);
     fn on_error(&self, code: i32);
 }
 
-const ACCEPTED_SSH_TYPES: &str = "ssh-ed25519,ecdsa-sha2-nistp256,ecdsa-sha2-nistp384,ecdsa-sha2-nistp521,ssh-rsa,rsa-sha2-512,rsa-sha2-256,ssh-dss";
+const DEFAULT_SSH_PORT: u16 = 22;
+const SSH_TAG: &str = "SshSession";
+/// Source address reported when opening direct-tcpip channels.
+const LOCALHOST: &str = "127.0.0.1";
 
 #[derive(uniffi::Object)]
 pub struct Session {
@@ -366,6 +1156,9 @@ pub struct Session {
 
 pub struct SessionHolder {
     pub session: RwLock<libssh_rs::Session>,
+    /// Host and port given to [Session::setup], shown when verifying the host key.
+    pub destination: RwLock<(String, u16)>,
+    pub options: RwLock<SessionOptions>,
 }
 
 #[uniffi::export]
@@ -376,6 +1169,8 @@ impl Session {
 
         let session_holder = SessionHolder {
             session: RwLock::new(session),
+            destination: RwLock::new((String::new(), DEFAULT_SSH_PORT)),
+            options: RwLock::new(SessionOptions::default()),
         };
 
         Session {
@@ -383,100 +1178,319 @@ impl Session {
         }
     }
 
-    pub fn setup(&self, host: String, user: String, port: Option<i32>) -> String {
-        let session_holder = self.session_holder.as_ref().unwrap();
-        let session = match session_holder.session.write() {
-            Ok(s) => s,
-            Err(e) => {
-                return format!("Something failed obtaining write session: {e:?}");
-            }
+    pub fn setup(
+        &self,
+        host: String,
+        user: String,
+        port: Option<i32>,
+        options: SessionOptions,
+    ) -> Result<(), SshError> {
+        self.connect(host, user, port.map(|port| port as u16), options, None)
+    }
+
+    /// Connects through `jump_session`, which has to be authenticated already, as ProxyJump does.
+    /// The SSH connection runs over a direct-tcpip channel of the jump session, so the host key
+    /// of this session still has to be verified with [Session::verify_host_key].
+    pub fn setup_through(
+        &self,
+        jump_session: Arc<Session>,
+        host: String,
+        user: String,
+        port: Option<i32>,
+        options: SessionOptions,
+    ) -> Result<(), SshError> {
+        let port = port.map(|port| port as u16);
+        let forward_host = session_options::resolve_host_name(&host, &options)?;
+        let forward_port = session_options::resolve_port(&host, port, &options)?;
+
+        let channel = {
+            let jump_session_holder = jump_session.session_holder.as_ref().unwrap();
+            let jump_session = jump_session_holder.session.write()?;
+
+            let channel = jump_session
+                .new_channel()
+                .map_err(|e| SshError::channel("creation", &e))?;
+
+            channel
+                .open_forward(&forward_host, forward_port, LOCALHOST, 0)
+                .map_err(|e| SshError::channel("open forward", &e))?;
+
+            channel
         };
 
-        if let Err(e) = session.set_option(SshOption::Hostname(host)) {
-            let message = libssh_error_to_message(&e);
-            return format!("SSH Hostname option failed: {message}");
+        let (socket, tunnel_stream) = tunnel::socket_pair()?;
+
+        let channel_holder = ChannelHolder {
+            channel: RwLock::new(channel),
+        };
+
+        tunnel::forward_channel(jump_session, channel_holder, tunnel_stream)?;
+
+        self.connect(host, user, port, options, Some(socket))
+    }
+
+    /// Checks the server key against known_hosts once connected. Unless the key is already known,
+    /// the strict host key checking policy or `verifier` decide whether to trust it. Returns the
+    /// status of the key if it's trusted.
+    pub fn verify_host_key(
+        &self,
+        verifier: Box<dyn HostKeyVerifier>,
+    ) -> Result<HostKeyStatus, SshError> {
+        let session_holder = self.session_holder.as_ref().unwrap();
+        let session = session_holder.session.write()?;
+        let status: HostKeyStatus = session.is_known_server()?.into();
+
+        if status == HostKeyStatus::Known {
+            return Ok(status);
         }
 
-        if !user.is_empty() {
-            if let Err(e) = session.set_option(SshOption::User(Some(user))) {
-                let message = libssh_error_to_message(&e);
-                return format!("SSH User option failed: {message}");
+        let fingerprint = host_key::fingerprint(&session.get_server_public_key()?)?;
+        let (host, port) = session_holder.destination.read()?.clone();
+
+        let policy = session_holder.options.read()?.strict_host_key_checking;
+
+        let decision = host_key::decision_for_policy(policy, status).unwrap_or_else(|| {
+            verifier.verify_host_key(HostKeyInfo {
+                host: host.clone(),
+                port,
+                fingerprint: fingerprint.clone(),
+                status,
+            })
+        });
+
+        match decision {
+            HostKeyDecision::Reject if status == HostKeyStatus::Unknown => {
+                Err(SshError::UntrustedHostKey {
+                    host,
+                    port,
+                    fingerprint,
+                })
             }
-        }
+            HostKeyDecision::Reject => Err(SshError::HostKeyMismatch {
+                host,
+                port,
+                fingerprint,
+                status,
+            }),
+            HostKeyDecision::AcceptOnce => Ok(status),
+            HostKeyDecision::AcceptAndSave => {
+                // libssh appends the new key, the old one would be reported as changed again
+                if status == HostKeyStatus::Changed {
+                    let known_hosts_path = session_holder.options.read()?.known_hosts_path.clone();
+
+                    if let Err(e) = KnownHosts::new(known_hosts_path).remove(host, port) {
+                        logger::error(
+                            SSH_TAG,
+                            format!("Removing the old server key from known_hosts failed: {e}"),
+                        );
+                    }
+                }
 
-        if let Some(port) = port {
-            if let Err(e) = session.set_option(SshOption::Port(port as u16)) {
-                let message = libssh_error_to_message(&e);
-                return format!("SSH Port option failed: {message}");
+                if let Err(e) = session.update_known_hosts_file() {
+                    let message = libssh_error_to_message(&e);
+                    logger::error(
+                        SSH_TAG,
+                        format!("Adding the server key to known_hosts failed: {message}"),
+                    );
+                }
+
+                Ok(status)
             }
         }
+    }
 
-        if let Err(e) = session.set_option(SshOption::PublicKeyAcceptedTypes(
-            ACCEPTED_SSH_TYPES.to_string(),
-        )) {
-            let message = libssh_error_to_message(&e);
-            return format!("SSH Public keys option failed: {message}");
-        }
+    /// Tries the `none` method to find out which methods the server allows, as libssh only knows
+    /// them once an authentication attempt has been rejected.
+    pub fn server_auth_info(&self) -> Result<ServerAuthInfo, SshError> {
+        let session_holder = self.session_holder.as_ref().unwrap();
+        let session = session_holder.session.write()?;
 
-        if let Err(e) = session.options_parse_config(None) {
-            let message = libssh_error_to_message(&e);
-            return format!("SSH Configuration parsing failed: {message}");
-        }
+        let auth_error = |e: libssh_rs::Error| SshError::AuthFailure {
+            reason: libssh_error_to_message(&e),
+        };
 
-        if let Err(e) = session.connect() {
-            let message = libssh_error_to_message(&e);
-            return format!("Server connection failed: {message}");
+        let status = session.userauth_none(None).map_err(auth_error)?;
+
+        if status == libssh_rs::AuthStatus::Again {
+            let (host, port) = session_holder.destination.read()?.clone();
+
+            return Err(SshError::Timeout {
+                host,
+                port,
+                kind: TimeoutKind::Authentication,
+            });
         }
 
-        String::new()
+        let authenticated = status == libssh_rs::AuthStatus::Success;
+
+        let methods = if authenticated {
+            Vec::new()
+        } else {
+            to_auth_methods(session.userauth_list(None).map_err(auth_error)?)
+        };
+
+        let banner = session
+            .get_issue_banner()
+            .ok()
+            .filter(|banner| !banner.trim().is_empty());
+
+        Ok(ServerAuthInfo {
+            authenticated,
+            methods,
+            banner,
+        })
     }
 
-    pub fn public_key_auth(&self, password: String) -> i32 {
-        //AuthStatus {
-        println!("Public key auth");
+    /// Lets libssh try the keys of the ssh-agent, ssh_config and the default identity files, using
+    /// `password` as their passphrase.
+    pub fn public_key_auth(&self, password: String) -> AuthStatus {
         let session_holder = self.session_holder.as_ref().unwrap();
         let session = match session_holder.session.write() {
             Ok(s) => s,
             Err(e) => {
-                println!("Something failed obtaining write session: {e:?}");
-                return -1;
+                return AuthStatus::Error {
+                    message: format!("Something failed obtaining write session: {e:?}"),
+                };
             }
         };
 
-        let status = match session.userauth_public_key_auto(None, Some(&password)) {
-            Ok(s) => s,
+        match session.userauth_public_key_auto(None, Some(&password)) {
+            Ok(status) => status.into(),
             Err(e) => {
                 let message = libssh_error_to_message(&e);
-                println!("Something failed when using public key auto auth: {message}");
-                return -2;
+                AuthStatus::Error {
+                    message: format!("Something failed when using public key auto auth: {message}"),
+                }
             }
-        };
+        }
+    }
 
-        println!("Status is {status:?}");
+    pub fn password_auth(&self, password: String) -> AuthStatus {
+        let session_holder = self.session_holder.as_ref().unwrap();
+        let session = match session_holder.session.write() {
+            Ok(s) => s,
+            Err(e) => {
+                return AuthStatus::Error {
+                    message: format!("Something failed obtaining write session: {e:?}"),
+                };
+            }
+        };
 
-        to_int(status) // TODO remove this cast
+        match session.userauth_password(None, Some(&password)) {
+            Ok(status) => status.into(),
+            Err(e) => {
+                let message = libssh_error_to_message(&e);
+                AuthStatus::Error {
+                    message: format!(
+                        "An error occurred when using user auth with password: {message}"
+                    ),
+                }
+            }
+        }
     }
 
-    pub fn password_auth(&self, password: String) -> i32 {
-        //AuthStatus {
+    /// Authenticates with the private key at `path`. The public key is offered first, so the key
+    /// is only used to sign if the server would accept it.
+    pub fn identity_file_auth(&self, path: String, passphrase: Option<String>) -> AuthStatus {
         let session_holder = self.session_holder.as_ref().unwrap();
         let session = match session_holder.session.write() {
             Ok(s) => s,
             Err(e) => {
-                println!("Something failed obtaining write session: {e:?}");
-                return -1;
+                return AuthStatus::Error {
+                    message: format!("Something failed obtaining write session: {e:?}"),
+                };
             }
         };
 
-        let status = match session.userauth_password(None, Some(&password)) {
+        authenticate_with_identity_file(&session, &path, passphrase.as_deref())
+    }
+
+    /// Authenticates with the keys of the ssh-agent at `SSH_AUTH_SOCK`, see
+    /// [agent::SshAgent::identities] for the keys that will be offered.
+    pub fn agent_auth(&self) -> AuthStatus {
+        let session_holder = self.session_holder.as_ref().unwrap();
+        let session = match session_holder.session.write() {
             Ok(s) => s,
+            Err(e) => {
+                return AuthStatus::Error {
+                    message: format!("Something failed obtaining write session: {e:?}"),
+                };
+            }
+        };
+
+        match session.userauth_agent(None) {
+            Ok(status) => status.into(),
             Err(e) => {
                 let message = libssh_error_to_message(&e);
-                println!("An error occurred when using user auth with password: {message}");
-                return -2;
+                AuthStatus::Error {
+                    message: format!("Something failed when using agent auth: {message}"),
+                }
+            }
+        }
+    }
+
+    /// Answers the server questions through `prompter` until the server stops asking, which can
+    /// take several rounds (password, then a one-time code...).
+    pub fn keyboard_interactive_auth(
+        &self,
+        prompter: Box<dyn InteractiveAuthPrompter>,
+    ) -> AuthStatus {
+        let session_holder = self.session_holder.as_ref().unwrap();
+        let session = match session_holder.session.write() {
+            Ok(s) => s,
+            Err(e) => {
+                return AuthStatus::Error {
+                    message: format!("Something failed obtaining write session: {e:?}"),
+                };
+            }
+        };
+
+        let interactive_auth_error = |e: libssh_rs::Error| {
+            let message = libssh_error_to_message(&e);
+            AuthStatus::Error {
+                message: format!(
+                    "Something failed when using keyboard-interactive auth: {message}"
+                ),
             }
         };
-        to_int(status) // TODO remove this cast
+
+        loop {
+            match session.userauth_keyboard_interactive(None, None) {
+                Ok(libssh_rs::AuthStatus::Info) => {}
+                Ok(status) => return status.into(),
+                Err(e) => return interactive_auth_error(e),
+            }
+
+            let info = match session.userauth_keyboard_interactive_info() {
+                Ok(info) => info,
+                Err(e) => return interactive_auth_error(e),
+            };
+
+            // Servers can send rounds without questions, which are answered without bothering
+            // the user
+            let answers = if info.prompts.is_empty() {
+                Vec::new()
+            } else {
+                let prompts_count = info.prompts.len();
+
+                match prompter.answer_prompts(info.into()) {
+                    Some(answers) if answers.len() == prompts_count => answers,
+                    Some(answers) => {
+                        return AuthStatus::Error {
+                            message: format!(
+                                "Expected {prompts_count} answers for keyboard-interactive auth, got {}",
+                                answers.len()
+                            ),
+                        };
+                    }
+                    None => return AuthStatus::Denied,
+                }
+            };
+
+            if let Err(e) = session.userauth_keyboard_interactive_set_answers(&answers) {
+                return interactive_auth_error(e);
+            }
+        }
     }
 
     pub fn disconnect(&self) {
@@ -488,6 +1502,90 @@ impl Session {
     }
 }
 
+impl Session {
+    /// Connects to `host`, over `socket` if it's given. The port is only set when given, so the
+    /// one of ssh_config isn't overridden.
+    fn connect(
+        &self,
+        host: String,
+        user: String,
+        port: Option<u16>,
+        options: SessionOptions,
+        socket: Option<RawSocket>,
+    ) -> Result<(), SshError> {
+        let session_holder = self.session_holder.as_ref().unwrap();
+        let session = session_holder.session.write()?;
+
+        let resolved_port = session_options::resolve_port(&host, port, &options)?;
+        *session_holder.destination.write()? = (host.clone(), resolved_port);
+
+        session
+            .set_option(SshOption::Hostname(host.clone()))
+            .map_err(|e| SshError::configuration("Hostname", &e))?;
+
+        if !user.is_empty() {
+            session
+                .set_option(SshOption::User(Some(user)))
+                .map_err(|e| SshError::configuration("User", &e))?;
+        }
+
+        if let Some(port) = port {
+            session
+                .set_option(SshOption::Port(port))
+                .map_err(|e| SshError::configuration("Port", &e))?;
+        }
+
+        session_options::apply_options(&session, &host, resolved_port, &options, socket)?;
+        *session_holder.options.write()? = options;
+
+        session
+            .connect()
+            .map_err(|e| SshError::from_libssh_connection(&host, resolved_port, &e))?;
+
+        session_options::apply_auth_timeout(&session, &*session_holder.options.read()?)
+    }
+}
+
+/// See [Session::identity_file_auth], also used for every key of [Session::public_key_auth].
+fn authenticate_with_identity_file(
+    session: &libssh_rs::Session,
+    path: &str,
+    passphrase: Option<&str>,
+) -> AuthStatus {
+    let private_key = match SshKey::from_privkey_file(path, passphrase) {
+        Ok(key) => key,
+        Err(e) => {
+            let message = libssh_error_to_message(&e);
+            return AuthStatus::Error {
+                message: format!("Unable to load the identity file {path}: {message}"),
+            };
+        }
+    };
+
+    let identity_file_error = |e: libssh_rs::Error| {
+        let message = libssh_error_to_message(&e);
+        AuthStatus::Error {
+            message: format!("Something failed when using the identity file {path}: {message}"),
+        }
+    };
+
+    let public_key = match private_key.get_public_key() {
+        Ok(key) => key,
+        Err(e) => return identity_file_error(e),
+    };
+
+    match session.userauth_try_publickey(None, &public_key) {
+        Ok(libssh_rs::AuthStatus::Success) => {}
+        Ok(status) => return status.into(),
+        Err(e) => return identity_file_error(e),
+    }
+
+    match session.userauth_publickey(None, &private_key) {
+        Ok(status) => status.into(),
+        Err(e) => identity_file_error(e),
+    }
+}
+
 pub struct ChannelHolder {
     channel: RwLock<libssh_rs::Channel>,
 }
@@ -498,6 +1596,8 @@ unsafe impl Sync for ChannelHolder {}
 #[derive(uniffi::Object)]
 pub struct Channel {
     channel: Option<ChannelHolder>,
+    /// Kept for the timeouts and keepalives of the session.
+    session: Arc<Session>,
 }
 
 #[uniffi::export]
@@ -505,8 +1605,12 @@ impl Channel {
     #[uniffi::constructor]
     pub fn new(session: Arc<Session>) -> Channel {
         let session_holder = session.as_ref().session_holder.as_ref().unwrap();
-        let session = session_holder.session.read().unwrap();
-        let channel = session.new_channel().unwrap();
+        let channel = session_holder
+            .session
+            .read()
+            .unwrap()
+            .new_channel()
+            .unwrap();
 
         let channel_holder = ChannelHolder {
             channel: RwLock::new(channel),
@@ -514,22 +1618,14 @@ impl Channel {
 
         Channel {
             channel: Some(channel_holder),
+            session,
         }
     }
 
-    pub fn open_session(&self) -> String {
-        let channel_holder = self.channel.as_ref().unwrap();
-        let channel = match channel_holder.channel.write() {
-            Ok(c) => c,
-            Err(e) => return format!("{e:#}"),
-        };
-
-        if let Err(e) = channel.open_session() {
-            let message = libssh_error_to_message(&e);
-            return format!("Channel open session failed: {message}");
-        };
-
-        String::new()
+    pub fn open_session(&self) -> Result<(), SshError> {
+        self.get_channel()?
+            .open_session()
+            .map_err(|e| SshError::channel("open session", &e))
     }
 
     pub fn is_open(&self) -> bool {
@@ -537,7 +1633,10 @@ impl Channel {
         let channel = match channel_holder.channel.write() {
             Ok(s) => s,
             Err(e) => {
-                println!("Something failed obtaining write channel: {e:?}");
+                logger::error(
+                    SSH_TAG,
+                    format!("Something failed obtaining write channel: {e:?}"),
+                );
                 return false;
             }
         };
@@ -545,128 +1644,61 @@ impl Channel {
         channel.is_open()
     }
 
-    pub fn close_channel(&self) -> String {
-        let channel_holder = self.channel.as_ref().unwrap();
-        let channel = match channel_holder.channel.write() {
-            Ok(s) => s,
-            Err(e) => {
-                return format!("Something failed obtaining write channel: {e:?}");
-            }
-        };
-
-        match channel.close() {
-            Ok(_) => String::new(),
-            Err(e) => {
-                let message = libssh_error_to_message(&e);
-                format!("Channel closing failed: {message}")
-            }
-        }
+    pub fn close_channel(&self) -> Result<(), SshError> {
+        self.get_channel()?
+            .close()
+            .map_err(|e| SshError::channel("close", &e))
     }
 
-    pub fn request_exec(&self, command: String) -> String {
-        let channel_holder = self.channel.as_ref().unwrap();
-        let channel = match channel_holder.channel.write() {
-            Ok(s) => s,
-            Err(e) => {
-                return format!("Something failed obtaining write channel: {e:?}");
-            }
-        };
-
-        match channel.request_exec(&command) {
-            Ok(_) => String::new(),
-            Err(e) => {
-                let message = libssh_error_to_message(&e);
-                format!("Channel request exec failed: {message}")
-            }
-        }
+    pub fn request_exec(&self, command: String) -> Result<(), SshError> {
+        self.get_channel()?
+            .request_exec(&command)
+            .map_err(|e| SshError::channel("request exec", &e))
     }
 
     pub fn poll_has_bytes(&self, is_stderr: bool) -> bool {
-        let channel_holder = self.channel.as_ref().unwrap();
-        let channel = match channel_holder.channel.write() {
-            Ok(s) => s,
-            Err(e) => {
-                println!("Something failed obtaining write channel: {e:?}");
-                return false;
-            }
-        };
-
-        let poll_timeout = match channel.poll_timeout(is_stderr, None) {
-            Ok(timeout) => timeout,
+        match self.wait_for_data(is_stderr) {
+            Ok(has_bytes) => has_bytes,
             Err(e) => {
-                let message = libssh_error_to_message(&e);
-                println!("{message}");
-                return false;
+                logger::error(SSH_TAG, format!("Polling the SSH channel failed: {e}"));
+                false
             }
-        };
-
-        match poll_timeout {
-            PollStatus::AvailableBytes(count) => count > 0,
-            PollStatus::EndOfFile => false,
         }
     }
 
-    pub fn read(&self, is_stderr: bool, len: u64) -> Option<ReadResult> {
-        let ulen = len as usize;
+    /// Waits for data up to the read timeout of the session. A read count of 0 means the end of
+    /// the stream has been reached.
+    pub fn read(&self, is_stderr: bool, len: u64) -> Result<ReadResult, SshError> {
+        let mut buffer = vec![0; len as usize];
 
-        let channel = match self.channel.as_ref()?.channel.write() {
-            Ok(s) => s,
-            Err(e) => {
-                println!("Something failed obtaining write channel: {e:?}");
-                return None;
-            }
-        };
+        if !self.wait_for_data(is_stderr)? {
+            return Ok(ReadResult {
+                read_count: 0,
+                data: buffer,
+            });
+        }
 
-        let mut buffer = vec![0; ulen];
-        let read = match channel.read_timeout(&mut buffer, is_stderr, None) {
-            Ok(s) => s,
-            Err(e) => {
-                let message = libssh_error_to_message(&e);
-                println!("Something failed reading SSH channel: {message}");
-                return None;
-            }
-        };
+        let read = self
+            .get_channel()?
+            .read_timeout(&mut buffer, is_stderr, None)
+            .map_err(|e| SshError::channel("read", &e))?;
 
-        Some(ReadResult {
+        Ok(ReadResult {
             read_count: read as u64,
             data: buffer,
         })
     }
 
-    pub fn write_byte(&self, byte: i32) -> String {
-        let channel = match self.get_channel() {
-            Ok(c) => c,
-            Err(e) => {
-                return format!("Something failed obtaining write channel: {e:?}");
-            }
-        };
+    pub fn write_byte(&self, byte: i32) -> Result<(), SshError> {
+        let channel = self.get_channel()?;
 
-        let res = channel.stdin().write_all(&byte.to_ne_bytes());
-
-        match res {
-            Ok(_) => String::new(),
-            Err(e) => {
-                format!("Something failed writing to channel STDIN: {e:?}")
-            }
-        }
+        Ok(channel.stdin().write_all(&byte.to_ne_bytes())?)
     }
 
-    pub fn write_bytes(&self, data: &Vec<u8>) -> String {
-        let channel = match self.get_channel() {
-            Ok(c) => c,
-            Err(e) => {
-                return format!("Something failed obtaining write channel: {e:?}");
-            }
-        };
+    pub fn write_bytes(&self, data: &Vec<u8>) -> Result<(), SshError> {
+        let channel = self.get_channel()?;
 
-        let res = channel.stdin().write_all(data);
-
-        match res {
-            Ok(_) => String::new(),
-            Err(e) => {
-                format!("Something failed writing to channel STDIN: {e:?}")
-            }
-        }
+        Ok(channel.stdin().write_all(data)?)
     }
 }
 
@@ -674,24 +1706,94 @@ impl Channel {
     fn get_channel(&'_ self) -> LockResult<RwLockWriteGuard<'_, libssh_rs::Channel>> {
         self.channel.as_ref().unwrap().channel.write()
     }
-}
 
-fn to_int(auth_status: AuthStatus) -> i32 {
-    match auth_status {
-        AuthStatus::Success => 1,
-        AuthStatus::Denied => 2,
-        AuthStatus::Partial => 3,
-        AuthStatus::Info => 4,
-        AuthStatus::Again => 5,
+    /// Waits until the stream has data, returning false once it has reached its end. Fails once
+    /// [SessionOptions::read_timeout_ms] has passed without data.
+    ///
+    /// Keepalives are sent in every silent interval so idle connections aren't dropped by
+    /// firewalls. libssh doesn't report their replies, so a silent server (running a long hook,
+    /// for example) is not considered dead, only a keepalive that can't be sent fails.
+    fn wait_for_data(&self, is_stderr: bool) -> Result<bool, SshError> {
+        let session_holder = self.session.session_holder.as_ref().unwrap();
+        let (read_timeout, keepalive_interval) = {
+            let options = session_holder.options.read()?;
+
+            (
+                options.read_timeout_ms.map(Duration::from_millis),
+                options.keepalive_interval_ms.map(Duration::from_millis),
+            )
+        };
+
+        let wait_start = Instant::now();
+
+        loop {
+            let remaining =
+                read_timeout.map(|timeout| timeout.saturating_sub(wait_start.elapsed()));
+
+            if remaining == Some(Duration::ZERO) {
+                let (host, port) = session_holder.destination.read()?.clone();
+
+                return Err(SshError::Timeout {
+                    host,
+                    port,
+                    kind: TimeoutKind::Read,
+                });
+            }
+
+            let poll_timeout = match (remaining, keepalive_interval) {
+                (Some(remaining), Some(interval)) => Some(remaining.min(interval)),
+                (remaining, interval) => remaining.or(interval),
+            };
+
+            let status = self
+                .get_channel()?
+                .poll_timeout(is_stderr, poll_timeout)
+                .map_err(|e| SshError::channel("poll", &e))?;
+
+            match status {
+                PollStatus::AvailableBytes(count) if count > 0 => return Ok(true),
+                PollStatus::AvailableBytes(_) => {}
+                PollStatus::EndOfFile => return Ok(false),
+            }
+
+            if keepalive_interval.is_some() {
+                session_holder
+                    .session
+                    .write()?
+                    .send_keepalive()
+                    .map_err(|e| SshError::channel("keepalive", &e))?;
+            }
+        }
     }
 }
 
-fn libssh_error_to_message(err: &libssh_rs::Error) -> String {
-    match err {
-        libssh_rs::Error::RequestDenied(message) => message.clone(),
-        libssh_rs::Error::Fatal(message) => message.clone(),
-        libssh_rs::Error::TryAgain => "Something went wrong, please try again".to_string(),
-        libssh_rs::Error::Sftp(_) => "Sftp not supported".to_string(),
+#[derive(uniffi::Enum, Debug, Clone, Eq, PartialEq)]
+pub enum AuthStatus {
+    Success,
+    /// The credentials were rejected, another method or password can be tried.
+    Denied,
+    /// The method succeeded, but the server requires more methods to be used.
+    Partial,
+    /// The server sent keyboard-interactive questions.
+    Info,
+    /// Non-blocking call that has to be retried. Sessions are blocking, so it means the server
+    /// didn't answer within [SessionOptions::auth_timeout_ms].
+    Again,
+    /// The authentication couldn't be attempted.
+    Error {
+        message: String,
+    },
+}
+
+impl From<libssh_rs::AuthStatus> for AuthStatus {
+    fn from(auth_status: libssh_rs::AuthStatus) -> Self {
+        match auth_status {
+            libssh_rs::AuthStatus::Success => AuthStatus::Success,
+            libssh_rs::AuthStatus::Denied => AuthStatus::Denied,
+            libssh_rs::AuthStatus::Partial => AuthStatus::Partial,
+            libssh_rs::AuthStatus::Info => AuthStatus::Info,
+            libssh_rs::AuthStatus::Again => AuthStatus::Again,
+        }
     }
 }
 
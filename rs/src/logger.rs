@@ -0,0 +1,36 @@
+use std::sync::RwLock;
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Error,
+}
+
+/// Receives the messages of the native code, so they end up in the app logs instead of stdout.
+#[uniffi::export(callback_interface)]
+pub trait NativeLogger: Send + Sync {
+    fn log(&self, level: LogLevel, tag: String, message: String);
+}
+
+static LOGGER: RwLock<Option<Box<dyn NativeLogger>>> = RwLock::new(None);
+
+/// Sets the logger used by the whole process. Messages logged before it's set are dropped.
+#[uniffi::export]
+pub fn set_native_logger(logger: Box<dyn NativeLogger>) {
+    *LOGGER.write().unwrap() = Some(logger);
+}
+
+pub fn log(level: LogLevel, tag: &str, message: String) {
+    if let Some(logger) = LOGGER.read().unwrap().as_ref() {
+        logger.log(level, tag.to_string(), message);
+    }
+}
+
+pub fn debug(tag: &str, message: String) {
+    log(LogLevel::Debug, tag, message);
+}
+
+pub fn error(tag: &str, message: String) {
+    log(LogLevel::Error, tag, message);
+}
@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use notify::EventKind;
+
+/// Snapshot of the watcher counters since it was created.
+#[derive(uniffi::Record, Debug, Clone, Default)]
+pub struct WatcherMetrics {
+    /// Events received from the OS by kind, before any filtering.
+    pub create_events: u64,
+    pub modify_events: u64,
+    pub remove_events: u64,
+    pub access_events: u64,
+    pub other_events: u64,
+    /// Paths created and removed within the same batch, which are never reported.
+    pub temporary_paths_filtered: u64,
+    /// Paths matching the ignore rules, which are never reported.
+    pub ignored_paths_filtered: u64,
+    /// Batches delivered to the notifier, including bulk changes.
+    pub batches_sent: u64,
+    /// Amount of paths of the largest batch delivered.
+    pub largest_batch: u64,
+    /// Average time between the first event of a batch being received and the batch being
+    /// delivered, in milliseconds.
+    pub average_flush_latency_ms: u64,
+    /// Batches merged into a pending one because the consumer was not keeping up.
+    pub merged_batches: u64,
+    /// Paths only reported as part of a bulk change because their batch exceeded the maximum
+    /// batch size.
+    pub dropped_paths: u64,
+}
+
+#[derive(Default)]
+pub struct MetricsCounters {
+    pub create_events: AtomicU64,
+    pub modify_events: AtomicU64,
+    pub remove_events: AtomicU64,
+    pub access_events: AtomicU64,
+    pub other_events: AtomicU64,
+    pub temporary_paths_filtered: AtomicU64,
+    pub ignored_paths_filtered: AtomicU64,
+    pub batches_sent: AtomicU64,
+    pub largest_batch: AtomicU64,
+    pub total_flush_latency_ms: AtomicU64,
+    pub merged_batches: AtomicU64,
+    pub dropped_paths: AtomicU64,
+}
+
+impl MetricsCounters {
+    pub fn record_event(&self, event_kind: &EventKind) {
+        let counter = match event_kind {
+            EventKind::Create(_) => &self.create_events,
+            EventKind::Modify(_) => &self.modify_events,
+            EventKind::Remove(_) => &self.remove_events,
+            EventKind::Access(_) => &self.access_events,
+            EventKind::Any | EventKind::Other => &self.other_events,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_batch_sent(&self, size: u64, latency_ms: u64) {
+        self.batches_sent.fetch_add(1, Ordering::Relaxed);
+        self.largest_batch.fetch_max(size, Ordering::Relaxed);
+        self.total_flush_latency_ms
+            .fetch_add(latency_ms, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for counter in [
+            &self.create_events,
+            &self.modify_events,
+            &self.remove_events,
+            &self.access_events,
+            &self.other_events,
+            &self.temporary_paths_filtered,
+            &self.ignored_paths_filtered,
+            &self.batches_sent,
+            &self.largest_batch,
+            &self.total_flush_latency_ms,
+            &self.merged_batches,
+            &self.dropped_paths,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> WatcherMetrics {
+        let batches_sent = self.batches_sent.load(Ordering::Relaxed);
+        let total_flush_latency_ms = self.total_flush_latency_ms.load(Ordering::Relaxed);
+
+        WatcherMetrics {
+            create_events: self.create_events.load(Ordering::Relaxed),
+            modify_events: self.modify_events.load(Ordering::Relaxed),
+            remove_events: self.remove_events.load(Ordering::Relaxed),
+            access_events: self.access_events.load(Ordering::Relaxed),
+            other_events: self.other_events.load(Ordering::Relaxed),
+            temporary_paths_filtered: self.temporary_paths_filtered.load(Ordering::Relaxed),
+            ignored_paths_filtered: self.ignored_paths_filtered.load(Ordering::Relaxed),
+            batches_sent,
+            largest_batch: self.largest_batch.load(Ordering::Relaxed),
+            average_flush_latency_ms: total_flush_latency_ms
+                .checked_div(batches_sent)
+                .unwrap_or_default(),
+            merged_batches: self.merged_batches.load(Ordering::Relaxed),
+            dropped_paths: self.dropped_paths.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, ModifyKind, RemoveKind};
+
+    #[test]
+    fn counts_events_by_kind() {
+        let counters = MetricsCounters::default();
+        counters.record_event(&EventKind::Create(CreateKind::File));
+        counters.record_event(&EventKind::Modify(ModifyKind::Any));
+        counters.record_event(&EventKind::Modify(ModifyKind::Any));
+        counters.record_event(&EventKind::Remove(RemoveKind::Folder));
+        counters.record_event(&EventKind::Other);
+
+        let metrics = counters.snapshot();
+
+        assert_eq!(metrics.create_events, 1);
+        assert_eq!(metrics.modify_events, 2);
+        assert_eq!(metrics.remove_events, 1);
+        assert_eq!(metrics.access_events, 0);
+        assert_eq!(metrics.other_events, 1);
+    }
+
+    #[test]
+    fn tracks_the_largest_batch_and_average_latency() {
+        let counters = MetricsCounters::default();
+        assert_eq!(counters.snapshot().average_flush_latency_ms, 0);
+
+        counters.record_batch_sent(10, 100);
+        counters.record_batch_sent(3, 200);
+
+        let metrics = counters.snapshot();
+
+        assert_eq!(metrics.batches_sent, 2);
+        assert_eq!(metrics.largest_batch, 10);
+        assert_eq!(metrics.average_flush_latency_ms, 150);
+    }
+
+    #[test]
+    fn reset_starts_counting_from_zero() {
+        let counters = MetricsCounters::default();
+        counters.record_event(&EventKind::Create(CreateKind::File));
+        counters.record_batch_sent(10, 100);
+        counters.merged_batches.fetch_add(1, Ordering::Relaxed);
+
+        counters.reset();
+        counters.record_batch_sent(2, 20);
+
+        let metrics = counters.snapshot();
+
+        assert_eq!(metrics.create_events, 0);
+        assert_eq!(metrics.merged_batches, 0);
+        assert_eq!(metrics.batches_sent, 1);
+        assert_eq!(metrics.largest_batch, 2);
+        assert_eq!(metrics.average_flush_latency_ms, 20);
+    }
+}
@@ -0,0 +1,333 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use libssh_rs::RawSocket;
+
+use crate::session_options::SessionOptions;
+use crate::ssh_config;
+use crate::ssh_error::SshError;
+use crate::tunnel;
+
+const PROXY_COMMAND: &str = "ProxyCommand";
+const BUFFER_SIZE: usize = 32 * 1024;
+
+/// Characters the shell would interpret, as rejected by OpenSSH in the user and host names.
+const SHELL_CHARACTERS: &str = "'`\"$\\;&<>|(){}[]*?!~#";
+
+/// [SessionOptions::proxy_command], or the `ProxyCommand` option of ssh_config for `host`.
+pub fn resolve_proxy_command(
+    host: &str,
+    options: &SessionOptions,
+) -> Result<Option<String>, SshError> {
+    match &options.proxy_command {
+        Some(proxy_command) => Ok(Some(proxy_command.clone())),
+        None => ssh_config::find_option(&options.ssh_config, host, PROXY_COMMAND),
+    }
+}
+
+/// Runs `proxy_command` through the shell and returns a socket connected to its stdin and
+/// stdout, so libssh can use it as the connection to the server. The command is killed once the
+/// session closes the socket, as OpenSSH does.
+///
+/// `host_name` is the actual name of the server (`HostName` in ssh_config) and `alias` the host
+/// as given in the URL.
+pub fn spawn(
+    proxy_command: &str,
+    host_name: &str,
+    alias: &str,
+    port: u16,
+    user: &str,
+) -> Result<RawSocket, SshError> {
+    let proxy_command = expand_tokens(proxy_command, host_name, alias, port, user)?;
+
+    let mut child = shell_command(&proxy_command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| SshError::Configuration {
+            option: PROXY_COMMAND.to_string(),
+            reason: format!("Unable to run \"{proxy_command}\": {e}"),
+        })?;
+
+    let mut child_stdin = child.stdin.take().expect("stdin is piped");
+    let mut child_stdout = child.stdout.take().expect("stdout is piped");
+
+    let (socket, tunnel_stream) = tunnel::socket_pair()?;
+    let mut session_reader = tunnel_stream.try_clone()?;
+    let mut session_writer = tunnel_stream;
+
+    let child = Arc::new(Mutex::new(child));
+
+    {
+        let child = child.clone();
+
+        thread::spawn(move || {
+            // Stops once the session has been disconnected or the command can't be written to
+            let _ = relay(&mut session_reader, &mut child_stdin);
+
+            drop(child_stdin);
+            kill(&child);
+        });
+    }
+
+    thread::spawn(move || {
+        let _ = relay(&mut child_stdout, &mut session_writer);
+
+        // The command exited or closed its stdout, so the session sees the connection closed
+        let _ = session_writer.shutdown(Shutdown::Both);
+        kill(&child);
+
+        // Its errors have already been written to stderr
+        if let Ok(mut child) = child.lock() {
+            let _ = child.wait();
+        }
+    });
+
+    Ok(socket)
+}
+
+/// Copies until `reader` is closed. [std::io::copy] isn't used as it splices sockets into pipes,
+/// which waits for more data than what the session may have sent.
+fn relay(reader: &mut impl Read, writer: &mut impl Write) -> std::io::Result<()> {
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(count) => {
+                writer.write_all(&buffer[..count])?;
+                writer.flush()?;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn kill(child: &Mutex<Child>) {
+    if let Ok(mut child) = child.lock() {
+        // Fails if the command has already exited, which is fine
+        let _ = child.kill();
+    }
+}
+
+/// Expands the `%h` (host name), `%n` (host as given), `%p` (port), `%r` (user) and `%%` tokens
+/// of ssh_config.
+///
+/// The host and user come from the remote URL, which can be chosen by anyone (in a submodule,
+/// for example), so values the shell would interpret are rejected instead of being run.
+fn expand_tokens(
+    proxy_command: &str,
+    host_name: &str,
+    alias: &str,
+    port: u16,
+    user: &str,
+) -> Result<String, SshError> {
+    let mut expanded = String::with_capacity(proxy_command.len());
+    let mut chars = proxy_command.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('h') => expanded.push_str(shell_safe("host", host_name)?),
+            Some('n') => expanded.push_str(shell_safe("host", alias)?),
+            Some('p') => expanded.push_str(&port.to_string()),
+            Some('r') => expanded.push_str(shell_safe("user", user)?),
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+
+    Ok(expanded)
+}
+
+fn shell_safe<'a>(name: &str, value: &'a str) -> Result<&'a str, SshError> {
+    let is_safe = !value.starts_with('-')
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || SHELL_CHARACTERS.contains(c));
+
+    if is_safe {
+        Ok(value)
+    } else {
+        Err(SshError::Configuration {
+            option: PROXY_COMMAND.to_string(),
+            reason: format!("The {name} \"{value}\" contains characters not allowed in a command"),
+        })
+    }
+}
+
+/// `exec` replaces the shell, so killing the child kills the command itself.
+#[cfg(unix)]
+fn shell_command(proxy_command: &str) -> Command {
+    let mut command = Command::new("/bin/sh");
+    command.arg("-c").arg(format!("exec {proxy_command}"));
+    command
+}
+
+#[cfg(windows)]
+fn shell_command(proxy_command: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(proxy_command);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_tokens() {
+        let expanded = expand_tokens(
+            "nc -X 5 -x proxy:1080 %h %p %r%% %n %x %",
+            "git.example.com",
+            "example",
+            22,
+            "git",
+        );
+
+        assert_eq!(
+            expanded.unwrap(),
+            "nc -X 5 -x proxy:1080 git.example.com 22 git% example %x %"
+        );
+    }
+
+    #[test]
+    fn expands_the_host_name_of_aliased_hosts() {
+        let config = std::env::temp_dir().join(format!(
+            "gitnuro-proxy-command-alias-{}",
+            std::process::id()
+        ));
+        std::fs::write(
+            &config,
+            "Host work\n    HostName git.corp\n    ProxyCommand nc %h %p # %n\n",
+        )
+        .unwrap();
+
+        let options = SessionOptions {
+            ssh_config: crate::session_options::SshConfigSource::File {
+                path: config.to_string_lossy().to_string(),
+            },
+            ..Default::default()
+        };
+
+        let proxy_command = resolve_proxy_command("work", &options).unwrap().unwrap();
+        let host_name = crate::session_options::resolve_host_name("work", &options).unwrap();
+
+        assert_eq!(
+            expand_tokens(&proxy_command, &host_name, "work", 22, "git").unwrap(),
+            "nc git.corp 22 # work"
+        );
+
+        std::fs::remove_file(config).unwrap();
+    }
+
+    #[test]
+    fn rejects_hosts_and_users_the_shell_would_interpret() {
+        for host in [
+            "$(touch pwned)",
+            "`id`",
+            "a;b",
+            "a b",
+            "-oProxyCommand=id",
+            "a|b",
+            "a\nb",
+        ] {
+            assert!(
+                expand_tokens("nc %h %p", host, host, 22, "git").is_err(),
+                "{host}"
+            );
+        }
+
+        assert!(
+            expand_tokens(
+                "nc %h %p %r",
+                "git.example.com",
+                "git.example.com",
+                22,
+                "$(id)"
+            )
+            .is_err()
+        );
+        assert!(expand_tokens("nc %h %p", "fe80::1%eth0", "fe80::1%eth0", 22, "").is_ok());
+
+        // Values not used by the command are not checked
+        assert!(expand_tokens("nc proxy 22", "$(id)", "$(id)", 22, "").is_ok());
+    }
+
+    /// Runs a relay script as the proxy command, connected to a server that answers with an SSH
+    /// banner, as a local sshd would.
+    #[cfg(unix)]
+    #[test]
+    fn runs_the_connection_through_a_relay_script() {
+        use std::io::BufRead;
+        use std::net::TcpListener;
+        use std::os::unix::io::FromRawFd;
+        use std::os::unix::net::UnixStream;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+
+            let mut client_banner = String::new();
+            std::io::BufReader::new(&stream)
+                .read_line(&mut client_banner)
+                .unwrap();
+
+            stream.write_all(client_banner.as_bytes()).unwrap();
+        });
+
+        let script = std::env::temp_dir().join(format!("gitnuro-relay-{}.sh", std::process::id()));
+        std::fs::write(
+            &script,
+            "exec 3<>\"/dev/tcp/$1/$2\"\ncat <&3 &\nexec cat >&3\n",
+        )
+        .unwrap();
+
+        let proxy_command = format!("bash {} %h %p", script.display());
+        let socket = spawn(&proxy_command, "127.0.0.1", "127.0.0.1", port, "git").unwrap();
+        let mut stream = unsafe { UnixStream::from_raw_fd(socket) };
+        let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+
+        let mut server_banner = String::new();
+        reader.read_line(&mut server_banner).unwrap();
+        assert_eq!(server_banner, "SSH-2.0-OpenSSH_9.6\r\n");
+
+        stream.write_all(b"SSH-2.0-gitnuro\r\n").unwrap();
+
+        let mut echoed_banner = String::new();
+        reader.read_line(&mut echoed_banner).unwrap();
+        assert_eq!(echoed_banner, "SSH-2.0-gitnuro\r\n");
+
+        server.join().unwrap();
+
+        // The server closed the connection, so the session sees its end too
+        let mut remaining = Vec::new();
+        reader.read_to_end(&mut remaining).unwrap();
+        assert!(remaining.is_empty());
+
+        std::fs::remove_file(script).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_run_with_unsafe_hosts() {
+        let result = spawn("nc %h %p", "$(id)", "$(id)", 22, "git");
+
+        assert!(matches!(result, Err(SshError::Configuration { .. })));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::session_options::SessionOptions;
+use crate::ssh_config;
+use crate::ssh_error::SshError;
+
+const PROXY_JUMP: &str = "ProxyJump";
+
+/// Host the connection goes through before reaching the destination. A missing user is resolved by
+/// libssh (ssh_config or the local user) and a missing port is the default one.
+#[derive(uniffi::Record, Debug, Clone, Eq, PartialEq)]
+pub struct JumpHost {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+}
+
+/// Jump hosts to go through to reach `host`, in order. [SessionOptions::jump_hosts] takes
+/// precedence over the `ProxyJump` option of ssh_config.
+///
+/// Each of them is connected with [crate::Session::setup_through] the previous one, so its host
+/// key is verified and its authentication done as for any other session.
+#[uniffi::export]
+pub fn resolve_jump_hosts(
+    host: String,
+    options: SessionOptions,
+) -> Result<Vec<JumpHost>, SshError> {
+    if !options.jump_hosts.is_empty() {
+        return Ok(options.jump_hosts);
+    }
+
+    match ssh_config::find_option(&options.ssh_config, &host, PROXY_JUMP)? {
+        Some(proxy_jump) => parse_jump_hosts(&proxy_jump),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Parses a comma separated list of `[user@]host[:port]` or `ssh://[user@]host[:port]`, where
+/// IPv6 addresses are written between brackets.
+pub fn parse_jump_hosts(proxy_jump: &str) -> Result<Vec<JumpHost>, SshError> {
+    proxy_jump
+        .split(',')
+        .map(|jump_host| {
+            parse_jump_host(jump_host.trim()).ok_or_else(|| SshError::Configuration {
+                option: PROXY_JUMP.to_string(),
+                reason: format!("Invalid jump host \"{jump_host}\""),
+            })
+        })
+        .collect()
+}
+
+fn parse_jump_host(jump_host: &str) -> Option<JumpHost> {
+    let jump_host = jump_host.strip_prefix("ssh://").unwrap_or(jump_host);
+
+    let (user, address) = match jump_host.rsplit_once('@') {
+        Some((user, address)) => (Some(user.to_string()), address),
+        None => (None, jump_host),
+    };
+
+    let (host, port) = match address.strip_prefix('[') {
+        Some(bracketed_address) => {
+            let (host, remaining) = bracketed_address.split_once(']')?;
+
+            match remaining {
+                "" => (host, None),
+                remaining => (host, Some(remaining.strip_prefix(':')?)),
+            }
+        }
+        None => match address.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        },
+    };
+
+    if host.is_empty() || user.as_ref().is_some_and(|user| user.is_empty()) {
+        return None;
+    }
+
+    let port = match port {
+        Some(port) => Some(port.parse::<u16>().ok()?),
+        None => None,
+    };
+
+    Some(JumpHost {
+        host: host.to_string(),
+        user,
+        port,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump_host(host: &str, user: Option<&str>, port: Option<u16>) -> JumpHost {
+        JumpHost {
+            host: host.to_string(),
+            user: user.map(str::to_string),
+            port,
+        }
+    }
+
+    #[test]
+    fn parses_lists_of_jump_hosts() {
+        let jump_hosts = parse_jump_hosts("bastion, git@proxy.example.com:2222").unwrap();
+
+        assert_eq!(
+            jump_hosts,
+            vec![
+                jump_host("bastion", None, None),
+                jump_host("proxy.example.com", Some("git"), Some(2222)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_ssh_urls() {
+        assert_eq!(
+            parse_jump_hosts("ssh://git@bastion:2222").unwrap(),
+            vec![jump_host("bastion", Some("git"), Some(2222))]
+        );
+        assert_eq!(
+            parse_jump_hosts("ssh://bastion").unwrap(),
+            vec![jump_host("bastion", None, None)]
+        );
+    }
+
+    #[test]
+    fn parses_bracketed_ipv6_addresses() {
+        assert_eq!(
+            parse_jump_hosts("[2001:db8::1]").unwrap(),
+            vec![jump_host("2001:db8::1", None, None)]
+        );
+        assert_eq!(
+            parse_jump_hosts("ssh://git@[2001:db8::1]:2222").unwrap(),
+            vec![jump_host("2001:db8::1", Some("git"), Some(2222))]
+        );
+    }
+
+    #[test]
+    fn keeps_the_last_at_sign_as_separator() {
+        assert_eq!(
+            parse_jump_hosts("user@domain@bastion").unwrap(),
+            vec![jump_host("bastion", Some("user@domain"), None)]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_jump_hosts() {
+        for proxy_jump in [
+            "",
+            "bastion,",
+            "@bastion",
+            "bastion:",
+            "bastion:ssh",
+            "bastion:65536",
+            "[2001:db8::1",
+            "[2001:db8::1]2222",
+        ] {
+            assert!(
+                matches!(
+                    parse_jump_hosts(proxy_jump),
+                    Err(SshError::Configuration { .. })
+                ),
+                "{proxy_jump}"
+            );
+        }
+    }
+}
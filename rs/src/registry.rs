@@ -0,0 +1,514 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::Duration;
+
+use notify::{Config, Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::filesystem::WatchBackend;
+use crate::logger;
+
+type EventSender = Sender<notify::Result<Event>>;
+
+const TAG: &str = "WatchRegistry";
+
+/// Native watches are shared by every [crate::FileWatcher] of the process, so opening the same
+/// repository in several tabs (or a worktree and its main checkout) doesn't duplicate them.
+static REGISTRY: LazyLock<Mutex<WatchRegistry>> =
+    LazyLock::new(|| Mutex::new(WatchRegistry::default()));
+
+#[derive(Debug, Clone)]
+struct WatchUser {
+    subscriber_id: u64,
+    /// Path as requested by the subscriber, events are reported relative to it.
+    path: PathBuf,
+    recursive_mode: RecursiveMode,
+}
+
+#[derive(Debug)]
+struct SharedWatch {
+    backend: WatchBackend,
+    users: Vec<WatchUser>,
+}
+
+impl SharedWatch {
+    fn recursive_mode(&self) -> Option<RecursiveMode> {
+        if self.users.is_empty() {
+            None
+        } else if self
+            .users
+            .iter()
+            .any(|user| user.recursive_mode == RecursiveMode::Recursive)
+        {
+            Some(RecursiveMode::Recursive)
+        } else {
+            Some(RecursiveMode::NonRecursive)
+        }
+    }
+}
+
+/// Shared with the event handlers of the native watchers, which run on their own threads.
+#[derive(Default)]
+struct Routes {
+    subscribers: HashMap<u64, EventSender>,
+    /// Watches by canonical path.
+    watches: HashMap<PathBuf, SharedWatch>,
+}
+
+#[derive(Default)]
+struct WatchRegistry {
+    native_watcher: Option<Box<dyn Watcher + Send>>,
+    poll_watcher: Option<Box<dyn Watcher + Send>>,
+    routes: Arc<RwLock<Routes>>,
+    /// Paths watched by the backends. Paths covered by a recursive watch of the same backend
+    /// don't get their own, as some backends can't watch the same directory twice.
+    native_watches: HashMap<PathBuf, (WatchBackend, RecursiveMode)>,
+    next_subscriber_id: u64,
+}
+
+/// Registration of a [crate::FileWatcher] in the process-wide registry. Every watch added
+/// through it is released when it's dropped.
+pub struct WatchSubscriber {
+    id: u64,
+}
+
+impl WatchSubscriber {
+    /// Fails if the native watcher can't be created, so the error is reported on initialization
+    /// rather than when the first path is watched.
+    pub fn new(sender: EventSender) -> notify::Result<WatchSubscriber> {
+        let mut registry = REGISTRY.lock().unwrap();
+
+        // The poll interval is only used by the poll watcher
+        registry.ensure_watcher(WatchBackend::Native, Duration::ZERO)?;
+
+        let id = registry.next_subscriber_id;
+        registry.next_subscriber_id += 1;
+        registry
+            .routes
+            .write()
+            .unwrap()
+            .subscribers
+            .insert(id, sender);
+
+        Ok(WatchSubscriber { id })
+    }
+
+    /// The poll interval only applies if the poll watcher has not been created yet.
+    pub fn watch(
+        &self,
+        path: &Path,
+        recursive_mode: RecursiveMode,
+        backend: WatchBackend,
+        poll_interval: Duration,
+    ) -> notify::Result<()> {
+        REGISTRY.lock().unwrap().watch(
+            WatchUser {
+                subscriber_id: self.id,
+                path: path.to_path_buf(),
+                recursive_mode,
+            },
+            backend,
+            poll_interval,
+        )
+    }
+
+    pub fn unwatch(&self, path: &Path) -> notify::Result<()> {
+        REGISTRY.lock().unwrap().unwatch(self.id, path)
+    }
+}
+
+impl Drop for WatchSubscriber {
+    fn drop(&mut self) {
+        REGISTRY.lock().unwrap().remove_subscriber(self.id);
+    }
+}
+
+impl WatchRegistry {
+    fn watch(
+        &mut self,
+        user: WatchUser,
+        backend: WatchBackend,
+        poll_interval: Duration,
+    ) -> notify::Result<()> {
+        let canonical_path = canonical_path(&user.path);
+
+        self.ensure_watcher(backend, poll_interval)?;
+
+        {
+            let mut routes = self.routes.write().unwrap();
+            let shared_watch = routes
+                .watches
+                .entry(canonical_path.clone())
+                .or_insert_with(|| SharedWatch {
+                    backend,
+                    users: Vec::new(),
+                });
+
+            shared_watch.users.retain(|existing| {
+                existing.subscriber_id != user.subscriber_id || existing.path != user.path
+            });
+            shared_watch.users.push(user.clone());
+        }
+
+        let result = self.sync(&canonical_path);
+
+        if result.is_err() {
+            self.remove_users(&canonical_path, |existing| {
+                existing.subscriber_id == user.subscriber_id && existing.path == user.path
+            });
+            let _ = self.sync(&canonical_path);
+            self.release_unused_watchers();
+        }
+
+        result
+    }
+
+    fn unwatch(&mut self, subscriber_id: u64, path: &Path) -> notify::Result<()> {
+        let canonical_path = canonical_path(path);
+
+        let removed = self.remove_users(&canonical_path, |user| {
+            user.subscriber_id == subscriber_id && user.path == path
+        });
+
+        if !removed {
+            return Err(notify::Error::watch_not_found().add_path(path.to_path_buf()));
+        }
+
+        let result = self.sync(&canonical_path);
+        self.release_unused_watchers();
+
+        result
+    }
+
+    fn remove_subscriber(&mut self, subscriber_id: u64) {
+        let canonical_paths: Vec<PathBuf> = {
+            let mut routes = self.routes.write().unwrap();
+            routes.subscribers.remove(&subscriber_id);
+
+            routes
+                .watches
+                .iter_mut()
+                .filter_map(|(canonical_path, shared_watch)| {
+                    let users_count = shared_watch.users.len();
+                    shared_watch
+                        .users
+                        .retain(|user| user.subscriber_id != subscriber_id);
+
+                    (shared_watch.users.len() != users_count).then(|| canonical_path.clone())
+                })
+                .collect()
+        };
+
+        for canonical_path in canonical_paths {
+            if let Err(e) = self.sync(&canonical_path) {
+                logger::error(
+                    TAG,
+                    format!("Failed to release watch of {canonical_path:?}: {e:?}"),
+                );
+            }
+        }
+
+        self.release_unused_watchers();
+    }
+
+    /// Drops the native watcher once no file watcher is registered, and the poll watcher once
+    /// nothing is polled, which stops their threads.
+    fn release_unused_watchers(&mut self) {
+        if self.routes.read().unwrap().subscribers.is_empty() {
+            self.native_watcher = None;
+        }
+
+        let is_polling = self
+            .native_watches
+            .values()
+            .any(|(backend, _)| *backend == WatchBackend::Polling);
+
+        if !is_polling {
+            self.poll_watcher = None;
+        }
+    }
+
+    /// Returns whether any user was removed.
+    fn remove_users(&self, canonical_path: &Path, predicate: impl Fn(&WatchUser) -> bool) -> bool {
+        let mut routes = self.routes.write().unwrap();
+
+        let Some(shared_watch) = routes.watches.get_mut(canonical_path) else {
+            return false;
+        };
+
+        let users_count = shared_watch.users.len();
+        shared_watch.users.retain(|user| !predicate(user));
+
+        shared_watch.users.len() != users_count
+    }
+
+    fn ensure_watcher(
+        &mut self,
+        backend: WatchBackend,
+        poll_interval: Duration,
+    ) -> notify::Result<()> {
+        // Symlinks are followed by the file watcher itself according to its SymlinkPolicy
+        let config = Config::default().with_follow_symlinks(false);
+
+        match backend {
+            WatchBackend::Native if self.native_watcher.is_none() => {
+                let watcher = RecommendedWatcher::new(self.event_handler(), config)?;
+                self.native_watcher = Some(Box::new(watcher));
+            }
+            WatchBackend::Polling if self.poll_watcher.is_none() => {
+                let config = config.with_poll_interval(poll_interval);
+                let watcher = PollWatcher::new(self.event_handler(), config)?;
+                self.poll_watcher = Some(Box::new(watcher));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn event_handler(&self) -> impl Fn(notify::Result<Event>) + Send + 'static {
+        let routes = self.routes.clone();
+
+        move |event| dispatch(&routes.read().unwrap(), event)
+    }
+
+    fn watcher(&mut self, backend: WatchBackend) -> Option<&mut Box<dyn Watcher + Send>> {
+        match backend {
+            WatchBackend::Native => self.native_watcher.as_mut(),
+            WatchBackend::Polling => self.poll_watcher.as_mut(),
+        }
+    }
+
+    /// Makes the backends watch the canonical path as its users need, updating the paths it
+    /// covers when it starts or stops being watched recursively.
+    fn sync(&mut self, canonical_path: &Path) -> notify::Result<()> {
+        let (backend, recursive_mode) = {
+            let mut routes = self.routes.write().unwrap();
+
+            match routes.watches.get(canonical_path) {
+                Some(shared_watch) if !shared_watch.users.is_empty() => {
+                    (shared_watch.backend, shared_watch.recursive_mode())
+                }
+                Some(shared_watch) => {
+                    let backend = shared_watch.backend;
+                    routes.watches.remove(canonical_path);
+                    (backend, None)
+                }
+                None => (WatchBackend::Native, None),
+            }
+        };
+
+        let desired = recursive_mode
+            .filter(|_| !self.is_covered(canonical_path, backend))
+            .map(|recursive_mode| (backend, recursive_mode));
+
+        let current = self.native_watches.get(canonical_path).copied();
+
+        if current == desired {
+            return Ok(());
+        }
+
+        let mut uncovered_paths = Vec::new();
+
+        if let Some((current_backend, current_mode)) = current {
+            if let Some(watcher) = self.watcher(current_backend) {
+                let _ = watcher.unwatch(canonical_path);
+            }
+
+            self.native_watches.remove(canonical_path);
+
+            if current_mode == RecursiveMode::Recursive {
+                uncovered_paths = self.paths_under(canonical_path, current_backend);
+            }
+        }
+
+        if let Some((backend, recursive_mode)) = desired {
+            if recursive_mode == RecursiveMode::Recursive {
+                self.release_covered_watches(canonical_path, backend);
+            }
+
+            if let Some(watcher) = self.watcher(backend) {
+                watcher.watch(canonical_path, recursive_mode)?;
+                self.native_watches
+                    .insert(canonical_path.to_path_buf(), (backend, recursive_mode));
+            }
+        }
+
+        // Every path is synced even if one fails, the first error is returned
+        uncovered_paths
+            .iter()
+            .map(|uncovered_path| self.sync(uncovered_path))
+            .fold(Ok(()), Result::and)
+    }
+
+    fn is_covered(&self, canonical_path: &Path, backend: WatchBackend) -> bool {
+        self.native_watches
+            .iter()
+            .any(|(watched_path, (watched_backend, recursive_mode))| {
+                watched_path != canonical_path
+                    && *watched_backend == backend
+                    && *recursive_mode == RecursiveMode::Recursive
+                    && canonical_path.starts_with(watched_path)
+            })
+    }
+
+    /// Paths with users under the canonical path, excluding itself.
+    fn paths_under(&self, canonical_path: &Path, backend: WatchBackend) -> Vec<PathBuf> {
+        self.routes
+            .read()
+            .unwrap()
+            .watches
+            .iter()
+            .filter(|(path, shared_watch)| {
+                shared_watch.backend == backend
+                    && *path != canonical_path
+                    && path.starts_with(canonical_path)
+            })
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Removes the backend watches of the paths that are now covered by a recursive watch.
+    fn release_covered_watches(&mut self, canonical_path: &Path, backend: WatchBackend) {
+        let covered_paths: Vec<PathBuf> = self
+            .native_watches
+            .iter()
+            .filter(|(path, (watched_backend, _))| {
+                *watched_backend == backend
+                    && path.as_path() != canonical_path
+                    && path.starts_with(canonical_path)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for covered_path in covered_paths {
+            if let Some(watcher) = self.watcher(backend) {
+                let _ = watcher.unwatch(&covered_path);
+            }
+
+            self.native_watches.remove(&covered_path);
+        }
+    }
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Sends the event to every subscriber watching its paths, translated to the paths they used.
+fn dispatch(routes: &Routes, event: notify::Result<Event>) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            for sender in routes.subscribers.values() {
+                let error = notify::Error::generic(&e.to_string()).set_paths(e.paths.clone());
+                let _ = sender.send(Err(error));
+            }
+
+            return;
+        }
+    };
+
+    let mut paths_by_subscriber = HashMap::<u64, Vec<PathBuf>>::new();
+    let mut seen = HashSet::<(u64, PathBuf)>::new();
+
+    for path in &event.paths {
+        // Watches are looked up by the ancestors of the path, rather than going through all of
+        // them, as this runs for every event while the routes are locked
+        for canonical_path in path.ancestors() {
+            let Some(shared_watch) = routes.watches.get(canonical_path) else {
+                continue;
+            };
+
+            let Ok(relative_path) = path.strip_prefix(canonical_path) else {
+                continue;
+            };
+
+            for user in &shared_watch.users {
+                let is_covered = user.recursive_mode == RecursiveMode::Recursive
+                    || relative_path.components().count() <= 1;
+
+                if !is_covered {
+                    continue;
+                }
+
+                let user_path = if relative_path.as_os_str().is_empty() {
+                    user.path.clone()
+                } else {
+                    user.path.join(relative_path)
+                };
+
+                if seen.insert((user.subscriber_id, user_path.clone())) {
+                    paths_by_subscriber
+                        .entry(user.subscriber_id)
+                        .or_default()
+                        .push(user_path);
+                }
+            }
+        }
+    }
+
+    for (subscriber_id, paths) in paths_by_subscriber {
+        if let Some(sender) = routes.subscribers.get(&subscriber_id) {
+            let mut subscriber_event = event.clone();
+            subscriber_event.paths = paths;
+
+            let _ = sender.send(Ok(subscriber_event));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use notify::EventKind;
+
+    use super::*;
+
+    fn shared_watch(subscriber_id: u64, path: &str, recursive_mode: RecursiveMode) -> SharedWatch {
+        SharedWatch {
+            backend: WatchBackend::Native,
+            users: vec![WatchUser {
+                subscriber_id,
+                path: PathBuf::from(path),
+                recursive_mode,
+            }],
+        }
+    }
+
+    #[test]
+    fn dispatches_events_to_the_watches_containing_them() {
+        let (sender, receiver) = channel();
+        let mut routes = Routes::default();
+        routes.subscribers.insert(0, sender);
+        routes.watches.insert(
+            PathBuf::from("/repo"),
+            shared_watch(0, "/link/repo", RecursiveMode::NonRecursive),
+        );
+        routes.watches.insert(
+            PathBuf::from("/repo/.git/refs"),
+            shared_watch(0, "/link/repo/.git/refs", RecursiveMode::Recursive),
+        );
+
+        let event = Event::new(EventKind::Any)
+            .add_path(PathBuf::from("/repo/file"))
+            .add_path(PathBuf::from("/repo/.git/refs/heads/main"))
+            .add_path(PathBuf::from("/repo/src/nested"))
+            .add_path(PathBuf::from("/other/file"));
+
+        dispatch(&routes, Ok(event));
+
+        let received = receiver.try_recv().unwrap().unwrap();
+        assert_eq!(
+            received.paths,
+            [
+                PathBuf::from("/link/repo/file"),
+                PathBuf::from("/link/repo/.git/refs/heads/main"),
+            ]
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,334 @@
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use libssh_rs::{RawSocket, SshOption};
+
+use crate::DEFAULT_SSH_PORT;
+use crate::proxy_command;
+use crate::proxy_jump::JumpHost;
+use crate::ssh_config;
+use crate::ssh_error::SshError;
+
+pub const ACCEPTED_SSH_TYPES: &str = "ssh-ed25519,ecdsa-sha2-nistp256,ecdsa-sha2-nistp384,ecdsa-sha2-nistp521,ssh-rsa,rsa-sha2-512,rsa-sha2-256,ssh-dss";
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum StrictHostKeyChecking {
+    /// Only keys already in known_hosts are accepted.
+    Yes,
+    /// Unknown and changed keys are confirmed through the [crate::host_key::HostKeyVerifier].
+    #[default]
+    Ask,
+    /// Unknown keys are added to known_hosts without asking, changed keys are rejected.
+    AcceptNew,
+    /// Any key is accepted and nothing is added to known_hosts.
+    No,
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum IpFamily {
+    #[default]
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+#[derive(uniffi::Enum, Debug, Clone, Eq, PartialEq, Default)]
+pub enum SshConfigSource {
+    /// `~/.ssh/config`, as libssh reads it by default.
+    #[default]
+    Default,
+    File {
+        path: String,
+    },
+    /// Only the options given to the session are used.
+    Disabled,
+}
+
+/// Comma separated lists in the same format as ssh_config. [None] keeps the libssh (or
+/// ssh_config) defaults.
+#[derive(uniffi::Record, Debug, Clone, Default)]
+pub struct AlgorithmPreferences {
+    pub key_exchange: Option<String>,
+    pub host_keys: Option<String>,
+    pub ciphers: Option<String>,
+    pub macs: Option<String>,
+    /// Falls back to [ACCEPTED_SSH_TYPES].
+    pub public_key_accepted_types: Option<String>,
+}
+
+#[derive(uniffi::Record, Debug, Clone, Default)]
+pub struct SessionOptions {
+    /// Private keys tried by [crate::Session::public_key_auth], besides the default ones.
+    pub identity_files: Vec<String>,
+    pub connect_timeout_ms: Option<u64>,
+    /// Maximum time each authentication attempt can take.
+    pub auth_timeout_ms: Option<u64>,
+    /// Maximum time to wait for data from a channel. Without it, reads wait until the server
+    /// sends something or the connection drops.
+    pub read_timeout_ms: Option<u64>,
+    /// Interval at which keepalives are sent while waiting for data, so idle connections aren't
+    /// dropped. Unlike `ServerAliveInterval`, a server that doesn't answer isn't disconnected, as
+    /// libssh doesn't report the replies, see [SessionOptions::read_timeout_ms] for that.
+    pub keepalive_interval_ms: Option<u64>,
+    pub strict_host_key_checking: StrictHostKeyChecking,
+    /// Defaults to `~/.ssh/known_hosts`.
+    pub known_hosts_path: Option<String>,
+    pub ssh_config: SshConfigSource,
+    pub compression: bool,
+    pub ip_family: IpFamily,
+    pub algorithms: AlgorithmPreferences,
+    /// Hosts to go through before reaching the destination, see
+    /// [crate::proxy_jump::resolve_jump_hosts].
+    pub jump_hosts: Vec<JumpHost>,
+    /// Command whose stdin and stdout carry the connection, as `ProxyCommand` (`%h`, `%n`, `%p`,
+    /// `%r` and `%%` are expanded). Falls back to ssh_config, and is not used when connecting
+    /// through a jump host.
+    pub proxy_command: Option<String>,
+}
+
+#[uniffi::export]
+pub fn default_session_options() -> SessionOptions {
+    SessionOptions::default()
+}
+
+/// Applies the options once the destination is set, as ssh_config entries depend on the host.
+/// Options given explicitly are applied after ssh_config, so they take precedence. The session
+/// runs over `socket` if it's given, instead of connecting to the host.
+pub fn apply_options(
+    session: &libssh_rs::Session,
+    host: &str,
+    port: u16,
+    options: &SessionOptions,
+    socket: Option<RawSocket>,
+) -> Result<(), SshError> {
+    let set_option = |name: &str, option: SshOption| {
+        session
+            .set_option(option)
+            .map_err(|e| SshError::configuration(name, &e))
+    };
+
+    match &options.ssh_config {
+        SshConfigSource::Default => session.options_parse_config(None),
+        SshConfigSource::File { path } => session.options_parse_config(Some(path)),
+        SshConfigSource::Disabled => Ok(()),
+    }
+    .map_err(|e| SshError::configuration("Configuration file", &e))?;
+
+    for identity_file in &options.identity_files {
+        set_option(
+            "Identity file",
+            SshOption::AddIdentity(identity_file.clone()),
+        )?;
+    }
+
+    if let Some(connect_timeout_ms) = options.connect_timeout_ms {
+        set_option(
+            "Timeout",
+            SshOption::Timeout(Duration::from_millis(connect_timeout_ms)),
+        )?;
+    }
+
+    if let Some(known_hosts_path) = &options.known_hosts_path {
+        set_option(
+            "Known hosts",
+            SshOption::KnownHosts(Some(known_hosts_path.clone())),
+        )?;
+    }
+
+    if options.compression {
+        set_option("Compression", SshOption::CompressionCS(true))?;
+        set_option("Compression", SshOption::CompressionSC(true))?;
+    }
+
+    let algorithms = &options.algorithms;
+
+    if let Some(key_exchange) = &algorithms.key_exchange {
+        set_option("Key exchange", SshOption::KeyExchange(key_exchange.clone()))?;
+    }
+
+    if let Some(host_keys) = &algorithms.host_keys {
+        set_option("Host keys", SshOption::HostKeys(host_keys.clone()))?;
+    }
+
+    if let Some(ciphers) = &algorithms.ciphers {
+        set_option("Ciphers", SshOption::CiphersCS(ciphers.clone()))?;
+        set_option("Ciphers", SshOption::CiphersSC(ciphers.clone()))?;
+    }
+
+    if let Some(macs) = &algorithms.macs {
+        set_option("MACs", SshOption::HmacCS(macs.clone()))?;
+        set_option("MACs", SshOption::HmacSC(macs.clone()))?;
+    }
+
+    let public_key_accepted_types = algorithms
+        .public_key_accepted_types
+        .clone()
+        .unwrap_or(ACCEPTED_SSH_TYPES.to_string());
+
+    set_option(
+        "Public keys",
+        SshOption::PublicKeyAcceptedTypes(public_key_accepted_types),
+    )?;
+
+    // libssh only runs proxy commands on Unix and has no option for the address family, so
+    // both are connected here
+    let socket = match socket {
+        Some(socket) => Some(socket),
+        None => match proxy_command::resolve_proxy_command(host, options)? {
+            Some(proxy_command) => {
+                let user = session.get_user_name().unwrap_or_default();
+                let host_name = resolve_host_name(host, options)?;
+
+                Some(proxy_command::spawn(
+                    &proxy_command,
+                    &host_name,
+                    host,
+                    port,
+                    &user,
+                )?)
+            }
+            None if options.ip_family != IpFamily::Any => {
+                let host_name = resolve_host_name(host, options)?;
+
+                Some(into_raw_socket(connect_tcp(&host_name, port, options)?))
+            }
+            None => None,
+        },
+    };
+
+    if let Some(socket) = socket {
+        set_option("Socket", SshOption::Socket(socket))?;
+    }
+
+    Ok(())
+}
+
+/// `port` if the URL has one, else the `Port` option of ssh_config for `host` or the default one.
+pub fn resolve_port(
+    host: &str,
+    port: Option<u16>,
+    options: &SessionOptions,
+) -> Result<u16, SshError> {
+    if let Some(port) = port {
+        return Ok(port);
+    }
+
+    let configured_port = ssh_config::find_option(&options.ssh_config, host, "Port")?
+        .and_then(|port| port.parse().ok());
+
+    Ok(configured_port.unwrap_or(DEFAULT_SSH_PORT))
+}
+
+/// Actual name of `host`, which can be an alias with a `HostName` in ssh_config.
+pub fn resolve_host_name(host: &str, options: &SessionOptions) -> Result<String, SshError> {
+    let host_name = ssh_config::find_option(&options.ssh_config, host, "HostName")?;
+
+    Ok(host_name.map_or_else(|| host.to_string(), |name| name.replace("%h", host)))
+}
+
+/// libssh uses the same timeout for every blocking call, so once connected it's replaced by the
+/// authentication one.
+pub fn apply_auth_timeout(
+    session: &libssh_rs::Session,
+    options: &SessionOptions,
+) -> Result<(), SshError> {
+    if let Some(auth_timeout_ms) = options.auth_timeout_ms {
+        session
+            .set_option(SshOption::Timeout(Duration::from_millis(auth_timeout_ms)))
+            .map_err(|e| SshError::configuration("Timeout", &e))?;
+    }
+
+    Ok(())
+}
+
+fn connect_tcp(host: &str, port: u16, options: &SessionOptions) -> Result<TcpStream, SshError> {
+    let addresses = (host, port).to_socket_addrs().map_err(|e| SshError::Dns {
+        host: host.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    let addresses: Vec<SocketAddr> = addresses
+        .filter(|address| match options.ip_family {
+            IpFamily::Any => true,
+            IpFamily::Ipv4 => address.is_ipv4(),
+            IpFamily::Ipv6 => address.is_ipv6(),
+        })
+        .collect();
+
+    if addresses.is_empty() {
+        return Err(SshError::Dns {
+            host: host.to_string(),
+            reason: format!("No {:?} address found", options.ip_family),
+        });
+    }
+
+    let mut last_error = None;
+
+    for address in addresses {
+        let stream = match options.connect_timeout_ms {
+            Some(timeout_ms) => {
+                TcpStream::connect_timeout(&address, Duration::from_millis(timeout_ms))
+            }
+            None => TcpStream::connect(address),
+        };
+
+        match stream {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    let error = last_error.expect("At least one address has been tried");
+
+    Err(SshError::from_connection(host, port, error))
+}
+
+/// libssh takes ownership of the socket and closes it on disconnection.
+#[cfg(unix)]
+pub fn into_raw_socket(stream: TcpStream) -> RawSocket {
+    std::os::unix::io::IntoRawFd::into_raw_fd(stream)
+}
+
+#[cfg(windows)]
+pub fn into_raw_socket(stream: TcpStream) -> RawSocket {
+    std::os::windows::io::IntoRawSocket::into_raw_socket(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_the_port_and_host_name_from_ssh_config() {
+        let config = std::env::temp_dir().join(format!(
+            "gitnuro-session-options-config-{}",
+            std::process::id()
+        ));
+        std::fs::write(
+            &config,
+            "Host work\n    HostName %h.example.com\n    Port 2222\n",
+        )
+        .unwrap();
+
+        let options = SessionOptions {
+            ssh_config: SshConfigSource::File {
+                path: config.to_string_lossy().to_string(),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(resolve_port("work", None, &options).unwrap(), 2222);
+        assert_eq!(resolve_port("work", Some(22), &options).unwrap(), 22);
+        assert_eq!(
+            resolve_port("other", None, &options).unwrap(),
+            DEFAULT_SSH_PORT
+        );
+        assert_eq!(
+            resolve_host_name("work", &options).unwrap(),
+            "work.example.com"
+        );
+        assert_eq!(resolve_host_name("other", &options).unwrap(), "other");
+
+        std::fs::remove_file(config).unwrap();
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use notify::EventKind;
+use notify::event::{CreateKind, ModifyKind, RemoveKind};
+
+use crate::FileChangeEvent;
+
+/// If the wall clock advanced this much more than the loop expected to wait, the system was most
+/// likely suspended and events may have been lost.
+pub const SUSPEND_DETECTION_GAP_IN_MS: u128 = 5_000;
+
+/// Keeps each idle wakeup of the watch loop short while a large tree is being captured.
+const ROOTS_CAPTURED_PER_CALL: usize = 64;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EntryStat {
+    pub modified: Option<SystemTime>,
+    pub len: u64,
+    pub is_dir: bool,
+}
+
+impl From<&Metadata> for EntryStat {
+    fn from(metadata: &Metadata) -> Self {
+        EntryStat {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            is_dir: metadata.is_dir(),
+        }
+    }
+}
+
+/// Stat of the path itself. Symlinks are not followed, as git tracks the links and not their
+/// targets, and every snapshot and journal comparison goes through here so they can't disagree.
+pub fn stat(path: &Path) -> Option<EntryStat> {
+    fs::symlink_metadata(path)
+        .ok()
+        .map(|metadata| EntryStat::from(&metadata))
+}
+
+#[derive(Debug)]
+struct RootSnapshot {
+    is_recursive: bool,
+    /// [None] until the root is captured, see [WatchSnapshot::capture_next].
+    entries: Option<HashMap<PathBuf, EntryStat>>,
+}
+
+/// Lightweight stat snapshot of the watched roots, used to find out what changed while events
+/// could not be received (system suspended or watcher restarted).
+///
+/// Adding a root doesn't read it, so watching a tree stays cheap. Roots are captured a few at a
+/// time by [WatchSnapshot::capture_next] while the watch loop is idle.
+#[derive(Default)]
+pub struct WatchSnapshot {
+    roots: HashMap<PathBuf, RootSnapshot>,
+    full_rescan_requested: bool,
+    pending: Vec<FileChangeEvent>,
+}
+
+impl WatchSnapshot {
+    /// Adds the root, to be captured later. If the root was already captured (for example after
+    /// the watcher has been restarted), it's captured again right away and the differences with
+    /// the previous snapshot are queued as pending changes.
+    pub fn add_root(&mut self, root: PathBuf, is_recursive: bool) {
+        let entries = match self.roots.get(&root).and_then(|root| root.entries.as_ref()) {
+            Some(previous_entries) => {
+                let entries = capture(&root, is_recursive);
+                self.pending.append(&mut diff(previous_entries, &entries));
+                Some(entries)
+            }
+            None => None,
+        };
+
+        self.roots.insert(
+            root,
+            RootSnapshot {
+                is_recursive,
+                entries,
+            },
+        );
+    }
+
+    /// Captures up to [ROOTS_CAPTURED_PER_CALL] roots that haven't been captured yet.
+    pub fn capture_next(&mut self) {
+        let uncaptured_roots = self
+            .roots
+            .iter_mut()
+            .filter(|(_, root)| root.entries.is_none())
+            .take(ROOTS_CAPTURED_PER_CALL);
+
+        for (root_path, root) in uncaptured_roots {
+            root.entries = Some(capture(root_path, root.is_recursive));
+        }
+    }
+
+    pub fn remove_root(&mut self, root: &Path) {
+        self.roots.remove(root);
+    }
+
+    /// Every root will be compared against the disk the next time pending changes are taken.
+    pub fn request_full_rescan(&mut self) {
+        self.full_rescan_requested = true;
+    }
+
+    /// Keeps the snapshot up to date with the changes reported by the watcher. Roots not captured
+    /// yet will read the changes from disk.
+    ///
+    /// Returns the paths of the events that are directories, according to the stat taken for the
+    /// update, or to the previous one for removed paths, so callers don't have to stat them again.
+    pub fn update(&mut self, events: &[FileChangeEvent]) -> HashSet<PathBuf> {
+        let mut directories = HashSet::new();
+
+        for event in events {
+            let path = Path::new(event.path.as_str());
+            let current_stat = stat(path);
+
+            if current_stat.is_some_and(|stat| stat.is_dir) {
+                directories.insert(path.to_path_buf());
+            }
+
+            let Some(entries) = self
+                .root_containing(path)
+                .and_then(|root| root.entries.as_mut())
+            else {
+                continue;
+            };
+
+            match current_stat {
+                Some(stat) => {
+                    entries.insert(path.to_path_buf(), stat);
+                }
+                None => {
+                    if entries.remove(path).is_some_and(|stat| stat.is_dir) {
+                        entries.retain(|entry, _| !entry.starts_with(path));
+                        directories.insert(path.to_path_buf());
+                    }
+                }
+            }
+        }
+
+        directories
+    }
+
+    /// Changes found by comparing the snapshot against the disk that have not been reported yet.
+    /// Roots that were not captured have nothing to compare with, so they are reported as
+    /// changed themselves.
+    pub fn take_pending(&mut self) -> Vec<FileChangeEvent> {
+        if self.full_rescan_requested {
+            self.full_rescan_requested = false;
+
+            for (root_path, root) in self.roots.iter_mut() {
+                let Some(previous_entries) = root.entries.as_ref() else {
+                    self.pending.push(to_change_event(
+                        root_path,
+                        EventKind::Modify(ModifyKind::Any),
+                    ));
+                    continue;
+                };
+
+                let entries = capture(root_path, root.is_recursive);
+                self.pending.append(&mut diff(previous_entries, &entries));
+                root.entries = Some(entries);
+            }
+        }
+
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Stats of every root inside the directory and their entries. Roots not captured yet are
+    /// captured first.
+    pub fn entries_under(&mut self, directory: &Path) -> HashMap<PathBuf, EntryStat> {
+        let mut entries = HashMap::new();
+
+        for (root_path, root) in self.roots.iter_mut() {
+            if !root_path.starts_with(directory) {
+                continue;
+            }
+
+            if let Some(stat) = stat(root_path) {
+                entries.insert(root_path.clone(), stat);
+            }
+
+            let root_entries = root
+                .entries
+                .get_or_insert_with(|| capture(root_path, root.is_recursive));
+
+            entries.extend(
+                root_entries
+                    .iter()
+                    .map(|(path, stat)| (path.clone(), *stat)),
+            );
+        }
+
+        entries
+    }
+
+    fn root_containing(&mut self, path: &Path) -> Option<&mut RootSnapshot> {
+        let parent = path.parent()?;
+
+        if self.roots.contains_key(parent) {
+            return self.roots.get_mut(parent);
+        }
+
+        self.roots
+            .iter_mut()
+            .find(|(root_path, root)| root.is_recursive && path.starts_with(root_path))
+            .map(|(_, root)| root)
+    }
+}
+
+fn capture(root: &Path, is_recursive: bool) -> HashMap<PathBuf, EntryStat> {
+    let mut entries = HashMap::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(directory) = pending.pop() {
+        let Ok(dir_entries) = fs::read_dir(&directory) else {
+            continue;
+        };
+
+        for entry in dir_entries.flatten() {
+            let path = entry.path();
+
+            let Some(stat) = stat(&path) else {
+                continue;
+            };
+
+            if is_recursive && stat.is_dir {
+                pending.push(path.clone());
+            }
+
+            entries.insert(path, stat);
+        }
+    }
+
+    entries
+}
+
+fn diff(
+    previous: &HashMap<PathBuf, EntryStat>,
+    current: &HashMap<PathBuf, EntryStat>,
+) -> Vec<FileChangeEvent> {
+    let mut changes = Vec::new();
+
+    for (path, stat) in current {
+        let event_kind = match previous.get(path) {
+            None if stat.is_dir => EventKind::Create(CreateKind::Folder),
+            None => EventKind::Create(CreateKind::File),
+            // Directory mtimes change when their content changes, which is already reported
+            // through the entries themselves.
+            Some(previous_stat) if !stat.is_dir && previous_stat != stat => {
+                EventKind::Modify(ModifyKind::Any)
+            }
+            Some(_) => continue,
+        };
+
+        changes.push(to_change_event(path, event_kind));
+    }
+
+    for (path, stat) in previous {
+        if !current.contains_key(path) {
+            let event_kind = if stat.is_dir {
+                EventKind::Remove(RemoveKind::Folder)
+            } else {
+                EventKind::Remove(RemoveKind::File)
+            };
+
+            changes.push(to_change_event(path, event_kind));
+        }
+    }
+
+    changes
+}
+
+fn to_change_event(path: &Path, event_kind: EventKind) -> FileChangeEvent {
+    FileChangeEvent {
+        path: path.to_string_lossy().into_owned(),
+        event_kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("gitnuro-snapshot-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn paths_of(changes: &[FileChangeEvent]) -> Vec<&str> {
+        changes.iter().map(|change| change.path.as_str()).collect()
+    }
+
+    #[test]
+    fn adding_a_root_does_not_read_it() {
+        let root = temp_directory("lazy");
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+
+        let mut snapshot = WatchSnapshot::default();
+        snapshot.add_root(root.clone(), false);
+        assert!(snapshot.roots[&root].entries.is_none());
+
+        // Without a capture, a rescan can only tell the root may have changed
+        snapshot.request_full_rescan();
+        let changes = snapshot.take_pending();
+        assert_eq!(paths_of(&changes), [root.to_string_lossy()]);
+
+        snapshot.capture_next();
+        assert!(snapshot.roots[&root].entries.is_some());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn rescans_report_what_changed_since_the_capture() {
+        let root = temp_directory("rescan");
+        fs::write(root.join("kept.rs"), "").unwrap();
+        fs::write(root.join("removed.rs"), "").unwrap();
+
+        let mut snapshot = WatchSnapshot::default();
+        snapshot.add_root(root.clone(), false);
+        snapshot.capture_next();
+
+        fs::remove_file(root.join("removed.rs")).unwrap();
+        fs::write(root.join("added.rs"), "").unwrap();
+
+        snapshot.request_full_rescan();
+        let changes = snapshot.take_pending();
+        let mut paths = paths_of(&changes);
+        paths.sort();
+
+        let expected = [root.join("added.rs"), root.join("removed.rs")];
+        assert_eq!(
+            paths,
+            expected.map(|path| path.to_string_lossy().into_owned())
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn updates_report_which_paths_are_directories() {
+        let root = temp_directory("directories");
+        fs::create_dir_all(root.join("removed")).unwrap();
+
+        let mut snapshot = WatchSnapshot::default();
+        snapshot.add_root(root.clone(), false);
+        snapshot.capture_next();
+
+        fs::remove_dir(root.join("removed")).unwrap();
+        fs::create_dir_all(root.join("created")).unwrap();
+        fs::write(root.join("file"), "content").unwrap();
+
+        let directories = snapshot.update(&[
+            to_change_event(&root.join("removed"), EventKind::Remove(RemoveKind::Any)),
+            to_change_event(&root.join("created"), EventKind::Create(CreateKind::Any)),
+            to_change_event(&root.join("file"), EventKind::Create(CreateKind::Any)),
+        ]);
+
+        assert_eq!(
+            directories,
+            HashSet::from([root.join("removed"), root.join("created")])
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_stated_the_same_way_by_captures_and_updates() {
+        let root = temp_directory("symlinks");
+        let target = temp_directory("symlinks-target");
+        fs::write(target.join("large"), vec![0; 4096]).unwrap();
+        std::os::unix::fs::symlink(target.join("large"), root.join("link")).unwrap();
+
+        let mut snapshot = WatchSnapshot::default();
+        snapshot.add_root(root.clone(), false);
+        snapshot.capture_next();
+
+        snapshot.update(&[to_change_event(
+            &root.join("link"),
+            EventKind::Create(CreateKind::File),
+        )]);
+
+        snapshot.request_full_rescan();
+        assert!(snapshot.take_pending().is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+        fs::remove_dir_all(target).unwrap();
+    }
+}
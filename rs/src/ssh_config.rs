@@ -0,0 +1,210 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::known_hosts;
+use crate::session_options::SshConfigSource;
+use crate::ssh_error::SshError;
+
+pub fn ssh_directory() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .unwrap_or_default();
+
+    PathBuf::from(home).join(".ssh")
+}
+
+/// Value of `keyword` for `host`, for the options libssh doesn't handle (or doesn't expose) itself.
+/// As in OpenSSH, the first value found wins and `none` means the option is not set.
+///
+/// Only `Host` blocks are supported, `Match` blocks and `Include` directives are skipped.
+pub fn find_option(
+    source: &SshConfigSource,
+    host: &str,
+    keyword: &str,
+) -> Result<Option<String>, SshError> {
+    let value = find_values(source, host, keyword)?.into_iter().next();
+
+    Ok(value.filter(|value| !value.eq_ignore_ascii_case("none")))
+}
+
+/// Every value of `keyword` for `host`, in order, for the options that can be given several times
+/// such as `IdentityFile`. Blocks are matched as in [find_option].
+pub fn find_values(
+    source: &SshConfigSource,
+    host: &str,
+    keyword: &str,
+) -> Result<Vec<String>, SshError> {
+    let path = match source {
+        SshConfigSource::Default => ssh_directory().join("config"),
+        SshConfigSource::File { path } => PathBuf::from(path),
+        SshConfigSource::Disabled => return Ok(Vec::new()),
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let host = host.to_lowercase();
+
+    // Options before the first block apply to every host
+    let mut is_matching_block = true;
+    let mut values = Vec::new();
+
+    for line in content.lines() {
+        let Some((line_keyword, value)) = parse_line(line) else {
+            continue;
+        };
+
+        if line_keyword.eq_ignore_ascii_case("Host") {
+            let patterns: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+            is_matching_block = known_hosts::matches_host(&patterns, &host);
+        } else if line_keyword.eq_ignore_ascii_case("Match") {
+            is_matching_block = false;
+        } else if is_matching_block && line_keyword.eq_ignore_ascii_case(keyword) {
+            values.push(value);
+        }
+    }
+
+    Ok(values)
+}
+
+/// Splits a `Keyword value` (or `Keyword=value`) line. Returns [None] for comments and empty
+/// lines.
+fn parse_line(line: &str) -> Option<(&str, String)> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (keyword, value) = line.split_once(|c: char| c.is_whitespace() || c == '=')?;
+    let value = value.trim_start_matches(|c: char| c.is_whitespace() || c == '=');
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+
+    Some((keyword, value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# Options for every host
+Compression yes
+
+Host github.com gitlab.com
+    ProxyJump bastion
+    User git
+
+Host *.internal !legacy.internal
+    ProxyCommand=\"nc -X 5 -x proxy:1080 %h %p\"
+
+Match host github.com
+    ProxyJump other
+
+Host *
+    ProxyJump fallback
+    ProxyCommand none
+";
+
+    fn config_with(name: &str, content: &str) -> SshConfigSource {
+        let path =
+            std::env::temp_dir().join(format!("gitnuro-ssh-config-{name}-{}", std::process::id()));
+        fs::write(&path, content).unwrap();
+
+        SshConfigSource::File {
+            path: path.to_string_lossy().to_string(),
+        }
+    }
+
+    fn find(source: &SshConfigSource, host: &str, keyword: &str) -> Option<String> {
+        find_option(source, host, keyword).unwrap()
+    }
+
+    #[test]
+    fn parses_lines() {
+        assert_eq!(parse_line("  User git"), Some(("User", "git".to_string())));
+        assert_eq!(parse_line("User=git"), Some(("User", "git".to_string())));
+        assert_eq!(parse_line("User = git"), Some(("User", "git".to_string())));
+        assert_eq!(
+            parse_line("ProxyCommand \"nc %h %p\""),
+            Some(("ProxyCommand", "nc %h %p".to_string()))
+        );
+        assert_eq!(parse_line("# User git"), None);
+        assert_eq!(parse_line("   "), None);
+        assert_eq!(parse_line("Compression"), None);
+    }
+
+    #[test]
+    fn finds_options_of_matching_host_blocks() {
+        let source = config_with("matching", CONFIG);
+
+        assert_eq!(
+            find(&source, "github.com", "ProxyJump").as_deref(),
+            Some("bastion")
+        );
+        assert_eq!(
+            find(&source, "GitLab.com", "proxyjump").as_deref(),
+            Some("bastion")
+        );
+        assert_eq!(
+            find(&source, "example.com", "ProxyJump").as_deref(),
+            Some("fallback")
+        );
+        assert_eq!(
+            find(&source, "example.com", "Compression").as_deref(),
+            Some("yes")
+        );
+        assert_eq!(
+            find(&source, "git.internal", "ProxyCommand").as_deref(),
+            Some("nc -X 5 -x proxy:1080 %h %p")
+        );
+    }
+
+    #[test]
+    fn skips_negated_hosts_and_match_blocks() {
+        let source = config_with("negated", CONFIG);
+
+        // Negated by the pattern of its block, then disabled by `none`
+        assert_eq!(find(&source, "legacy.internal", "ProxyCommand"), None);
+
+        // The first value wins, Match blocks are never applied
+        assert_eq!(
+            find(&source, "github.com", "ProxyJump").as_deref(),
+            Some("bastion")
+        );
+        assert_eq!(find(&source, "github.com", "IdentityFile"), None);
+    }
+
+    #[test]
+    fn finds_every_value_of_repeated_options() {
+        let source = config_with(
+            "repeated",
+            "IdentityFile ~/.ssh/id_work\nHost github.com\n    IdentityFile ~/.ssh/id_github\nHost gitlab.com\n    IdentityFile ~/.ssh/id_gitlab\n",
+        );
+
+        assert_eq!(
+            find_values(&source, "github.com", "IdentityFile").unwrap(),
+            vec!["~/.ssh/id_work", "~/.ssh/id_github"]
+        );
+    }
+
+    #[test]
+    fn ignores_missing_or_disabled_configs() {
+        let missing = SshConfigSource::File {
+            path: "/nonexistent/gitnuro/ssh_config".to_string(),
+        };
+
+        assert_eq!(find(&missing, "github.com", "ProxyJump"), None);
+        assert_eq!(
+            find(&SshConfigSource::Disabled, "github.com", "ProxyJump"),
+            None
+        );
+    }
+}
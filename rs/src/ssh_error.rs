@@ -0,0 +1,210 @@
+use crate::host_key::HostKeyStatus;
+
+/// Beginning of the libssh messages for connection failures (see `ssh_connect` and
+/// `ssh_connect_host_nonblocking`).
+const LIBSSH_RESOLVE_ERROR: &str = "Failed to resolve hostname";
+const LIBSSH_TIMEOUT_ERROR: &str = "Timeout connecting to";
+const LIBSSH_CONNECT_ERROR: &str = "Failed to connect:";
+/// strerror(ECONNREFUSED), appended to the connect error.
+const REFUSED_ERROR: &str = "Connection refused";
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeoutKind {
+    Connect,
+    Authentication,
+    Read,
+}
+
+/// Fields are not named `message`, as it would clash with the message of the generated
+/// exceptions.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum SshError {
+    #[error("Could not resolve host {host}: {reason}")]
+    Dns { host: String, reason: String },
+    #[error("Connection to {host}:{port} was refused")]
+    ConnectionRefused { host: String, port: u16 },
+    #[error("Connection to {host}:{port} timed out ({kind:?})")]
+    Timeout {
+        host: String,
+        port: u16,
+        kind: TimeoutKind,
+    },
+    #[error("The host key of {host}:{port} ({fingerprint}) is not trusted")]
+    UntrustedHostKey {
+        host: String,
+        port: u16,
+        fingerprint: String,
+    },
+    #[error("The host key of {host}:{port} ({fingerprint}) does not match the known one")]
+    HostKeyMismatch {
+        host: String,
+        port: u16,
+        fingerprint: String,
+        status: HostKeyStatus,
+    },
+    #[error("Authentication failed: {reason}")]
+    AuthFailure { reason: String },
+    #[error("SSH channel {operation} failed: {reason}")]
+    ChannelFailure { operation: String, reason: String },
+    #[error("SSH {option} option failed: {reason}")]
+    Configuration { option: String, reason: String },
+    #[error("I/O error: {reason}")]
+    Io { reason: String },
+}
+
+impl SshError {
+    /// Classifies the error libssh returns when connecting to `host` fails. libssh only reports
+    /// fatal errors for connections, so its messages for each case are matched.
+    pub fn from_libssh_connection(host: &str, port: u16, error: &libssh_rs::Error) -> SshError {
+        let libssh_rs::Error::Fatal(message) = error else {
+            return SshError::Io {
+                reason: libssh_error_to_message(error),
+            };
+        };
+
+        if message.starts_with(LIBSSH_RESOLVE_ERROR) {
+            SshError::Dns {
+                host: host.to_string(),
+                reason: message.clone(),
+            }
+        } else if message.starts_with(LIBSSH_TIMEOUT_ERROR) {
+            SshError::Timeout {
+                host: host.to_string(),
+                port,
+                kind: TimeoutKind::Connect,
+            }
+        } else if message.starts_with(LIBSSH_CONNECT_ERROR) && message.ends_with(REFUSED_ERROR) {
+            SshError::ConnectionRefused {
+                host: host.to_string(),
+                port,
+            }
+        } else {
+            SshError::Io {
+                reason: message.clone(),
+            }
+        }
+    }
+
+    /// Classifies the error of a TCP connection to `host` opened here instead of by libssh.
+    pub fn from_connection(host: &str, port: u16, error: std::io::Error) -> SshError {
+        match error.kind() {
+            std::io::ErrorKind::ConnectionRefused => SshError::ConnectionRefused {
+                host: host.to_string(),
+                port,
+            },
+            std::io::ErrorKind::TimedOut => SshError::Timeout {
+                host: host.to_string(),
+                port,
+                kind: TimeoutKind::Connect,
+            },
+            _ => error.into(),
+        }
+    }
+
+    pub fn channel(operation: &str, error: &libssh_rs::Error) -> SshError {
+        SshError::ChannelFailure {
+            operation: operation.to_string(),
+            reason: libssh_error_to_message(error),
+        }
+    }
+
+    pub fn configuration(option: &str, error: &libssh_rs::Error) -> SshError {
+        SshError::Configuration {
+            option: option.to_string(),
+            reason: libssh_error_to_message(error),
+        }
+    }
+}
+
+impl From<libssh_rs::Error> for SshError {
+    fn from(error: libssh_rs::Error) -> Self {
+        SshError::Io {
+            reason: libssh_error_to_message(&error),
+        }
+    }
+}
+
+impl From<std::io::Error> for SshError {
+    fn from(error: std::io::Error) -> Self {
+        SshError::Io {
+            reason: error.to_string(),
+        }
+    }
+}
+
+/// A poisoned lock means a previous call panicked while using the session or channel.
+impl<T> From<std::sync::PoisonError<T>> for SshError {
+    fn from(error: std::sync::PoisonError<T>) -> Self {
+        SshError::Io {
+            reason: format!("The SSH connection is in an invalid state: {error}"),
+        }
+    }
+}
+
+pub fn libssh_error_to_message(err: &libssh_rs::Error) -> String {
+    match err {
+        libssh_rs::Error::RequestDenied(message) => message.clone(),
+        libssh_rs::Error::Fatal(message) => message.clone(),
+        libssh_rs::Error::TryAgain => "Something went wrong, please try again".to_string(),
+        libssh_rs::Error::Sftp(_) => "Sftp not supported".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_libssh_connection_errors() {
+        let error = |message: &str| libssh_rs::Error::Fatal(message.to_string());
+
+        assert!(matches!(
+            SshError::from_libssh_connection(
+                "work",
+                22,
+                &error("Failed to resolve hostname work (Name or service not known)")
+            ),
+            SshError::Dns { .. }
+        ));
+        assert!(matches!(
+            SshError::from_libssh_connection("work", 22, &error("Timeout connecting to work")),
+            SshError::Timeout {
+                kind: TimeoutKind::Connect,
+                ..
+            }
+        ));
+        assert!(matches!(
+            SshError::from_libssh_connection(
+                "work",
+                2222,
+                &error("Failed to connect: Connection refused")
+            ),
+            SshError::ConnectionRefused { port: 2222, .. }
+        ));
+        assert!(matches!(
+            SshError::from_libssh_connection("work", 22, &error("Protocol mismatch")),
+            SshError::Io { .. }
+        ));
+    }
+
+    #[test]
+    fn classifies_connection_errors_by_kind() {
+        let error = |kind| std::io::Error::new(kind, "failed");
+
+        assert!(matches!(
+            SshError::from_connection("host", 22, error(std::io::ErrorKind::ConnectionRefused)),
+            SshError::ConnectionRefused { port: 22, .. }
+        ));
+        assert!(matches!(
+            SshError::from_connection("host", 22, error(std::io::ErrorKind::TimedOut)),
+            SshError::Timeout {
+                kind: TimeoutKind::Connect,
+                ..
+            }
+        ));
+        assert!(matches!(
+            SshError::from_connection("host", 22, error(std::io::ErrorKind::Other)),
+            SshError::Io { .. }
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+
+use crate::FileChanged;
+
+/// Time without new events after which the changes of a path subscription are delivered.
+/// Much shorter than the repository-wide batch so focused views (diff, blame) reload
+/// almost as soon as the file is saved.
+pub const SUBSCRIPTION_DEBOUNCE_IN_MS: u128 = 50;
+
+pub type Subscriptions = Arc<RwLock<HashMap<u64, PathSubscription>>>;
+
+#[uniffi::export(callback_interface)]
+pub trait PathChangeNotifier: Send + Sync + Debug {
+    fn detected_change(&self, paths: Vec<FileChanged>);
+}
+
+pub struct PathSubscription {
+    path: PathBuf,
+    notifier: Arc<dyn PathChangeNotifier>,
+    pending: Vec<FileChanged>,
+    last_event: u128,
+}
+
+impl PathSubscription {
+    pub fn new(path: PathBuf, notifier: Box<dyn PathChangeNotifier>) -> PathSubscription {
+        PathSubscription {
+            path,
+            notifier: Arc::from(notifier),
+            pending: Vec::new(),
+            last_event: 0,
+        }
+    }
+
+    /// A subscription matches the subscribed path itself and, when it is a directory,
+    /// everything below it.
+    fn matches(&self, path: &Path) -> bool {
+        path.starts_with(&self.path)
+    }
+}
+
+#[derive(uniffi::Object)]
+pub struct SubscriptionHandle {
+    id: u64,
+    subscriptions: Weak<RwLock<HashMap<u64, PathSubscription>>>,
+}
+
+impl SubscriptionHandle {
+    pub fn new(id: u64, subscriptions: &Subscriptions) -> SubscriptionHandle {
+        SubscriptionHandle {
+            id,
+            subscriptions: Arc::downgrade(subscriptions),
+        }
+    }
+}
+
+#[uniffi::export]
+impl SubscriptionHandle {
+    fn unsubscribe(&self) {
+        if let Some(subscriptions) = self.subscriptions.upgrade() {
+            subscriptions.write().unwrap().remove(&self.id);
+        }
+    }
+}
+
+impl Drop for SubscriptionHandle {
+    fn drop(&mut self) {
+        self.unsubscribe();
+    }
+}
+
+/// Queues the change in every subscription interested in it.
+pub fn dispatch_to_subscriptions(
+    subscriptions: &Subscriptions,
+    file_changed: &FileChanged,
+    current_time: u128,
+) {
+    let mut subscriptions = subscriptions.write().unwrap();
+
+    if subscriptions.is_empty() {
+        return;
+    }
+
+    let path = Path::new(file_changed.path.as_str());
+
+    for subscription in subscriptions.values_mut() {
+        if subscription.matches(path) {
+            if !subscription.pending.contains(file_changed) {
+                subscription.pending.push(file_changed.clone());
+            }
+            subscription.last_event = current_time;
+        }
+    }
+}
+
+/// Delivers the changes of every subscription that has been quiet for at least
+/// [SUBSCRIPTION_DEBOUNCE_IN_MS]. Notifiers are invoked once the lock has been released so
+/// they can safely unsubscribe from within the callback.
+pub fn flush_subscriptions(subscriptions: &Subscriptions, current_time: u128) {
+    let ready: Vec<(Arc<dyn PathChangeNotifier>, Vec<FileChanged>)> = {
+        let mut subscriptions = subscriptions.write().unwrap();
+
+        subscriptions
+            .values_mut()
+            .filter(|subscription| {
+                !subscription.pending.is_empty()
+                    && current_time.saturating_sub(subscription.last_event)
+                        >= SUBSCRIPTION_DEBOUNCE_IN_MS
+            })
+            .map(|subscription| {
+                let paths = std::mem::take(&mut subscription.pending);
+                (subscription.notifier.clone(), paths)
+            })
+            .collect()
+    };
+
+    for (notifier, paths) in ready {
+        notifier.detected_change(paths);
+    }
+}
+
+/// Time left until the next subscription with pending changes has to be flushed, if any.
+pub fn next_subscription_deadline(
+    subscriptions: &Subscriptions,
+    current_time: u128,
+) -> Option<u128> {
+    let subscriptions = subscriptions.read().unwrap();
+
+    subscriptions
+        .values()
+        .filter(|subscription| !subscription.pending.is_empty())
+        .map(|subscription| {
+            (subscription.last_event + SUBSCRIPTION_DEBOUNCE_IN_MS).saturating_sub(current_time)
+        })
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileType;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct RecordingNotifier {
+        received: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PathChangeNotifier for RecordingNotifier {
+        fn detected_change(&self, paths: Vec<FileChanged>) {
+            let mut received = self.received.lock().unwrap();
+            received.extend(paths.into_iter().map(|file_changed| file_changed.path));
+        }
+    }
+
+    fn file_changed(path: &str) -> FileChanged {
+        FileChanged {
+            path: path.to_string(),
+            file_type: FileType::File,
+        }
+    }
+
+    fn subscribe(subscriptions: &Subscriptions, id: u64, path: &str) -> Arc<Mutex<Vec<String>>> {
+        let notifier = RecordingNotifier::default();
+        let received = notifier.received.clone();
+
+        subscriptions.write().unwrap().insert(
+            id,
+            PathSubscription::new(PathBuf::from(path), Box::new(notifier)),
+        );
+
+        received
+    }
+
+    #[test]
+    fn delivers_changes_below_the_subscribed_path() {
+        let subscriptions = Subscriptions::default();
+        let received = subscribe(&subscriptions, 0, "/repo/src");
+
+        for path in [
+            "/repo/src",
+            "/repo/src/main.rs",
+            "/repo/src_old/main.rs",
+            "/repo/README.md",
+        ] {
+            dispatch_to_subscriptions(&subscriptions, &file_changed(path), 100);
+        }
+        dispatch_to_subscriptions(&subscriptions, &file_changed("/repo/src/main.rs"), 100);
+
+        assert_eq!(next_subscription_deadline(&subscriptions, 120), Some(30));
+
+        flush_subscriptions(&subscriptions, 100 + SUBSCRIPTION_DEBOUNCE_IN_MS - 1);
+        assert!(received.lock().unwrap().is_empty());
+
+        flush_subscriptions(&subscriptions, 100 + SUBSCRIPTION_DEBOUNCE_IN_MS);
+        assert_eq!(
+            *received.lock().unwrap(),
+            ["/repo/src", "/repo/src/main.rs"]
+        );
+        assert_eq!(next_subscription_deadline(&subscriptions, 200), None);
+    }
+
+    #[test]
+    fn stops_delivering_once_unsubscribed() {
+        let subscriptions = Subscriptions::default();
+        let unsubscribed = subscribe(&subscriptions, 0, "/repo/src");
+        let kept = subscribe(&subscriptions, 1, "/repo");
+
+        SubscriptionHandle::new(0, &subscriptions).unsubscribe();
+
+        dispatch_to_subscriptions(&subscriptions, &file_changed("/repo/src/main.rs"), 0);
+        flush_subscriptions(&subscriptions, SUBSCRIPTION_DEBOUNCE_IN_MS);
+
+        assert!(unsubscribed.lock().unwrap().is_empty());
+        assert_eq!(*kept.lock().unwrap(), ["/repo/src/main.rs"]);
+
+        drop(SubscriptionHandle::new(1, &subscriptions));
+
+        assert!(subscriptions.read().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::FileChangeEvent;
+use crate::watched_roots::WatchedRoots;
+
+#[derive(uniffi::Enum, Debug, Clone, Eq, PartialEq)]
+pub enum SymlinkPolicy {
+    /// Symlinked directories are watched and their changes are reported under the link path.
+    Follow,
+    /// Only changes to the links themselves are reported, as git does. This is the default.
+    ReportLinkOnly,
+    /// Same as [SymlinkPolicy::Follow], but only for links whose target is inside
+    /// `repository_path`.
+    FollowInsideRepository { repository_path: String },
+}
+
+#[derive(Debug)]
+struct FollowedLink {
+    /// Canonical path of the linked directory.
+    target: PathBuf,
+    /// Targets outside the watched roots have their own watch, and their events are only
+    /// reported under the link path.
+    outside_roots: bool,
+    is_recursive: bool,
+}
+
+/// Directory symlinks found under the watched roots that are followed according to the
+/// [SymlinkPolicy]. Targets are watched through their canonical path, as watching the same
+/// directory twice isn't supported by every backend, and events are mapped back to the links.
+#[derive(Debug)]
+pub struct SymlinkTracking {
+    policy: SymlinkPolicy,
+    links: HashMap<PathBuf, FollowedLink>,
+}
+
+impl Default for SymlinkTracking {
+    fn default() -> Self {
+        SymlinkTracking::new(SymlinkPolicy::ReportLinkOnly)
+    }
+}
+
+impl SymlinkTracking {
+    pub fn new(policy: SymlinkPolicy) -> SymlinkTracking {
+        SymlinkTracking {
+            policy,
+            links: HashMap::new(),
+        }
+    }
+
+    /// Finds the directory symlinks in `directory` (and its subdirectories if `is_recursive`)
+    /// to follow. Returns the targets that have to be watched, as they are not covered by
+    /// `roots`.
+    pub fn discover(
+        &mut self,
+        directory: &Path,
+        is_recursive: bool,
+        roots: &WatchedRoots,
+    ) -> Vec<PathBuf> {
+        if self.policy == SymlinkPolicy::ReportLinkOnly {
+            return Vec::new();
+        }
+
+        let mut visited: HashSet<PathBuf> = self
+            .links
+            .values()
+            .map(|link| link.target.clone())
+            .collect();
+
+        if let Ok(canonical_directory) = fs::canonicalize(directory) {
+            visited.insert(canonical_directory);
+        }
+
+        let mut targets_to_watch = Vec::new();
+        let mut pending = vec![directory.to_path_buf()];
+
+        while let Some(directory) = pending.pop() {
+            let Ok(entries) = fs::read_dir(&directory) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+
+                let path = entry.path();
+
+                if file_type.is_symlink() {
+                    if let Some(target) = self.follow_link(&path, is_recursive, roots, &mut visited)
+                    {
+                        targets_to_watch.push(target.clone());
+
+                        if is_recursive {
+                            pending.push(target);
+                        }
+                    }
+                } else if is_recursive && file_type.is_dir() {
+                    pending.push(path);
+                }
+            }
+        }
+
+        targets_to_watch
+    }
+
+    /// Same as [SymlinkTracking::discover] for a single link, such as one that has just been
+    /// created.
+    pub fn discover_link(
+        &mut self,
+        link: &Path,
+        is_recursive: bool,
+        roots: &WatchedRoots,
+    ) -> Vec<PathBuf> {
+        if self.policy == SymlinkPolicy::ReportLinkOnly {
+            return Vec::new();
+        }
+
+        let mut visited = self
+            .links
+            .values()
+            .map(|link| link.target.clone())
+            .collect();
+
+        let Some(target) = self.follow_link(link, is_recursive, roots, &mut visited) else {
+            return Vec::new();
+        };
+
+        let mut targets_to_watch = vec![target.clone()];
+
+        if is_recursive {
+            targets_to_watch.extend(self.discover(&target, is_recursive, roots));
+        }
+
+        targets_to_watch
+    }
+
+    /// Registers the link if it should be followed. Returns its target if it needs its own watch.
+    fn follow_link(
+        &mut self,
+        link: &Path,
+        is_recursive: bool,
+        roots: &WatchedRoots,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Option<PathBuf> {
+        if self.links.contains_key(link) {
+            return None;
+        }
+
+        let target = fs::canonicalize(link)
+            .ok()
+            .filter(|target| target.is_dir())?;
+
+        if let SymlinkPolicy::FollowInsideRepository { repository_path } = &self.policy {
+            let repository_path =
+                fs::canonicalize(repository_path).unwrap_or(PathBuf::from(repository_path));
+
+            if !target.starts_with(repository_path) {
+                return None;
+            }
+        }
+
+        // A link to one of its own parents would make the tree infinite
+        let canonical_parent = link
+            .parent()
+            .and_then(|parent| fs::canonicalize(parent).ok());
+
+        if canonical_parent.is_some_and(|parent| parent.starts_with(&target)) {
+            return None;
+        }
+
+        // Already reachable through the root or another link, also catches links pointing back
+        // from a followed directory
+        if !visited.insert(target.clone()) {
+            return None;
+        }
+
+        let outside_roots = !roots.covers(&target);
+
+        self.links.insert(
+            link.to_path_buf(),
+            FollowedLink {
+                target: target.clone(),
+                outside_roots,
+                is_recursive,
+            },
+        );
+
+        if outside_roots { Some(target) } else { None }
+    }
+
+    /// Forgets the links at or under `path`. Returns the targets whose watch is no longer needed.
+    pub fn forget(&mut self, path: &Path) -> Vec<PathBuf> {
+        let removed_links: Vec<PathBuf> = self
+            .links
+            .keys()
+            .filter(|link| link.starts_with(path))
+            .cloned()
+            .collect();
+
+        removed_links
+            .into_iter()
+            .filter_map(|link| self.links.remove(&link))
+            .filter(|link| link.outside_roots)
+            .map(|link| link.target)
+            .collect()
+    }
+
+    /// Targets watched only because of a link, along with whether they are watched recursively.
+    pub fn watched_targets(&self) -> impl Iterator<Item = (&PathBuf, bool)> {
+        self.links
+            .values()
+            .filter(|link| link.outside_roots)
+            .map(|link| (&link.target, link.is_recursive))
+    }
+
+    /// Adds the link paths of the events that happened inside followed targets. Events of targets
+    /// that are only watched because of a link are replaced, as they are outside the roots.
+    pub fn map_to_links(&self, events: Vec<FileChangeEvent>) -> Vec<FileChangeEvent> {
+        if self.links.is_empty() {
+            return events;
+        }
+
+        let mut mapped_events = Vec::with_capacity(events.len());
+
+        for event in events {
+            let path = Path::new(event.path.as_str());
+            let mut keep_original = true;
+
+            for (link, followed) in &self.links {
+                let Ok(relative_path) = path.strip_prefix(&followed.target) else {
+                    continue;
+                };
+
+                if followed.outside_roots {
+                    keep_original = false;
+                }
+
+                mapped_events.push(FileChangeEvent {
+                    path: link.join(relative_path).to_string_lossy().into_owned(),
+                    event_kind: event.event_kind,
+                });
+            }
+
+            if keep_original {
+                mapped_events.push(event);
+            }
+        }
+
+        mapped_events
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::filesystem::WatchBackend;
+    use crate::watched_roots::{WatchPriority, WatchedRoot};
+    use notify::EventKind;
+    use notify::event::{CreateKind, ModifyKind};
+    use std::os::unix::fs::symlink;
+
+    /// Worktree with a `linked` symlink to a directory outside of it, both canonicalized.
+    fn worktree_with_link(name: &str) -> (PathBuf, PathBuf) {
+        let directory =
+            std::env::temp_dir().join(format!("gitnuro-symlinks-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("worktree")).unwrap();
+        fs::create_dir_all(directory.join("outside")).unwrap();
+
+        let directory = fs::canonicalize(directory).unwrap();
+        let worktree = directory.join("worktree");
+        let outside = directory.join("outside");
+        symlink(&outside, worktree.join("linked")).unwrap();
+
+        (worktree, outside)
+    }
+
+    fn roots(worktree: &Path) -> WatchedRoots {
+        let mut roots = WatchedRoots::default();
+        roots.insert(
+            worktree.to_path_buf(),
+            WatchedRoot {
+                is_recursive: false,
+                priority: WatchPriority::Normal,
+                backend: WatchBackend::Native,
+            },
+        );
+        roots
+    }
+
+    fn event(path: &Path, event_kind: EventKind) -> FileChangeEvent {
+        FileChangeEvent {
+            path: path.to_string_lossy().into_owned(),
+            event_kind,
+        }
+    }
+
+    #[test]
+    fn tracks_targets_outside_the_roots_until_forgotten() {
+        let (worktree, outside) = worktree_with_link("tracking");
+        let roots = roots(&worktree);
+        let mut tracking = SymlinkTracking::new(SymlinkPolicy::Follow);
+
+        let targets_to_watch = tracking.discover(&worktree, false, &roots);
+        assert_eq!(targets_to_watch, [outside.as_path()]);
+        assert_eq!(
+            tracking.watched_targets().collect::<Vec<_>>(),
+            [(&outside, false)]
+        );
+
+        // Already followed links don't need another watch
+        assert!(
+            tracking
+                .discover_link(&worktree.join("linked"), false, &roots)
+                .is_empty()
+        );
+
+        let mapped = tracking.map_to_links(vec![
+            event(
+                &outside.join("file.txt"),
+                EventKind::Modify(ModifyKind::Any),
+            ),
+            event(
+                &worktree.join("other.txt"),
+                EventKind::Create(CreateKind::File),
+            ),
+        ]);
+        let mapped_paths: Vec<PathBuf> = mapped
+            .iter()
+            .map(|event| PathBuf::from(&event.path))
+            .collect();
+
+        assert_eq!(
+            mapped_paths,
+            [
+                worktree.join("linked").join("file.txt"),
+                worktree.join("other.txt")
+            ]
+        );
+
+        assert_eq!(tracking.forget(&worktree.join("linked")), [outside]);
+        assert_eq!(tracking.watched_targets().count(), 0);
+        assert!(tracking.forget(&worktree.join("linked")).is_empty());
+    }
+
+    #[test]
+    fn follows_links_only_as_allowed_by_the_policy() {
+        let (worktree, _) = worktree_with_link("policy");
+        let roots = roots(&worktree);
+
+        let mut report_link_only = SymlinkTracking::default();
+        assert!(
+            report_link_only
+                .discover(&worktree, false, &roots)
+                .is_empty()
+        );
+
+        let mut inside_repository = SymlinkTracking::new(SymlinkPolicy::FollowInsideRepository {
+            repository_path: worktree.to_string_lossy().into_owned(),
+        });
+        assert!(
+            inside_repository
+                .discover(&worktree, false, &roots)
+                .is_empty()
+        );
+        assert_eq!(inside_repository.watched_targets().count(), 0);
+    }
+}
@@ -0,0 +1,251 @@
+use std::io::{ErrorKind, Read, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use libssh_rs::RawSocket;
+
+use crate::ssh_error::SshError;
+use crate::{ChannelHolder, Session};
+
+/// Longest wait for either end of the tunnel. libssh can read the data of the channel while the
+/// jump session is used from another thread, in which case its socket isn't readable anymore.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+const BUFFER_SIZE: usize = 32 * 1024;
+
+#[cfg(unix)]
+pub type TunnelStream = std::os::unix::net::UnixStream;
+
+#[cfg(windows)]
+pub type TunnelStream = std::net::TcpStream;
+
+/// Connected pair of sockets. The first one is meant to be given to libssh with
+/// [libssh_rs::SshOption::Socket], as the session can only run over a socket, and the second one
+/// is the end forwarded by the tunnel.
+#[cfg(unix)]
+pub fn socket_pair() -> std::io::Result<(libssh_rs::RawSocket, TunnelStream)> {
+    let (session_end, tunnel_end) = std::os::unix::net::UnixStream::pair()?;
+
+    Ok((
+        std::os::unix::io::IntoRawFd::into_raw_fd(session_end),
+        tunnel_end,
+    ))
+}
+
+/// Windows has no socketpair, so both ends are connected through the loopback interface. Only
+/// the connection coming from the session end is accepted.
+#[cfg(windows)]
+pub fn socket_pair() -> std::io::Result<(libssh_rs::RawSocket, TunnelStream)> {
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let session_end = TcpStream::connect(listener.local_addr()?)?;
+
+    loop {
+        let (tunnel_end, address) = listener.accept()?;
+
+        if address == session_end.local_addr()? {
+            let session_end = crate::session_options::into_raw_socket(session_end);
+
+            return Ok((session_end, tunnel_end));
+        }
+    }
+}
+
+/// Forwards the data between `stream` and a direct-tcpip channel open on `jump_session` from its
+/// own thread, until either of them is closed. libssh sessions aren't thread safe, so every use of
+/// the channel holds the lock of the jump session.
+pub fn forward_channel(
+    jump_session: Arc<Session>,
+    channel_holder: ChannelHolder,
+    stream: TunnelStream,
+) -> Result<(), SshError> {
+    let session_socket = {
+        let session_holder = jump_session.session_holder.as_ref().unwrap();
+        let session = session_holder.session.write()?;
+
+        session.get_fd().ok_or_else(|| SshError::Io {
+            reason: "The jump session is not connected".to_string(),
+        })?
+    };
+
+    thread::spawn(move || forward(&jump_session, channel_holder, stream, session_socket));
+
+    Ok(())
+}
+
+fn forward(
+    jump_session: &Session,
+    channel_holder: ChannelHolder,
+    stream: TunnelStream,
+    session_socket: RawSocket,
+) {
+    let session_holder = jump_session.session_holder.as_ref().unwrap();
+    let channel = channel_holder
+        .channel
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner());
+
+    // On errors the tunnel is closed, which the session running over it reports as a lost
+    // connection
+    let _ = pump_channel(jump_session, &channel, stream, session_socket);
+
+    // The channel is closed and freed by libssh when dropped
+    let _session = session_holder.session.write();
+    drop(channel);
+}
+
+fn pump_channel(
+    jump_session: &Session,
+    channel: &libssh_rs::Channel,
+    mut stream: TunnelStream,
+    session_socket: RawSocket,
+) -> Result<(), SshError> {
+    let session_holder = jump_session.session_holder.as_ref().unwrap();
+    let stream_socket = raw_socket(&stream);
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    loop {
+        // Reading packets of the jump session for other channels may have buffered data of this
+        // one, so the channel is drained before waiting on its socket.
+        {
+            let _session = session_holder.session.write()?;
+
+            loop {
+                let count = channel.read_timeout(&mut buffer, false, Some(Duration::ZERO))?;
+
+                if count == 0 {
+                    break;
+                }
+
+                stream.write_all(&buffer[..count])?;
+            }
+
+            if channel.is_eof() {
+                return Ok(());
+            }
+        }
+
+        let [stream_readable, _] = wait_readable([stream_socket, session_socket], IDLE_TIMEOUT)?;
+
+        if !stream_readable {
+            continue;
+        }
+
+        match stream.read(&mut buffer) {
+            // The session has been disconnected
+            Ok(0) => {
+                let _session = session_holder.session.write()?;
+                return Ok(channel.send_eof()?);
+            }
+            Ok(count) => {
+                let _session = session_holder.session.write()?;
+                channel.stdin().write_all(&buffer[..count])?;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn raw_socket(stream: &TunnelStream) -> RawSocket {
+    std::os::unix::io::AsRawFd::as_raw_fd(stream)
+}
+
+#[cfg(windows)]
+fn raw_socket(stream: &TunnelStream) -> RawSocket {
+    std::os::windows::io::AsRawSocket::as_raw_socket(stream)
+}
+
+/// Waits until any of `sockets` can be read, or is closed, and returns which ones.
+#[cfg(unix)]
+fn wait_readable<const N: usize>(
+    sockets: [RawSocket; N],
+    timeout: Duration,
+) -> std::io::Result<[bool; N]> {
+    let mut fds = sockets.map(|fd| libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    });
+
+    let result = unsafe {
+        libc::poll(
+            fds.as_mut_ptr(),
+            fds.len() as libc::nfds_t,
+            timeout.as_millis() as libc::c_int,
+        )
+    };
+
+    if result < 0 {
+        let error = std::io::Error::last_os_error();
+
+        return if error.kind() == ErrorKind::Interrupted {
+            Ok([false; N])
+        } else {
+            Err(error)
+        };
+    }
+
+    Ok(fds.map(|fd| fd.revents != 0))
+}
+
+#[cfg(windows)]
+fn wait_readable<const N: usize>(
+    sockets: [RawSocket; N],
+    timeout: Duration,
+) -> std::io::Result<[bool; N]> {
+    use windows_sys::Win32::Networking::WinSock::{POLLRDNORM, SOCKET_ERROR, WSAPOLLFD, WSAPoll};
+
+    let mut fds = sockets.map(|socket| WSAPOLLFD {
+        fd: socket as usize,
+        events: POLLRDNORM,
+        revents: 0,
+    });
+
+    let result = unsafe {
+        WSAPoll(
+            fds.as_mut_ptr(),
+            fds.len() as u32,
+            timeout.as_millis() as i32,
+        )
+    };
+
+    if result == SOCKET_ERROR {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(fds.map(|fd| fd.revents != 0))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_readable_sockets() {
+        let (session_end, mut tunnel_end) = socket_pair().unwrap();
+        let (_, other_end) = socket_pair().unwrap();
+        let sockets = [raw_socket(&tunnel_end), raw_socket(&other_end)];
+
+        let readable = wait_readable(sockets, Duration::from_millis(10)).unwrap();
+        assert_eq!(readable, [false, false]);
+
+        let mut session_end =
+            unsafe { <TunnelStream as std::os::unix::io::FromRawFd>::from_raw_fd(session_end) };
+        session_end.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+
+        let readable = wait_readable(sockets, IDLE_TIMEOUT).unwrap();
+        assert_eq!(readable, [true, false]);
+
+        let mut buffer = [0; 64];
+        let count = tunnel_end.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"SSH-2.0-OpenSSH_9.6\r\n");
+
+        // Closing the other end is reported as readable too
+        drop(session_end);
+        let readable = wait_readable(sockets, IDLE_TIMEOUT).unwrap();
+        assert_eq!(readable, [true, false]);
+    }
+}
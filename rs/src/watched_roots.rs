@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::filesystem::WatchBackend;
+
+#[derive(uniffi::Enum, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum WatchPriority {
+    /// Changes are batched with the rest of the repository changes.
+    Normal,
+    /// Changes bypass the batching window and are sent after a short debounce. Meant for the git
+    /// directory, where refs and HEAD updates should be reflected in the log immediately.
+    High,
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchedRoot {
+    pub is_recursive: bool,
+    pub priority: WatchPriority,
+    pub backend: WatchBackend,
+}
+
+/// Paths added to the watcher and how their events should be treated.
+#[derive(Debug, Default)]
+pub struct WatchedRoots {
+    roots: HashMap<PathBuf, WatchedRoot>,
+}
+
+impl WatchedRoots {
+    pub fn insert(&mut self, path: PathBuf, root: WatchedRoot) {
+        self.roots.insert(path, root);
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<WatchedRoot> {
+        self.roots.remove(path)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&WatchedRoot> {
+        self.roots.get(path)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&PathBuf, &WatchedRoot)> {
+        self.roots.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Whether changes to the path are already received through one of the roots.
+    pub fn covers(&self, path: &Path) -> bool {
+        self.roots.iter().any(|(root_path, root)| {
+            root_path == path || (root.is_recursive && path.starts_with(root_path))
+        })
+    }
+
+    /// Priority of the most specific root containing the path. Paths outside every root are
+    /// treated as [WatchPriority::Normal].
+    pub fn priority_for(&self, path: &Path) -> WatchPriority {
+        self.root_containing(path)
+            .map(|root| root.priority)
+            .unwrap_or(WatchPriority::Normal)
+    }
+
+    /// Most specific root containing the path.
+    pub fn root_containing(&self, path: &Path) -> Option<&WatchedRoot> {
+        self.roots
+            .iter()
+            .filter(|(root_path, _)| path.starts_with(root_path))
+            .max_by_key(|(root_path, _)| root_path.components().count())
+            .map(|(_, root)| root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roots() -> WatchedRoots {
+        let mut roots = WatchedRoots::default();
+
+        for (path, is_recursive, priority) in [
+            ("/repo", false, WatchPriority::Normal),
+            ("/repo/.git", false, WatchPriority::High),
+            ("/repo/.git/refs", true, WatchPriority::High),
+            ("/repo/.git/modules", true, WatchPriority::Normal),
+        ] {
+            roots.insert(
+                PathBuf::from(path),
+                WatchedRoot {
+                    is_recursive,
+                    priority,
+                    backend: WatchBackend::Native,
+                },
+            );
+        }
+
+        roots
+    }
+
+    #[test]
+    fn priority_comes_from_the_most_specific_root() {
+        let roots = roots();
+
+        let priority_for = |path: &str| roots.priority_for(Path::new(path));
+
+        assert_eq!(priority_for("/repo/src/main.rs"), WatchPriority::Normal);
+        assert_eq!(priority_for("/repo/.git/HEAD"), WatchPriority::High);
+        assert_eq!(
+            priority_for("/repo/.git/refs/heads/main"),
+            WatchPriority::High
+        );
+        assert_eq!(
+            priority_for("/repo/.git/modules/lib/HEAD"),
+            WatchPriority::Normal
+        );
+        assert_eq!(priority_for("/other/file"), WatchPriority::Normal);
+    }
+
+    #[test]
+    fn finds_the_root_containing_a_path() {
+        let roots = roots();
+
+        let refs_root = roots
+            .root_containing(Path::new("/repo/.git/refs/heads/main"))
+            .unwrap();
+        assert!(refs_root.is_recursive);
+
+        let git_dir_root = roots.root_containing(Path::new("/repo/.git")).unwrap();
+        assert!(!git_dir_root.is_recursive);
+        assert_eq!(git_dir_root.priority, WatchPriority::High);
+
+        assert!(roots.root_containing(Path::new("/repository")).is_none());
+    }
+
+    #[test]
+    fn only_recursive_roots_cover_their_descendants() {
+        let roots = roots();
+
+        assert!(roots.covers(Path::new("/repo")));
+        assert!(roots.covers(Path::new("/repo/.git/refs/heads")));
+        assert!(!roots.covers(Path::new("/repo/src")));
+        assert!(!roots.covers(Path::new("/other")));
+    }
+}